@@ -55,6 +55,7 @@ interface OnboardedNode {
   kind: StateKind.Onboarded;
   process: childProcess.ChildProcess;
   authToken: AuthToken;
+  csrfToken: string;
   peerAddress: PeerAddress;
   peerId: PeerId;
 }
@@ -78,6 +79,14 @@ class Node {
     return this.state.authToken;
   }
 
+  get csrfToken(): string {
+    if (this.state.kind !== StateKind.Onboarded) {
+      throw new Error("Can't get csrfToken before node is onboarded");
+    }
+
+    return this.state.csrfToken;
+  }
+
   get peerAddress(): PeerAddress {
     if (this.state.kind !== StateKind.Onboarded) {
       throw new Error("Can't get peerAddress before node is onboarded");
@@ -117,6 +126,8 @@ class Node {
       this.proxyBinaryPath,
       [
         "--test",
+        "--allowed-origin",
+        "http://localhost:17299",
         "--http-listen",
         `${HOST}:${this.httpPort}`,
         "--peer-listen",
@@ -171,6 +182,11 @@ class Node {
       throw new Error("Response did not contain an auth cookie");
     }
 
+    const csrfToken = keystoreResponse.headers.get("x-csrf-token");
+    if (!csrfToken) {
+      throw new Error("Response did not contain a CSRF token");
+    }
+
     const match = cookie.match(/auth-token=(.*);/);
     let authToken;
     if (match && match[1]) {
@@ -191,6 +207,7 @@ class Node {
         headers: {
           Cookie: `auth-token=${authToken}`,
           "Content-Type": "application/json",
+          "x-csrf-token": csrfToken,
         },
       }
     );
@@ -200,6 +217,7 @@ class Node {
       ...this.state,
       kind: StateKind.Onboarded,
       authToken: authToken,
+      csrfToken: csrfToken,
       peerAddress: `${json.peerId}@${HOST}:${this.peerPort}`,
       peerId: json.peerId,
     };
@@ -302,6 +320,7 @@ class NodeManager {
       headers: {
         Cookie: `auth-token=${firstNode.authToken}`,
         "Content-Type": "application/json",
+        "x-csrf-token": firstNode.csrfToken,
       },
     });
 
@@ -59,7 +59,7 @@ export const createProjectWithFixture = (
   defaultBranch = "master",
   fakePeers: string[] = []
 ): Cypress.Chainable<void> =>
-  requestOk({
+  requestWithCsrfTokenOk({
     url: "http://localhost:17246/v1/control/create-project",
     method: "POST",
     headers: {
@@ -86,7 +86,7 @@ export const onboardUser = (
       passphrase: "radicle-upstream",
     }),
   });
-  return requestOk({
+  return requestWithCsrfTokenOk({
     url: "http://localhost:17246/v1/identities",
     method: "POST",
     headers: {
@@ -115,3 +115,21 @@ function requestOk(
     })
     .wrap(undefined);
 }
+
+/**
+ * Like `requestOk`, but sends the CSRF token of the session the auth cookie
+ * belongs to, which the proxy requires for mutating requests.
+ */
+function requestWithCsrfTokenOk(
+  opts: Partial<Cypress.RequestOptions> & { url: string }
+): Cypress.Chainable<void> {
+  return cy
+    .request("http://localhost:17246/v1/keystore/csrf")
+    .then(response => {
+      requestOk({
+        ...opts,
+        headers: { ...opts.headers, "x-csrf-token": response.body.token },
+      });
+    })
+    .wrap(undefined);
+}
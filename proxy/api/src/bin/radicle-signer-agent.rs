@@ -0,0 +1,35 @@
+//! Reference agent for `radicle-proxy --signer-socket`, which signs with the key from the keystore
+//! in `RAD_HOME`. The agent starts locked, the proxy unlocks it with the passphrase of the key when
+//! its keystore is unsealed.
+
+use std::path::PathBuf;
+
+use argh::FromArgs;
+
+use coco::keystore::Keystore as _;
+
+/// Serve signing requests for the key stored in `RAD_HOME` on a Unix domain socket.
+#[derive(FromArgs)]
+struct Args {
+    /// path of the Unix domain socket to listen on, only accessible by the current user
+    #[argh(option)]
+    socket: PathBuf,
+}
+
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    api::env::set_if_unset("RUST_LOG", "info");
+    pretty_env_logger::init();
+
+    let args: Args = argh::from_env();
+    let keystore = coco::keystore::file(coco::Paths::from_env()?);
+
+    let listener = coco::signer::agent::bind(&args.socket)?;
+    log::info!("listening on '{}'", args.socket.display());
+    coco::signer::agent::serve(listener, move |passphrase| {
+        keystore.get(passphrase).map_err(|err| err.to_string())
+    })
+    .await?;
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
 //! Datastructure and machinery to safely share the common dependencies across components.
 
-use std::{net, sync::Arc};
+use std::{
+    net,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
 
 use data_encoding::HEXLOWER;
 use rand::Rng as _;
@@ -8,11 +13,15 @@ use tokio::sync::RwLock;
 
 use coco::PeerControl;
 
-use crate::service;
+use crate::{metrics, service};
 
 #[cfg(test)]
 use coco::{signer, RunConfig};
 
+/// Auth token of the session of [`Unsealed::tmp`].
+#[cfg(test)]
+pub const TEST_AUTH_TOKEN: &str = "test-auth-token";
+
 /// Container to pass down dependencies into HTTP filter chains.
 #[derive(Clone)]
 #[allow(clippy::large_enum_variant)]
@@ -48,6 +57,21 @@ impl Context {
         }
     }
 
+    /// Returns the origins from which requests to the HTTP API are allowed.
+    pub fn allowed_origins(&self) -> &[String] {
+        match self {
+            Self::Sealed(sealed) => &sealed.allowed_origins,
+            Self::Unsealed(unsealed) => &unsealed.allowed_origins,
+        }
+    }
+
+    /// Returns `true` if requests from `origin` to the HTTP API are allowed.
+    pub fn is_origin_allowed(&self, origin: &str) -> bool {
+        self.allowed_origins()
+            .iter()
+            .any(|allowed| allowed == origin)
+    }
+
     /// Returns the [`kv::Store`] for persistent storage.
     pub const fn store(&self) -> &kv::Store {
         match self {
@@ -56,6 +80,23 @@ impl Context {
         }
     }
 
+    /// Returns the socket of the agent signing for the peer, `None` if the key is taken from the
+    /// key store.
+    pub fn signer_socket(&self) -> Option<&PathBuf> {
+        match self {
+            Self::Sealed(sealed) => sealed.signer_socket.as_ref(),
+            Self::Unsealed(unsealed) => unsealed.signer_socket.as_ref(),
+        }
+    }
+
+    /// Returns the directory of the helper binaries, which contains the git remote helper.
+    pub fn bin_dir(&self) -> &Path {
+        match self {
+            Self::Sealed(sealed) => &sealed.bin_dir,
+            Self::Unsealed(unsealed) => &unsealed.bin_dir,
+        }
+    }
+
     /// Returns a mutable reference to the authentication cookie value.
     pub fn auth_token(&self) -> Arc<RwLock<Option<String>>> {
         match self {
@@ -64,6 +105,14 @@ impl Context {
         }
     }
 
+    /// Returns the [`metrics::Metrics`] collected over the lifetime of the process.
+    pub const fn metrics(&self) -> &metrics::Metrics {
+        match self {
+            Self::Sealed(sealed) => &sealed.metrics,
+            Self::Unsealed(unsealed) => &unsealed.metrics,
+        }
+    }
+
     /// Returns a handle to control the service configuration
     pub fn service_handle(&mut self) -> &mut service::Handle {
         match self {
@@ -72,22 +121,45 @@ impl Context {
         }
     }
 
-    /// Unseal the key store and restart the coco service with the obtained key. Returns the auth
-    /// token required to access the keystore.
+    /// Unseal the key store and restart the coco service with the obtained key. If signing is
+    /// delegated to an agent, the agent is unlocked instead. Returns the auth token required to
+    /// access the keystore.
     ///
     /// # Errors
     ///
     /// * Errors if the passphrase is wrong.
+    /// * Errors if the signing agent can't be reached.
+    /// * Errors if too many wrong passphrases were tried recently, see [`crate::keystore`].
     /// * Errors if backend fails to retrieve the data.
     /// * Errors if there is no key in the storage yet.
     pub async fn unseal_keystore(
         &mut self,
         passphrase: coco::keystore::SecUtf8,
     ) -> Result<String, crate::error::Error> {
+        crate::keystore::attempt(self.store())?;
+
+        if let Some(socket) = self.signer_socket().cloned() {
+            let unlocked = match coco::signer::agent::Agent::unlock(&socket, passphrase).await {
+                Ok(()) => Ok(()),
+                Err(coco::signer::agent::Error::Agent(reason)) => {
+                    log::warn!("signing agent refused to unlock: {}", reason);
+                    Err(crate::error::Error::WrongPassphrase)
+                },
+                Err(err) => Err(err.into()),
+            };
+            crate::keystore::settle(self.store(), unlocked)?;
+            let agent = coco::signer::agent::Agent::connect(&socket).await?;
+            self.service_handle().set_agent(agent);
+            let auth_token = self.reset_auth_token().await;
+            return Ok(auth_token);
+        }
+
         let keystore = self.keystore();
         let key = tokio::task::spawn_blocking(move || keystore.get(passphrase))
             .await
-            .expect("Task to unseal key was aborted")?;
+            .expect("Task to unseal key was aborted")
+            .map_err(crate::error::Error::from);
+        let key = crate::keystore::settle(self.store(), key)?;
         self.service_handle().set_secret_key(key);
         let auth_token = self.reset_auth_token().await;
         Ok(auth_token)
@@ -98,11 +170,16 @@ impl Context {
     ///
     /// # Errors
     ///
-    /// Errors when the storage backend fails to persist the key or a key already exists.
+    /// * Errors if signing is delegated to an agent, which holds the key instead.
+    /// * Errors when the storage backend fails to persist the key or a key already exists.
     pub async fn create_key(
         &mut self,
         passphrase: coco::keystore::SecUtf8,
     ) -> Result<String, crate::error::Error> {
+        if self.signer_socket().is_some() {
+            return Err(crate::error::Error::KeyManagedByAgent);
+        }
+
         let keystore = self.keystore();
         let key = tokio::task::spawn_blocking(move || keystore.create_key(passphrase))
             .await
@@ -112,7 +189,49 @@ impl Context {
         Ok(auth_token)
     }
 
-    fn keystore(&self) -> Arc<dyn coco::keystore::Keystore + Sync + Send> {
+    /// Re-encrypt the key in the key store with the `new` passphrase.
+    ///
+    /// # Errors
+    ///
+    /// * Errors if signing is delegated to an agent, which holds the key instead.
+    /// * Errors if the `old` passphrase is wrong.
+    /// * Errors if too many wrong passphrases were tried recently, see [`crate::keystore`].
+    /// * Errors if there is no key in the storage yet.
+    /// * Errors if the backend fails to persist the re-encrypted key.
+    pub async fn change_passphrase(
+        &self,
+        old: coco::keystore::SecUtf8,
+        new: coco::keystore::SecUtf8,
+    ) -> Result<(), crate::error::Error> {
+        if self.signer_socket().is_some() {
+            return Err(crate::error::Error::KeyManagedByAgent);
+        }
+
+        crate::keystore::attempt(self.store())?;
+
+        let keystore = self.keystore();
+        let changed = tokio::task::spawn_blocking(move || keystore.change_passphrase(old, new))
+            .await
+            .expect("Task to change passphrase was aborted")
+            .map_err(crate::error::Error::from);
+        crate::keystore::settle(self.store(), changed)
+    }
+
+    /// Seal the key store by restarting the coco service without the key and invalidate the auth
+    /// token. If signing is delegated to an agent, the agent is locked, so the peer can't be
+    /// started again before the key store is unsealed.
+    pub async fn seal(&mut self) {
+        *self.auth_token().write().await = None;
+        if let Some(socket) = self.signer_socket() {
+            if let Err(err) = coco::signer::agent::Agent::lock(socket).await {
+                log::error!("Failed to lock the signing agent: {}", err);
+            }
+        }
+        self.service_handle().seal();
+    }
+
+    /// Returns the key store holding the key of the peer.
+    pub fn keystore(&self) -> Arc<dyn coco::keystore::Keystore + Sync + Send> {
         match self {
             Self::Sealed(sealed) => sealed.keystore.clone(),
             Self::Unsealed(unsealed) => unsealed.keystore.clone(),
@@ -129,12 +248,40 @@ impl Context {
         new_token
     }
 
-    /// Returns `true` if `token` matches the stored authentication token.
+    /// Returns `true` if `token` matches the stored authentication token. Without a session no
+    /// token matches.
     pub async fn check_auth_token(&self, token: Option<String>) -> bool {
-        token == *self.auth_token().read().await
+        matches!(
+            (token, &*self.auth_token().read().await),
+            (Some(token), Some(auth_token)) if token == *auth_token
+        )
+    }
+
+    /// Returns the CSRF token of the current session, which is derived from the authentication
+    /// token. `None` if there is no session.
+    pub async fn csrf_token(&self) -> Option<String> {
+        self.auth_token()
+            .read()
+            .await
+            .as_ref()
+            .map(|auth_token| csrf_token(auth_token))
+    }
+
+    /// Returns `true` if `token` matches the CSRF token of the current session. Without a session
+    /// no token matches.
+    pub async fn check_csrf_token(&self, token: Option<String>) -> bool {
+        match (token, self.csrf_token().await) {
+            (Some(token), Some(csrf_token)) => token == csrf_token,
+            _ => false,
+        }
     }
 }
 
+/// Derive the CSRF token of the session authenticated with `auth_token`.
+pub fn csrf_token(auth_token: &str) -> String {
+    coco::Hash::hash(format!("csrf:{}", auth_token).as_bytes()).to_string()
+}
+
 impl From<Unsealed> for Context {
     fn from(unsealed: Unsealed) -> Self {
         Self::Unsealed(unsealed)
@@ -162,12 +309,22 @@ pub struct Unsealed {
     pub http_listen: net::SocketAddr,
     /// Default seeds that will be written to the settings kv store.
     pub default_seeds: Vec<String>,
+    /// Origins which are allowed to make requests to the HTTP API.
+    pub allowed_origins: Vec<String>,
     /// Handle to control the service configuration.
     pub service_handle: service::Handle,
     /// Cookie set on unsealing the key store.
     pub auth_token: Arc<RwLock<Option<String>>>,
     /// Reference to the key store.
     pub keystore: Arc<dyn coco::keystore::Keystore + Send + Sync>,
+    /// Socket of the agent signing for the peer, if the key isn't taken from the key store.
+    pub signer_socket: Option<PathBuf>,
+    /// Directory of the helper binaries, which contains the git remote helper.
+    pub bin_dir: PathBuf,
+    /// Metrics about the peer and the HTTP API.
+    pub metrics: metrics::Metrics,
+    /// Time of the last authenticated request, to seal the key store when idle.
+    pub last_activity: Arc<RwLock<Instant>>,
 }
 
 /// Context for HTTP request if the coco peer APIs have not been initialized yet.
@@ -181,12 +338,20 @@ pub struct Sealed {
     pub http_listen: net::SocketAddr,
     /// Default seeds that will be written to the settings kv store.
     pub default_seeds: Vec<String>,
+    /// Origins which are allowed to make requests to the HTTP API.
+    pub allowed_origins: Vec<String>,
     /// Handle to control the service configuration.
     pub service_handle: service::Handle,
     /// Cookie set on unsealing the key store.
     pub auth_token: Arc<RwLock<Option<String>>>,
     /// Reference to the key store.
     pub keystore: Arc<dyn coco::keystore::Keystore + Send + Sync>,
+    /// Socket of the agent signing for the peer, if the key isn't taken from the key store.
+    pub signer_socket: Option<PathBuf>,
+    /// Directory of the helper binaries, which contains the git remote helper.
+    pub bin_dir: PathBuf,
+    /// Metrics about the peer and the HTTP API.
+    pub metrics: metrics::Metrics,
 }
 
 impl Unsealed {
@@ -199,13 +364,27 @@ impl Unsealed {
     /// * creation of the [`kv::Store`] fails
     #[cfg(test)]
     pub async fn tmp(tmp_dir: &tempfile::TempDir) -> Result<Self, crate::error::Error> {
+        Self::tmp_with_key(tmp_dir, coco::keys::SecretKey::new()).await
+    }
+
+    /// Initialises a new [`Unsealed`] context like [`Unsealed::tmp`], with the peer running with
+    /// `key`.
+    ///
+    /// # Errors
+    ///
+    /// * creation of the [`kv::Store`] fails
+    /// * the peer can't be set up
+    #[cfg(test)]
+    pub async fn tmp_with_key(
+        tmp_dir: &tempfile::TempDir,
+        key: coco::keys::SecretKey,
+    ) -> Result<Self, crate::error::Error> {
         let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
 
-        let key = coco::keys::SecretKey::new();
         let signer = signer::BoxedSigner::from(signer::SomeSigner { signer: key });
 
         let (peer_control, state) = {
-            let config = coco::config::default(key, tmp_dir.path())?;
+            let config = coco::config::default(signer.clone(), tmp_dir.path())?;
             let (peer, state) =
                 coco::into_peer_state(config, signer.clone(), store.clone(), RunConfig::default())
                     .await?;
@@ -223,9 +402,41 @@ impl Unsealed {
             test: false,
             http_listen: "127.0.0.1:17246".parse().expect("Couln't parse address"),
             default_seeds: vec![],
+            allowed_origins: vec!["file://".to_string()],
+            service_handle: service::Handle::dummy(),
+            auth_token: Arc::new(RwLock::new(Some(TEST_AUTH_TOKEN.to_string()))),
+            keystore: Arc::new(coco::keystore::memory()),
+            signer_socket: None,
+            bin_dir: tmp_dir.path().join("bin"),
+            metrics: metrics::Metrics::default(),
+            last_activity: Arc::new(RwLock::new(Instant::now())),
+        })
+    }
+}
+
+impl Sealed {
+    /// Initialises a new [`Sealed`] context with the store in the given temporary directory and an
+    /// empty in-memory key store.
+    ///
+    /// # Errors
+    ///
+    /// * creation of the [`kv::Store`] fails
+    #[cfg(test)]
+    pub fn tmp(tmp_dir: &tempfile::TempDir) -> Result<Self, crate::error::Error> {
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        Ok(Self {
+            store,
+            test: false,
+            http_listen: "127.0.0.1:17246".parse().expect("Couln't parse address"),
+            default_seeds: vec![],
+            allowed_origins: vec!["file://".to_string()],
             service_handle: service::Handle::dummy(),
             auth_token: Arc::new(RwLock::new(None)),
             keystore: Arc::new(coco::keystore::memory()),
+            signer_socket: None,
+            bin_dir: tmp_dir.path().join("bin"),
+            metrics: metrics::Metrics::default(),
         })
     }
 }
@@ -9,6 +9,18 @@ pub enum Error {
     #[error(transparent)]
     Keystore(#[from] coco::keystore::Error),
 
+    /// Talking to the signing agent failed.
+    #[error(transparent)]
+    Agent(#[from] coco::signer::agent::Error),
+
+    /// Scanning for repositories to import failed.
+    #[error(transparent)]
+    Import(#[from] coco::project::import::Error),
+
+    /// A migration bundle could not be created or restored.
+    #[error(transparent)]
+    Migration(#[from] crate::migration::Error),
+
     /// Error interacting with [`coco::state::State`].
     #[error(transparent)]
     State(#[from] coco::state::Error),
@@ -25,14 +37,65 @@ pub enum Error {
     #[error("Keystore is sealed")]
     KeystoreSealed,
 
+    /// The key store is unsealed, but the operation requires it to be sealed.
+    #[error("Keystore is unsealed")]
+    KeystoreUnsealed,
+
+    /// The key is held by the signing agent, so the proxy can't create or re-encrypt it.
+    #[error("The key is managed by the signing agent")]
+    KeyManagedByAgent,
+
     /// The passphrase was incorrect
     #[error("Passphrase incorrect")]
     WrongPassphrase,
 
+    /// Too many wrong passphrases were tried to unseal the keystore.
+    #[error("Too many failed attempts, retry in {retry_after} seconds")]
+    TooManyAttempts {
+        /// Seconds until the next attempt is allowed.
+        retry_after: u64,
+    },
+
+    /// An identity other than the one of the current session was to be edited.
+    #[error("Only the identity of the current session can be edited")]
+    NotSessionIdentity,
+
     /// The request auth token differs from the one in context
     #[error("Invalid authentication token")]
     InvalidAuthCookie,
 
+    /// A request authenticated with the auth cookie lacks the CSRF token of the session.
+    #[error("Invalid CSRF token")]
+    InvalidCsrfToken,
+
+    /// The API token passed in the `Authorization` header is unknown or malformed.
+    #[error("Invalid API token")]
+    InvalidApiToken,
+
+    /// The API token passed in the `Authorization` header doesn't grant access to the endpoint.
+    #[error("The API token does not grant access to this endpoint")]
+    InsufficientScope,
+
+    /// An API token with the same name exists already.
+    #[error("An API token named '{0}' exists already")]
+    TokenExists(String),
+
+    /// There is no API token with the given name.
+    #[error("There is no API token named '{0}'")]
+    TokenNotFound(String),
+
+    /// A webhook doesn't point at a local HTTP endpoint.
+    #[error("Webhook '{0}' is not served over HTTP on a loopback address")]
+    InvalidWebhook(String),
+
+    /// A new webhook was registered without a secret to sign its payloads with.
+    #[error("Webhook '{0}' has no secret")]
+    MissingWebhookSecret(String),
+
+    /// There is no notification with the given id in the inbox.
+    #[error("There is no notification with id {0}")]
+    NotificationNotFound(u64),
+
     /// Errors stemming from [`coco::request::waiting_room::WaitingRoom`] interactions.
     #[error(transparent)]
     WaitingRoom(#[from] coco::request::waiting_room::Error),
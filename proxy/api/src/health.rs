@@ -0,0 +1,159 @@
+//! Health checks of the subsystems the proxy depends on, to tell if it is working and why not.
+
+use std::{
+    os::unix::fs::PermissionsExt as _,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{context, session};
+
+/// Time to wait for the peer to answer before it is considered unresponsive.
+const PEER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Health of the proxy and its subsystems.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Health {
+    /// `true` if all subsystems the proxy needs in its current state are working. A sealed
+    /// keystore is healthy, as the peer only runs once it is unsealed.
+    pub healthy: bool,
+    /// `true` if the proxy is healthy and the keystore is unsealed, so it is ready to serve all
+    /// endpoints.
+    pub ready: bool,
+    /// State of the keystore.
+    pub keystore: Keystore,
+    /// State of the peer.
+    pub peer: Peer,
+    /// Access to the monorepo, `None` while the keystore is sealed.
+    pub monorepo: Option<Check>,
+    /// Access to the [`kv::Store`].
+    pub store: Check,
+    /// Presence of the git remote helper installed by [`coco::git_helper::setup`].
+    pub git_helper: Check,
+}
+
+/// State of the keystore.
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(rename = "KeystoreHealth")]
+#[serde(rename_all = "camelCase")]
+pub struct Keystore {
+    /// `true` if the keystore is sealed.
+    pub sealed: bool,
+}
+
+/// State of the peer.
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(rename = "PeerHealth")]
+#[serde(rename_all = "camelCase")]
+pub struct Peer {
+    /// `true` if the peer is running and responsive.
+    pub running: bool,
+    /// Current status of the peer, if it is running.
+    pub status: Option<coco::PeerStatus>,
+    /// Time the peer entered its current status, in seconds since the UNIX epoch.
+    pub status_since: Option<u64>,
+}
+
+/// Outcome of a single health check.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Check {
+    /// `true` if the check passed.
+    pub ok: bool,
+    /// Reason the check failed.
+    pub error: Option<String>,
+}
+
+impl<E: std::fmt::Display> From<Result<(), E>> for Check {
+    fn from(result: Result<(), E>) -> Self {
+        match result {
+            Ok(()) => Self {
+                ok: true,
+                error: None,
+            },
+            Err(err) => Self {
+                ok: false,
+                error: Some(err.to_string()),
+            },
+        }
+    }
+}
+
+/// Check the health of all subsystems available in `ctx`.
+pub async fn check(ctx: &context::Context) -> Health {
+    let store = Check::from(session::get_current(ctx.store()).map(|_| ()));
+    let git_helper = Check::from(check_git_helper(ctx.bin_dir()));
+
+    let (sealed, peer, monorepo) = match ctx {
+        context::Context::Sealed(_) => (
+            true,
+            Peer {
+                running: false,
+                status: None,
+                status_since: None,
+            },
+            None,
+        ),
+        context::Context::Unsealed(unsealed) => {
+            let mut peer_control = unsealed.peer_control.clone();
+            // A peer which is gone or doesn't answer in time is not running.
+            let answer = tokio::time::timeout(PEER_TIMEOUT, async {
+                let status = peer_control.try_current_status().await?;
+                let since = peer_control.try_status_since().await?;
+                Some((status, since))
+            })
+            .await
+            .ok()
+            .flatten();
+            let peer = answer.map_or(
+                Peer {
+                    running: false,
+                    status: None,
+                    status_since: None,
+                },
+                |(status, since)| Peer {
+                    running: status != coco::PeerStatus::Stopped,
+                    status: Some(status),
+                    status_since: since
+                        .duration_since(UNIX_EPOCH)
+                        .ok()
+                        .map(|duration| duration.as_secs()),
+                },
+            );
+            let monorepo = Check::from(unsealed.state.list_users().await.map(|_| ()));
+
+            (false, peer, Some(monorepo))
+        },
+    };
+
+    let healthy = store.ok
+        && git_helper.ok
+        && (sealed || (peer.running && monorepo.as_ref().map_or(false, |check| check.ok)));
+
+    Health {
+        healthy,
+        ready: healthy && !sealed,
+        keystore: Keystore { sealed },
+        peer,
+        monorepo,
+        store,
+        git_helper,
+    }
+}
+
+/// Check that the git remote helper is present in `bin_dir` and executable.
+fn check_git_helper(bin_dir: &Path) -> Result<(), String> {
+    let path = bin_dir.join(coco::git_helper::GIT_REMOTE_RAD);
+    let metadata = std::fs::metadata(&path)
+        .map_err(|err| format!("{} is missing: {}", path.display(), err))?;
+
+    if metadata.is_file() && metadata.permissions().mode() & 0o111 != 0 {
+        Ok(())
+    } else {
+        Err(format!("{} is not executable", path.display()))
+    }
+}
@@ -1,19 +1,32 @@
 //! HTTP API delivering JSON over `RESTish` endpoints.
 
+use std::{
+    io,
+    os::unix::fs::{FileTypeExt as _, PermissionsExt as _},
+    path::Path,
+};
+
+use schemars::gen::SchemaGenerator;
 use serde::Deserialize;
+use tokio::net::UnixListener;
 use warp::{filters::BoxedFilter, path, reject, Filter, Rejection, Reply};
 
 use crate::{context, notification::Subscriptions};
 
 mod avatar;
 mod control;
+mod doc;
 mod error;
+mod health;
 mod identity;
 mod keystore;
+mod metrics;
 mod notification;
 mod project;
 mod session;
 mod source;
+mod token;
+mod webhook;
 
 /// Helper to combine the multiple filters together with Filter::or, possibly boxing the types in
 /// the process.
@@ -37,6 +50,56 @@ pub fn api(
     ctx: context::Context,
     subscriptions: Subscriptions,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let api = routes(ctx.clone(), subscriptions);
+
+    // `warp::cors` can only express web origins, the other allowed origins, like `file://` of the
+    // app, are answered by `with_app_cors`.
+    let (web_origins, app_origins): (Vec<String>, Vec<String>) = ctx
+        .allowed_origins()
+        .iter()
+        .cloned()
+        .partition(|origin| is_web_origin(origin));
+    let cors = warp::cors()
+        .allow_origins(web_origins.iter().map(String::as_str))
+        .allow_credentials(true)
+        .allow_headers(CORS_HEADERS.iter().copied())
+        .allow_methods(CORS_METHODS.iter().copied())
+        .expose_headers(vec![CSRF_HEADER]);
+    let metrics = ctx.metrics().clone();
+    let log = warp::log::custom(move |info| {
+        metrics.record_request(
+            info.method().as_str(),
+            doc::route(info.method().as_str(), info.path()).unwrap_or("other"),
+            info.status().as_u16(),
+            info.elapsed(),
+        );
+        log::info!(
+            target: "proxy::http",
+            "\"{} {} {:?}\" {} {:?}",
+            info.method(),
+            info.path(),
+            info.version(),
+            info.status().as_u16(),
+            info.elapsed(),
+        );
+    });
+
+    let recovered = api.recover(error::recover);
+
+    // Requests from origins which are not allowed are rejected by `with_allowed_origin` before
+    // they reach the CORS handling or any endpoint.
+    with_allowed_origin(ctx)
+        .and(
+            with_app_cors(app_origins, recovered.clone())
+                .or(recovered.with(cors).map(Reply::into_response))
+                .unify(),
+        )
+        .recover(error::recover)
+        .with(log)
+}
+
+/// Endpoints of the API, without the CORS handling, recovery and logging added by [`api`].
+fn routes(ctx: context::Context, subscriptions: Subscriptions) -> BoxedFilter<(impl Reply,)> {
     let test = ctx.test();
 
     let avatar_filter = path("avatars").and(avatar::get_filter());
@@ -51,51 +114,196 @@ pub fn api(
         })
         .untuple_one()
         .and(control::filters(ctx.clone()));
+    let health_filter = path("health").and(health::filters(ctx.clone()));
     let identity_filter = path("identities").and(identity::filters(ctx.clone()));
     let notification_filter =
-        path("notifications").and(notification::filters(ctx.clone(), subscriptions));
-    let project_filter = path("projects").and(project::filters(ctx.clone()));
+        path("notifications").and(notification::filters(ctx.clone(), subscriptions.clone()));
+    let project_filter = path("projects").and(project::filters(ctx.clone(), subscriptions.clone()));
     let session_filter = path("session").and(session::filters(ctx.clone()));
-    let keystore_filter = path("keystore").and(keystore::filters(ctx.clone()));
-    let source_filter = path("source").and(source::filters(ctx));
-
-    let api = path("v1").and(combine!(
-        avatar_filter,
-        control_filter,
-        identity_filter,
-        notification_filter,
-        project_filter,
-        session_filter,
-        keystore_filter,
-        source_filter
-    ));
+    let keystore_filter = path("keystore").and(keystore::filters(ctx.clone(), subscriptions));
+    let source_filter = path("source").and(source::filters(ctx.clone()));
+    let token_filter = path("tokens").and(token::filters(ctx.clone()));
+    let webhook_filter = path("webhooks").and(webhook::filters(ctx.clone()));
+
+    path("v1")
+        .and(combine!(
+            avatar_filter,
+            control_filter,
+            health_filter,
+            identity_filter,
+            notification_filter,
+            project_filter,
+            session_filter,
+            keystore_filter,
+            source_filter,
+            token_filter,
+            webhook_filter
+        ))
+        .or(path("docs").and(doc::filters()))
+        .or(path("metrics").and(metrics::filters(ctx)))
+        .boxed()
+}
 
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_credentials(true)
-        .allow_headers(&[warp::http::header::CONTENT_TYPE, warp::http::header::COOKIE])
-        .allow_methods(&[
-            warp::http::Method::DELETE,
-            warp::http::Method::GET,
-            warp::http::Method::POST,
-            warp::http::Method::PUT,
-            warp::http::Method::OPTIONS,
-        ]);
-    let log = warp::log::custom(|info| {
-        log::info!(
-            target: "proxy::http",
-            "\"{} {} {:?}\" {} {:?}",
-            info.method(),
-            info.path(),
-            info.version(),
-            info.status().as_u16(),
-            info.elapsed(),
+/// Documentation of the endpoints served by [`routes`], see [`doc`]. The control endpoints, which
+/// are only served in test mode, aren't documented.
+fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    let mounted = vec![
+        ("/v1/avatars", avatar::docs(gen)),
+        ("/v1/health", health::docs(gen)),
+        ("/v1/identities", identity::docs(gen)),
+        ("/v1/notifications", notification::docs(gen)),
+        ("/v1/projects", project::docs(gen)),
+        ("/v1/session", session::docs(gen)),
+        ("/v1/keystore", keystore::docs(gen)),
+        ("/v1/source", source::docs(gen)),
+        ("/v1/tokens", token::docs(gen)),
+        ("/v1/webhooks", webhook::docs(gen)),
+        ("/docs", doc::docs()),
+        ("/metrics", metrics::docs()),
+    ];
+
+    mounted
+        .into_iter()
+        .flat_map(|(prefix, routes)| routes.into_iter().map(move |route| route.mount(prefix)))
+        .collect()
+}
+
+/// Methods allowed in CORS requests.
+const CORS_METHODS: [&str; 5] = ["DELETE", "GET", "POST", "PUT", "OPTIONS"];
+
+/// Headers allowed in CORS requests.
+const CORS_HEADERS: [&str; 4] = ["authorization", "content-type", "cookie", CSRF_HEADER];
+
+/// Returns `true` if `origin` is made up of a scheme and a host, like `http://localhost:8080`,
+/// which is what `warp::cors` requires of allowed origins.
+fn is_web_origin(origin: &str) -> bool {
+    origin.parse::<warp::http::Uri>().map_or(false, |uri| {
+        uri.scheme().is_some()
+            && uri.authority().is_some()
+            && uri.path_and_query().map_or(true, |path| {
+                path.as_str().is_empty() || path.as_str() == "/"
+            })
+    })
+}
+
+/// Answers CORS requests from the allowed `origins` which aren't web origins and so can't be
+/// handled by `warp::cors`, like `file://` of the app. Preflight requests are answered directly,
+/// all other requests are passed to `api` and the CORS headers added to its response.
+fn with_app_cors<F, R>(origins: Vec<String>, api: F) -> BoxedFilter<(warp::reply::Response,)>
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    use warp::http::header::{self, HeaderValue};
+
+    let origins = std::sync::Arc::new(origins);
+    let app_origin = warp::header::<HeaderValue>("origin")
+        .and_then(move |origin: HeaderValue| {
+            let origins = origins.clone();
+            async move {
+                if origins.iter().any(|allowed| origin == allowed.as_str()) {
+                    Ok(origin)
+                } else {
+                    Err(reject::not_found())
+                }
+            }
+        })
+        .boxed();
+    let with_headers = |origin: HeaderValue, mut res: warp::reply::Response| {
+        let headers = res.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+        headers.insert(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            HeaderValue::from_static(CSRF_HEADER),
+        );
+        res
+    };
+
+    let methods = HeaderValue::from_str(&CORS_METHODS.join(", ")).expect("invalid CORS methods");
+    let allowed_headers =
+        HeaderValue::from_str(&CORS_HEADERS.join(", ")).expect("invalid CORS headers");
+    let preflight = warp::options().and(app_origin.clone()).map(move |origin| {
+        let mut res = with_headers(origin, warp::reply().into_response());
+        let headers = res.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, methods.clone());
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            allowed_headers.clone(),
         );
+        res
     });
+    let requests = app_origin
+        .and(api)
+        .map(move |origin, reply: R| with_headers(origin, reply.into_response()));
 
-    let recovered = api.recover(error::recover);
+    preflight.or(requests).unify().boxed()
+}
+
+/// Bind a Unix domain socket at `path` to serve the API on, which is only accessible by the
+/// current user. A stale socket left at `path`, e.g. by a previous run, is replaced, any other file
+/// is left alone.
+///
+/// The socket is bound in a private directory next to `path` and only moved to `path` after its
+/// permissions are restricted, so other users can't connect in between.
+///
+/// # Errors
+///
+/// * Errors with [`io::ErrorKind::AlreadyExists`] if there is a file at `path` which isn't a
+///   socket.
+/// * Errors if the socket can't be bound, its permissions can't be set or it can't be moved to
+///   `path`.
+pub fn bind_socket(path: &Path) -> Result<UnixListener, io::Error> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
 
-    recovered.with(cors).with(log)
+    let parent = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let private_dir = tempfile::Builder::new()
+        .prefix(".radicle-proxy")
+        .tempdir_in(parent)?;
+    std::fs::set_permissions(private_dir.path(), std::fs::Permissions::from_mode(0o700))?;
+
+    let private_path = private_dir.path().join("api.sock");
+    let listener = UnixListener::bind(&private_path)?;
+    std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))?;
+    std::fs::rename(&private_path, path)?;
+
+    Ok(listener)
+}
+
+/// Name of the header carrying the CSRF token of the session, which has to be present on all
+/// `POST`, `PUT` and `DELETE` requests authenticated with the auth cookie.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Rejects requests which carry an `Origin` header not allowed by the context with
+/// [`error::Routing::ForbiddenOrigin`]. Requests without an `Origin` header pass, as they don't
+/// originate from a website.
+fn with_allowed_origin(ctx: context::Context) -> BoxedFilter<()> {
+    with_context(ctx)
+        .and(warp::header::optional::<String>("origin"))
+        .and_then(|ctx: context::Context, origin: Option<String>| async move {
+            match origin {
+                Some(origin) if !ctx.is_origin_allowed(&origin) => {
+                    Err(Rejection::from(error::Routing::ForbiddenOrigin { origin }))
+                },
+                _ => Ok(()),
+            }
+        })
+        .untuple_one()
+        .boxed()
 }
 
 /// Asserts presence of the owner and rejects the request early if missing. Otherwise unpacks and
@@ -128,27 +336,103 @@ fn with_context(ctx: context::Context) -> BoxedFilter<(context::Context,)> {
 
 /// Assert that the context is unsealed and and passes [`context::Unsealed`] to the handler.
 ///
+/// Requests are authenticated either with the auth cookie or with an API token passed as
+/// `Authorization: Bearer <secret>`, which has to grant access to the requested endpoint, see
+/// [`crate::token::Scope`]. Mutating requests authenticated with the cookie also have to carry the
+/// CSRF token of the session in the [`CSRF_HEADER`].
+///
 /// Otherwise the requests rejects with [`crate::error::Error::KeystoreSealed`].
 fn with_context_unsealed(ctx: context::Context) -> BoxedFilter<(context::Unsealed,)> {
     with_context(ctx)
         .and(warp::filters::cookie::optional("auth-token"))
-        .and_then(|ctx: context::Context, token: Option<String>| async move {
-            if !ctx.check_auth_token(token).await {
-                return Err(Rejection::from(crate::error::Error::InvalidAuthCookie));
-            }
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::header::optional::<String>(CSRF_HEADER))
+        .and(warp::method())
+        .and(path::full())
+        .and_then(
+            |ctx: context::Context,
+             token: Option<String>,
+             authorization: Option<String>,
+             csrf_token: Option<String>,
+             method: warp::http::Method,
+             full_path: path::FullPath| async move {
+                if let Some(authorization) = authorization {
+                    let secret = authorization
+                        .strip_prefix("Bearer ")
+                        .ok_or(crate::error::Error::InvalidApiToken)?;
+                    let api_token = crate::token::authorize(ctx.store(), secret)?
+                        .ok_or(crate::error::Error::InvalidApiToken)?;
+                    let segments = full_path
+                        .as_str()
+                        .split('/')
+                        .filter(|segment| !segment.is_empty())
+                        .collect::<Vec<_>>();
+                    let segments = match segments.as_slice() {
+                        ["v1", rest @ ..] => rest,
+                        all => all,
+                    };
+                    if !api_token.allows(&method, segments) {
+                        return Err(Rejection::from(crate::error::Error::InsufficientScope));
+                    }
+                } else if !ctx.check_auth_token(token).await {
+                    return Err(Rejection::from(crate::error::Error::InvalidAuthCookie));
+                } else if is_mutating(&method) && !ctx.check_csrf_token(csrf_token).await {
+                    return Err(Rejection::from(crate::error::Error::InvalidCsrfToken));
+                }
 
-            let unsealed_ctx = match ctx {
-                context::Context::Sealed(_) => {
-                    return Err(Rejection::from(crate::error::Error::KeystoreSealed))
-                },
-                context::Context::Unsealed(unsealed) => unsealed,
-            };
+                let unsealed_ctx = match ctx {
+                    context::Context::Sealed(_) => {
+                        return Err(Rejection::from(crate::error::Error::KeystoreSealed))
+                    },
+                    context::Context::Unsealed(unsealed) => unsealed,
+                };
+                *unsealed_ctx.last_activity.write().await = std::time::Instant::now();
+
+                Ok(unsealed_ctx)
+            },
+        )
+        .boxed()
+}
 
-            Ok(unsealed_ctx)
-        })
+/// Assert that the keystore is sealed and pass [`context::Sealed`] to the handler, for endpoints
+/// which set up the keystore before there is a session.
+///
+/// Without a session there is no CSRF token, instead the request has to be sent as JSON, which
+/// browsers only allow for other origins after a CORS preflight, see [`with_allowed_origin`].
+///
+/// Otherwise the request rejects with [`crate::error::Error::InvalidCsrfToken`] or
+/// [`crate::error::Error::KeystoreUnsealed`].
+fn with_context_sealed(ctx: context::Context) -> BoxedFilter<(context::Sealed,)> {
+    with_context(ctx)
+        .and(warp::header::optional::<String>("content-type"))
+        .and_then(
+            |ctx: context::Context, content_type: Option<String>| async move {
+                let is_json = content_type.map_or(false, |content_type| {
+                    content_type.starts_with("application/json")
+                });
+                if !is_json {
+                    return Err(Rejection::from(crate::error::Error::InvalidCsrfToken));
+                }
+
+                match ctx {
+                    context::Context::Sealed(sealed) => Ok(sealed),
+                    context::Context::Unsealed(_) => {
+                        Err(Rejection::from(crate::error::Error::KeystoreUnsealed))
+                    },
+                }
+            },
+        )
         .boxed()
 }
 
+/// Returns `true` for the methods which modify state and need protection against CSRF.
+fn is_mutating(method: &warp::http::Method) -> bool {
+    matches!(
+        *method,
+        warp::http::Method::POST | warp::http::Method::PUT | warp::http::Method::DELETE
+    )
+}
+
 /// Parses an optional query string with [`serde_qs`] and returns the result.
 ///
 /// If no query string is present (i.e. `?` is not included in the path) `None`
@@ -249,6 +533,14 @@ mod test {
     use serde_json::Value;
     use warp::http::StatusCode;
 
+    /// Request carrying the auth cookie and the CSRF token of the session of
+    /// [`context::Unsealed::tmp`].
+    pub fn request() -> warp::test::RequestBuilder {
+        warp::test::request()
+            .header("cookie", format!("auth-token={}", context::TEST_AUTH_TOKEN))
+            .header(CSRF_HEADER, context::csrf_token(context::TEST_AUTH_TOKEN))
+    }
+
     pub fn assert_response<F>(res: &Response<Bytes>, code: StatusCode, checks: F)
     where
         F: FnOnce(Value),
@@ -264,6 +556,359 @@ mod test {
         let have: Value = serde_json::from_slice(res.body()).expect("failed to deserialise body");
         checks(have);
     }
+
+    /// Transports the API is served over, the tests below run against each of them.
+    #[derive(Clone, Copy, Debug)]
+    enum Transport {
+        /// Requests are passed to the filters directly.
+        Direct,
+        /// Requests are sent over the Unix domain socket bound by [`bind_socket`].
+        Socket,
+    }
+
+    /// All [`Transport`]s.
+    const TRANSPORTS: [Transport; 2] = [Transport::Direct, Transport::Socket];
+
+    /// The API served over a [`Transport`].
+    struct Client {
+        /// The filters serving the API.
+        api: BoxedFilter<(warp::reply::Response,)>,
+        /// Socket the API is served on, `None` for [`Transport::Direct`].
+        socket: Option<std::path::PathBuf>,
+    }
+
+    impl Client {
+        /// Serve the API for `ctx` over `transport`, binding the socket in `dir`.
+        fn serve(transport: Transport, ctx: context::Context, dir: &Path) -> io::Result<Self> {
+            let api = super::api(ctx, Subscriptions::default())
+                .map(Reply::into_response)
+                .boxed();
+            let socket = match transport {
+                Transport::Direct => None,
+                Transport::Socket => {
+                    let socket = dir.join("api.sock");
+                    let mut listener = bind_socket(&socket)?;
+                    let api = api.clone();
+                    tokio::spawn(async move {
+                        warp::serve(api).serve_incoming(listener.incoming()).await
+                    });
+                    Some(socket)
+                },
+            };
+
+            Ok(Self { api, socket })
+        }
+
+        /// Send `request` to the API and wait for the full response.
+        async fn send(
+            &self,
+            request: http::Request<Vec<u8>>,
+        ) -> Result<Response<Bytes>, Box<dyn std::error::Error>> {
+            let (parts, body) = request.into_parts();
+            match &self.socket {
+                None => {
+                    let mut builder = warp::test::request()
+                        .method(parts.method.as_str())
+                        .path(&parts.uri.to_string())
+                        .body(body);
+                    for (name, value) in &parts.headers {
+                        builder = builder.header(name.as_str(), value.clone());
+                    }
+                    Ok(builder.reply(&self.api).await)
+                },
+                Some(socket) => {
+                    let stream = tokio::net::UnixStream::connect(socket).await?;
+                    let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+                    tokio::spawn(connection);
+
+                    let res = sender
+                        .send_request(http::Request::from_parts(parts, hyper::Body::from(body)))
+                        .await?;
+                    let (parts, body) = res.into_parts();
+                    Ok(Response::from_parts(
+                        parts,
+                        hyper::body::to_bytes(body).await?,
+                    ))
+                },
+            }
+        }
+    }
+
+    /// Builder for a request like [`request`], carrying the auth cookie and the CSRF token.
+    fn authed(method: &str, path: &str) -> http::request::Builder {
+        http::Request::builder()
+            .method(method)
+            .uri(path)
+            .header("cookie", format!("auth-token={}", context::TEST_AUTH_TOKEN))
+            .header(CSRF_HEADER, context::csrf_token(context::TEST_AUTH_TOKEN))
+    }
+
+    /// Serialize `body` for a JSON request.
+    fn json(body: &Value) -> Vec<u8> {
+        serde_json::to_vec(body).expect("failed to serialize body")
+    }
+
+    #[tokio::test]
+    async fn reject_forbidden_origin() -> Result<(), Box<dyn std::error::Error>> {
+        for transport in &TRANSPORTS {
+            let tmp_dir = tempfile::tempdir()?;
+            let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+            let client = Client::serve(*transport, ctx.into(), tmp_dir.path())?;
+
+            let res = client
+                .send(
+                    authed("POST", "/v1/keystore/seal")
+                        .header("origin", "https://example.com")
+                        .body(vec![])?,
+                )
+                .await?;
+            assert_response(&res, StatusCode::FORBIDDEN, |have| {
+                assert_eq!(have["variant"], "FORBIDDEN_ORIGIN", "{:?}", transport);
+            });
+
+            let res = client
+                .send(
+                    authed("GET", "/v1/tokens")
+                        .header("origin", "https://example.com")
+                        .body(vec![])?,
+                )
+                .await?;
+            assert_response(&res, StatusCode::FORBIDDEN, |have| {
+                assert_eq!(have["variant"], "FORBIDDEN_ORIGIN", "{:?}", transport);
+            });
+
+            let res = client
+                .send(
+                    authed("GET", "/v1/tokens")
+                        .header("origin", "file://")
+                        .body(vec![])?,
+                )
+                .await?;
+            assert_response(&res, StatusCode::OK, |have| {
+                assert_eq!(have, serde_json::json!([]), "{:?}", transport);
+            });
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cors() -> Result<(), Box<dyn std::error::Error>> {
+        for transport in &TRANSPORTS {
+            let tmp_dir = tempfile::tempdir()?;
+            let mut ctx = context::Unsealed::tmp(&tmp_dir).await?;
+            ctx.test = true;
+            ctx.allowed_origins = vec!["file://".to_string(), "http://localhost:8080".to_string()];
+            let client = Client::serve(*transport, ctx.into(), tmp_dir.path())?;
+
+            for origin in &["file://", "http://localhost:8080"] {
+                let res = client
+                    .send(
+                        http::Request::builder()
+                            .method("OPTIONS")
+                            .uri("/v1/tokens")
+                            .header("origin", *origin)
+                            .header("access-control-request-method", "POST")
+                            .header("access-control-request-headers", CSRF_HEADER)
+                            .body(vec![])?,
+                    )
+                    .await?;
+                assert_eq!(res.status(), StatusCode::OK, "{:?}", transport);
+                assert_eq!(res.headers()["access-control-allow-origin"], *origin);
+                assert_eq!(res.headers()["access-control-allow-credentials"], "true");
+
+                let res = client
+                    .send(
+                        authed("GET", "/v1/tokens")
+                            .header("origin", *origin)
+                            .body(vec![])?,
+                    )
+                    .await?;
+                assert_eq!(res.status(), StatusCode::OK, "{:?}", transport);
+                assert_eq!(res.headers()["access-control-allow-origin"], *origin);
+                assert_eq!(res.headers()["access-control-expose-headers"], CSRF_HEADER);
+            }
+
+            // Test mode doesn't allow any other origins.
+            let res = client
+                .send(
+                    http::Request::builder()
+                        .method("OPTIONS")
+                        .uri("/v1/tokens")
+                        .header("origin", "https://example.com")
+                        .header("access-control-request-method", "POST")
+                        .body(vec![])?,
+                )
+                .await?;
+            assert_response(&res, StatusCode::FORBIDDEN, |have| {
+                assert_eq!(have["variant"], "FORBIDDEN_ORIGIN", "{:?}", transport);
+            });
+            assert!(res.headers().get("access-control-allow-origin").is_none());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn require_csrf_token() -> Result<(), Box<dyn std::error::Error>> {
+        for transport in &TRANSPORTS {
+            let tmp_dir = tempfile::tempdir()?;
+            let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+            *ctx.auth_token.write().await = Some("secret".to_string());
+            let ctx = context::Context::from(ctx);
+            let csrf_token = ctx.csrf_token().await.unwrap_or_default();
+            let client = Client::serve(*transport, ctx, tmp_dir.path())?;
+            let body = json(&serde_json::json!({ "name": "editor", "scopes": [] }));
+
+            let res = client
+                .send(
+                    http::Request::builder()
+                        .method("POST")
+                        .uri("/v1/tokens")
+                        .header("cookie", "auth-token=secret")
+                        .header("content-type", "application/json")
+                        .body(body.clone())?,
+                )
+                .await?;
+            assert_response(&res, StatusCode::FORBIDDEN, |have| {
+                assert_eq!(have["message"], "Invalid CSRF token", "{:?}", transport);
+            });
+
+            let res = client
+                .send(
+                    http::Request::builder()
+                        .method("POST")
+                        .uri("/v1/tokens")
+                        .header("cookie", "auth-token=secret")
+                        .header(CSRF_HEADER, csrf_token)
+                        .header("content-type", "application/json")
+                        .body(body)?,
+                )
+                .await?;
+            assert_eq!(res.status(), StatusCode::CREATED, "{:?}", transport);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_without_session() -> Result<(), Box<dyn std::error::Error>> {
+        for transport in &TRANSPORTS {
+            let tmp_dir = tempfile::tempdir()?;
+            let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+            *ctx.auth_token.write().await = None;
+            let client = Client::serve(*transport, ctx.into(), tmp_dir.path())?;
+
+            let res = client
+                .send(
+                    http::Request::builder()
+                        .method("GET")
+                        .uri("/v1/tokens")
+                        .body(vec![])?,
+                )
+                .await?;
+            assert_response(&res, StatusCode::FORBIDDEN, |have| {
+                assert_eq!(
+                    have["message"], "Invalid authentication token",
+                    "{:?}",
+                    transport
+                );
+            });
+
+            let res = client
+                .send(
+                    authed("POST", "/v1/tokens")
+                        .header("content-type", "application/json")
+                        .body(json(&serde_json::json!({ "name": "editor", "scopes": [] })))?,
+                )
+                .await?;
+            assert_response(&res, StatusCode::FORBIDDEN, |have| {
+                assert_eq!(
+                    have["message"], "Invalid authentication token",
+                    "{:?}",
+                    transport
+                );
+            });
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn manage_tokens() -> Result<(), Box<dyn std::error::Error>> {
+        for transport in &TRANSPORTS {
+            let tmp_dir = tempfile::tempdir()?;
+            let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+            let client = Client::serve(*transport, ctx.into(), tmp_dir.path())?;
+
+            let res = client
+                .send(
+                    authed("POST", "/v1/tokens")
+                        .header("content-type", "application/json")
+                        .body(json(
+                            &serde_json::json!({ "name": "editor", "scopes": ["browse"] }),
+                        ))?,
+                )
+                .await?;
+            assert_eq!(res.status(), StatusCode::CREATED, "{:?}", transport);
+
+            let res = client
+                .send(authed("GET", "/v1/tokens").body(vec![])?)
+                .await?;
+            assert_response(&res, StatusCode::OK, |have| {
+                assert_eq!(have[0]["name"], "editor", "{:?}", transport);
+            });
+
+            let res = client
+                .send(authed("DELETE", "/v1/tokens/editor").body(vec![])?)
+                .await?;
+            assert_eq!(res.status(), StatusCode::NO_CONTENT, "{:?}", transport);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bind_private_socket() -> Result<(), Box<dyn std::error::Error>> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let tmp_dir = tempfile::tempdir()?;
+        let socket = tmp_dir.path().join("api.sock");
+        let _listener = bind_socket(&socket)?;
+        assert_eq!(
+            std::fs::metadata(&socket)?.permissions().mode() & 0o777,
+            0o600
+        );
+        // The private directory the socket was bound in is gone.
+        assert_eq!(
+            std::fs::read_dir(tmp_dir.path())?
+                .map(|entry| entry.map(|entry| entry.file_name()))
+                .collect::<Result<Vec<_>, _>>()?,
+            vec![std::ffi::OsString::from("api.sock")]
+        );
+
+        // Replaces the stale socket.
+        bind_socket(&socket)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bind_socket_over_file() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("api.sock");
+        std::fs::write(&path, "not a socket")?;
+
+        let err = bind_socket(&path).err();
+        assert_eq!(
+            err.map(|err| err.kind()),
+            Some(std::io::ErrorKind::AlreadyExists)
+        );
+        assert_eq!(std::fs::read_to_string(&path)?, "not a socket");
+
+        Ok(())
+    }
+
     #[derive(serde::Deserialize, serde::Serialize)]
     struct Query {
         value: u32,
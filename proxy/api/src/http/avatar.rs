@@ -1,8 +1,11 @@
 //! Endpoints for Avatar.
 
+use schemars::gen::SchemaGenerator;
 use serde::Deserialize;
 use warp::{filters::BoxedFilter, path, Filter, Reply};
 
+use crate::{http::doc, identity};
+
 /// `GET /<id>?usage=<usage>`
 pub fn get_filter() -> BoxedFilter<(impl Reply,)> {
     warp::any()
@@ -13,6 +16,15 @@ pub fn get_filter() -> BoxedFilter<(impl Reply,)> {
         .boxed()
 }
 
+/// Documentation of the avatar route.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new("GET", "/{id}", "Generate the avatar for an identifier")
+            .response::<identity::AvatarSchema>(200, gen)
+            .errors(&[(400, "INVALID_QUERY")]),
+    ]
+}
+
 /// Avatar handlers for conversion between core domain and http request fullfilment.
 mod handler {
     use warp::{http::StatusCode, reply, Rejection, Reply};
@@ -37,6 +49,7 @@ mod handler {
                         reply::json(&error::Error {
                             message: "BAD_REQUEST".to_string(),
                             variant: "Invalid query input".to_string(),
+                            retry_after: None,
                         }),
                         StatusCode::BAD_REQUEST,
                     ))
@@ -1,34 +1,292 @@
 //! Infrastructure to provide `OpenAPI` documentation for the exposed endpoints.
+//!
+//! Every module documents its endpoints in a `docs` function next to its filters, with the schemas
+//! of the request and response bodies derived from their types. The test in this module sends a
+//! request for every documented endpoint through the mounted filters, so documenting an endpoint
+//! which isn't served fails the test suite, and requests with every method to the paths around
+//! them, so serving an endpoint which isn't documented fails it as well.
 
-use warp::filters::BoxedFilter;
-use warp::{document, path, reply, Filter, Rejection, Reply};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+use warp::{filters::BoxedFilter, path, reply, Filter, Rejection, Reply};
+
+use super::error;
 
 /// Combined docs filters.
-pub fn filters<F>(api: &F) -> BoxedFilter<(impl Reply,)>
-where
-    F: Filter + 'static,
-{
-    describe_filter(api).or(index_filter()).boxed()
+pub fn filters() -> BoxedFilter<(impl Reply,)> {
+    describe_filter().or(index_filter()).boxed()
+}
+
+/// Documentation of the docs endpoints.
+#[must_use]
+pub fn docs() -> Vec<Route> {
+    vec![
+        Route::new("GET", "/", "Browse the documentation of the API").body(200, "text/html"),
+        Route::new("GET", "/index.html", "Browse the documentation of the API")
+            .body(200, "text/html"),
+        Route::new(
+            "GET",
+            "/openapi.json",
+            "Get the OpenAPI document of the API",
+        )
+        .body(200, "application/json"),
+    ]
 }
 
 /// GET /
 /// GET /index.html
 pub fn index_filter() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
-    path::end().or(path("index.html")).map(|_| {
-        warp::http::Response::builder()
-            .header("content-type", "text/html; charset=utf-8")
-            .body(INDEX_HTML)
+    warp::get()
+        .and(path::end().or(path("index.html")))
+        .map(|_| {
+            warp::http::Response::builder()
+                .header("content-type", "text/html; charset=utf-8")
+                .body(INDEX_HTML)
+        })
+}
+
+/// GET /openapi.json
+pub fn describe_filter() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let docs = openapi();
+
+    warp::get()
+        .and(path!("openapi.json"))
+        .map(move || reply::json(&docs))
+}
+
+/// Documentation of a single endpoint, built next to its filter by the `docs` function of every
+/// module and mounted by [`super::docs`].
+pub struct Route {
+    /// HTTP method of the endpoint.
+    method: &'static str,
+    /// Path of the endpoint, with parameters in curly braces.
+    path: String,
+    /// Short description of what the endpoint does.
+    summary: &'static str,
+    /// Schema of the JSON request body, if any.
+    request: Option<Schema>,
+    /// Status codes of the successful responses, with the media type and schema of their body, if
+    /// any.
+    responses: Vec<(u16, Option<(&'static str, Schema)>)>,
+    /// Status codes and variants of the errors the endpoint responds with, in addition to the ones
+    /// every endpoint may respond with.
+    errors: &'static [(u16, &'static str)],
+}
+
+impl Route {
+    /// Document the endpoint serving `method` requests to `path`, relative to where its filter is
+    /// mounted.
+    #[must_use]
+    pub fn new(method: &'static str, path: &str, summary: &'static str) -> Self {
+        Self {
+            method,
+            path: path.to_string(),
+            summary,
+            request: None,
+            responses: Vec::new(),
+            errors: &[],
+        }
+    }
+
+    /// Set the type of the JSON request body.
+    pub fn request<T: JsonSchema>(mut self, gen: &mut SchemaGenerator) -> Self {
+        self.request = Some(gen.subschema_for::<T>());
+        self
+    }
+
+    /// Add a successful response with a JSON body of type `T`.
+    pub fn response<T: JsonSchema>(mut self, status: u16, gen: &mut SchemaGenerator) -> Self {
+        self.responses
+            .push((status, Some(("application/json", gen.subschema_for::<T>()))));
+        self
+    }
+
+    /// Add a successful response which is a stream of server-sent events with JSON data of type
+    /// `T`.
+    pub fn events<T: JsonSchema>(mut self, gen: &mut SchemaGenerator) -> Self {
+        self.responses
+            .push((200, Some(("text/event-stream", gen.subschema_for::<T>()))));
+        self
+    }
+
+    /// Add a successful response with a body of `media_type` which isn't described by a schema,
+    /// like an HTML page.
+    #[must_use]
+    pub fn body(mut self, status: u16, media_type: &'static str) -> Self {
+        self.responses
+            .push((status, Some((media_type, Schema::Bool(true)))));
+        self
+    }
+
+    /// Add a successful response without a body.
+    #[must_use]
+    pub fn empty(mut self, status: u16) -> Self {
+        self.responses.push((status, None));
+        self
+    }
+
+    /// Set the errors the endpoint responds with.
+    #[must_use]
+    pub const fn errors(mut self, errors: &'static [(u16, &'static str)]) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Prefix the path with the path the filter of the endpoint is mounted at.
+    #[must_use]
+    pub fn mount(mut self, prefix: &str) -> Self {
+        self.path = format!("{}{}", prefix, self.path.trim_end_matches('/'));
+        self
+    }
+}
+
+/// Errors of endpoints which require an unsealed keystore and authentication.
+pub const AUTH: &[(u16, &str)] = &[(403, "FORBIDDEN")];
+/// Errors of endpoints which also require an identity for the session.
+pub const OWNER: &[(u16, &str)] = &[(401, "UNAUTHORIZED"), (403, "FORBIDDEN")];
+/// Errors of endpoints reading from the monorepo.
+pub const STATE: &[(u16, &str)] = &[(403, "FORBIDDEN"), (404, "NOT_FOUND")];
+/// Errors of endpoints reading from the monorepo with a query string.
+pub const QUERY: &[(u16, &str)] = &[
+    (400, "INVALID_QUERY"),
+    (400, "QUERY_MISSING"),
+    (403, "FORBIDDEN"),
+    (404, "NOT_FOUND"),
+];
+
+lazy_static::lazy_static! {
+    /// Methods and paths of all documented endpoints, see [`route`].
+    static ref PATHS: Vec<(&'static str, String)> = super::docs(&mut SchemaGenerator::default())
+        .into_iter()
+        .map(|route| (route.method, route.path))
+        .collect();
+}
+
+/// Build the `OpenAPI` document from the documentation of the mounted filters.
+fn openapi() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let routes = super::docs(&mut gen);
+    let error_schema = gen.subschema_for::<error::Error>();
+
+    let mut paths = Map::new();
+    for route in routes {
+        let mut responses = Map::new();
+        for (status, body) in route.responses {
+            let response = match body {
+                Some((media_type, schema)) => json!({
+                    "description": "Success",
+                    "content": { media_type: { "schema": schema } },
+                }),
+                None => json!({ "description": "Success" }),
+            };
+            responses.insert(status.to_string(), response);
+        }
+        for (status, variant) in route.errors {
+            let entry = responses.entry(status.to_string()).or_insert_with(|| {
+                json!({
+                    "description": "",
+                    "content": { "application/json": { "schema": error_schema } },
+                })
+            });
+            let description = entry["description"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            entry["description"] = if description.is_empty() {
+                Value::from(*variant)
+            } else {
+                Value::from(format!("{}, {}", description, variant))
+            };
+        }
+
+        let parameters = route
+            .path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut operation = json!({
+            "summary": route.summary,
+            "tags": [route
+                .path
+                .split('/')
+                .find(|segment| !segment.is_empty() && *segment != "v1")
+                .unwrap_or_default()],
+            "parameters": parameters,
+            "responses": responses,
+        });
+        if let Some(request) = route.request {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": request } },
+            });
+        }
+
+        if let Some(item) = paths
+            .entry(route.path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+        {
+            item.insert(route.method.to_lowercase(), operation);
+        }
+    }
+
+    json!({
+        "openapi": "3.0.0",
+        "info": {
+            "title": "Radicle Upstream Proxy",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": { "schemas": gen.take_definitions() },
     })
 }
 
-/// GET /opnapi.json
-pub fn describe_filter<F>(api: &F) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone
-where
-    F: Filter,
-{
-    let docs = document::to_openapi(document::describe(api));
+/// Parameter at the end of a documented path which takes the rest of the request path, which can
+/// span several segments, e.g. `/v1/source/local-state/{path}`.
+const TAIL_PARAM: &str = "{path}";
+
+/// Find the documented path of the endpoint serving a request to `path` with `method`, e.g.
+/// `/v1/projects/{urn}` for `/v1/projects/rad:git:hwd1`. Fixed segments take precedence over
+/// parameters, so `/v1/projects/tracked` isn't mistaken for `/v1/projects/{urn}`. A trailing
+/// [`TAIL_PARAM`] matches one or more segments.
+pub fn route(method: &str, path: &str) -> Option<&'static str> {
+    let segments = path.trim_end_matches('/').split('/').collect::<Vec<_>>();
+
+    PATHS
+        .iter()
+        .filter(|(route_method, _)| *route_method == method)
+        .filter_map(|(_, route_path)| {
+            let template = route_path.split('/').collect::<Vec<_>>();
+            let tail = template.last() == Some(&TAIL_PARAM) && segments.len() > template.len();
+            if template.len() != segments.len() && !tail {
+                return None;
+            }
 
-    path!("openapi.json").map(move || reply::json(&docs))
+            let mut params = 0;
+            for (expected, actual) in template.iter().zip(&segments) {
+                if expected.starts_with('{') {
+                    params += 1;
+                } else if expected != actual {
+                    return None;
+                }
+            }
+            Some((params, route_path.as_str()))
+        })
+        .min_by_key(|(params, _)| *params)
+        .map(|(_, path)| path)
 }
 
 /// Static HTML served for the docs endpoint.
@@ -40,7 +298,7 @@ const INDEX_HTML: &str = r#"
   <script type="module" src="https://unpkg.com/rapidoc/dist/rapidoc-min.js"></script>
 </head>
 <body>
-  <rapi-doc 
+  <rapi-doc
     spec-url = "/docs/openapi.json"
     render-style = "read"
     default-schema-tab = "model"
@@ -58,6 +316,206 @@ const INDEX_HTML: &str = r#"
     primary-color = '#ea526f'
     >
   </rapi-doc>
-</body> 
+</body>
 </html>
 "#;
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use schemars::gen::SchemaGenerator;
+    use warp::{filters::BoxedFilter, http::StatusCode, test::request, Rejection, Reply};
+
+    use crate::{context, http, notification::Subscriptions};
+
+    use super::route;
+
+    const URN: &str = "rad:git:hwd1yrerz7sig1smr8yjs5ue1oij61bfhyx41couxqj61qn5joox5pu4o4c";
+    const SHA1: &str = "0000000000000000000000000000000000000000";
+
+    /// Path of a request to the documented `path`, with its parameters substituted.
+    fn request_path(path: &str, peer_id: &str) -> String {
+        path.split('/')
+            .map(|segment| match segment {
+                "{urn}" | "{project_urn}" => URN,
+                "{peer_id}" => peer_id,
+                "{sha1}" => SHA1,
+                "{id}" => "1",
+                "{name}" => "editor",
+                "{path}" => "tmp",
+                segment => segment,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Send a request through `api` and return the rejection if no endpoint served it. Only an
+    /// endpoint serving the request gets past the path and method and rejects with one of these.
+    async fn served(
+        api: &BoxedFilter<(impl Reply + 'static,)>,
+        method: &str,
+        path: &str,
+    ) -> Result<(), Rejection> {
+        let res = http::test::request()
+            .method(method)
+            .path(path)
+            .json(&serde_json::json!({}))
+            .filter(api)
+            .await;
+
+        match res {
+            Err(rejection)
+                if rejection.find::<http::error::Routing>().is_none()
+                    && rejection.find::<crate::error::Error>().is_none()
+                    && rejection
+                        .find::<warp::body::BodyDeserializeError>()
+                        .is_none() =>
+            {
+                Err(rejection)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    #[tokio::test]
+    async fn every_route_mounted() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let peer_id = ctx.state.peer_id().to_string();
+        let api = http::routes(ctx.into(), Subscriptions::default());
+
+        let mut routes = http::docs(&mut SchemaGenerator::default());
+        // The keystore rejects the requests after it is sealed.
+        routes.sort_by_key(|route| route.path == "/v1/keystore/seal");
+
+        for route in routes {
+            let path = request_path(&route.path, &peer_id);
+            let res = served(&api, route.method, &path).await;
+            assert!(
+                res.is_ok(),
+                "{} {} isn't served: {:?}",
+                route.method,
+                route.path,
+                res
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn every_mounted_route_documented() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let peer_id = ctx.state.peer_id().to_string();
+        let api = http::routes(ctx.into(), Subscriptions::default());
+
+        // The documented paths and the paths they are nested in, e.g. `/v1/projects` and
+        // `/v1/projects/{urn}` for `/v1/projects/{urn}/peers`.
+        let mut paths = http::docs(&mut SchemaGenerator::default())
+            .into_iter()
+            .flat_map(|route| {
+                let segments = route
+                    .path
+                    .split('/')
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                (2..=segments.len())
+                    .map(|len| segments[..len].join("/"))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        let mut requests = paths
+            .iter()
+            .flat_map(|path| {
+                http::CORS_METHODS
+                    .iter()
+                    .filter(|method| **method != "OPTIONS")
+                    .map(move |method| (*method, path.as_str()))
+            })
+            .collect::<Vec<_>>();
+        // The keystore rejects the requests after it is sealed.
+        requests.sort_by_key(|request| *request == ("POST", "/v1/keystore/seal"));
+
+        for (method, path) in requests {
+            let path = request_path(path, &peer_id);
+            if served(&api, method, &path).await.is_ok() {
+                assert!(
+                    route(method, &path).is_some(),
+                    "{} {} is served but not documented",
+                    method,
+                    path
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn route_template() {
+        assert_eq!(
+            route("GET", "/v1/projects/rad:git:hwd1"),
+            Some("/v1/projects/{urn}")
+        );
+        assert_eq!(
+            route("GET", "/v1/projects/tracked"),
+            Some("/v1/projects/tracked")
+        );
+        assert_eq!(
+            route("GET", "/v1/source/local-state/home/cloudhead/radicle"),
+            Some("/v1/source/local-state/{path}")
+        );
+        assert_eq!(
+            route("GET", "/v1/source/local-state/tmp"),
+            Some("/v1/source/local-state/{path}")
+        );
+        assert_eq!(route("GET", "/v1/source/local-state"), None);
+        assert_eq!(route("GET", "/v1/projects/rad:git:hwd1/extra"), None);
+        assert_eq!(route("POST", "/v1/projects/tracked"), None);
+        assert_eq!(route("GET", "/metrics"), Some("/metrics"));
+        assert_eq!(
+            route("GET", "/docs/openapi.json"),
+            Some("/docs/openapi.json")
+        );
+        assert_eq!(route("GET", "/favicon.ico"), None);
+    }
+
+    #[tokio::test]
+    async fn openapi() -> Result<(), Box<dyn std::error::Error>> {
+        let api = super::filters();
+        let res = request()
+            .method("GET")
+            .path("/openapi.json")
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let docs: serde_json::Value = serde_json::from_slice(res.body())?;
+        assert_eq!(
+            docs["paths"]["/v1/tokens/{name}"]["delete"]["responses"]["404"]["description"],
+            "TOKEN_NOT_FOUND"
+        );
+        assert_eq!(
+            docs["paths"]["/v1/identities"]["post"]["requestBody"]["content"]["application/json"]
+                ["schema"]["$ref"],
+            "#/components/schemas/CreateIdentityInput"
+        );
+        let schemas = &docs["components"]["schemas"];
+        assert_eq!(
+            schemas["CreateIdentityInput"]["properties"]["handle"]["type"],
+            "string"
+        );
+        assert_eq!(
+            schemas["Identity"]["properties"]["avatarFallback"]["$ref"],
+            "#/components/schemas/Avatar"
+        );
+
+        let res = request().method("GET").path("/").reply(&api).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        Ok(())
+    }
+}
@@ -1,13 +1,20 @@
 //! Recovery and conversion of [`error::Error`] to proper JSON responses, which expose variants
 //! for API consumers to act on.
 
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::convert::Infallible;
-use warp::{http::StatusCode, reject, reply, Rejection, Reply};
+use warp::{
+    http::{header, StatusCode},
+    reject, reply, Rejection, Reply,
+};
 
-use coco::{project::create, state};
+use coco::{
+    project::{create, import},
+    state,
+};
 
-use crate::error;
+use crate::{error, migration};
 
 /// HTTP layer specific rejections.
 #[derive(Debug, thiserror::Error)]
@@ -35,6 +42,12 @@ pub enum Routing {
     /// Used by [`crate::http::with_qs`].
     #[error("Required query string is missing")]
     QueryMissing,
+    /// The request was made from an origin which isn't allowed to use the API.
+    #[error("Requests from origin '{origin}' are not allowed")]
+    ForbiddenOrigin {
+        /// The value of the `Origin` header.
+        origin: String,
+    },
 }
 
 impl reject::Reject for Routing {}
@@ -54,12 +67,16 @@ impl From<error::Error> for Rejection {
 }
 
 /// Error type to carry context for failed requests.
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct Error {
     /// Human readable message to convery error case.
     pub message: String,
     /// The triggered error variant.
     pub variant: String,
+    /// Seconds to wait before the request may be retried, also sent as `Retry-After` header.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
 }
 
 /// Handler to convert [`error::Error`] to [`Error`] response.
@@ -86,6 +103,9 @@ pub async fn recover(err: Rejection) -> Result<impl Reply, Infallible> {
                 Routing::QueryMissing { .. } => {
                     (StatusCode::BAD_REQUEST, "QUERY_MISSING", err.to_string())
                 },
+                Routing::ForbiddenOrigin { .. } => {
+                    (StatusCode::FORBIDDEN, "FORBIDDEN_ORIGIN", err.to_string())
+                },
             }
         } else if let Some(err) = err.find::<error::Error>() {
             match err {
@@ -98,61 +118,25 @@ pub async fn recover(err: Rejection) -> Result<impl Reply, Infallible> {
                             "WORKING_DIRECTORY_EXISTS",
                             git_error.message().to_string(),
                         ),
+                        coco::project::checkout::Error::Fetch { .. } => (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "FETCH_FAILED",
+                            checkout_error.to_string(),
+                        ),
+                        coco::project::checkout::Error::MissingRevision { .. } => (
+                            StatusCode::NOT_FOUND,
+                            "REVISION_NOT_FOUND",
+                            checkout_error.to_string(),
+                        ),
                         coco::project::checkout::Error::Include(include_error) => (
                             StatusCode::INTERNAL_SERVER_ERROR,
                             "INTERNAL_ERROR",
                             include_error.to_string(),
                         ),
                     },
-                    coco::state::Error::Create(create::Error::Validation(err)) => match err {
-                        create::validation::Error::AlreadExists(_) => {
-                            (StatusCode::CONFLICT, "PATH_EXISTS", err.to_string())
-                        },
-                        create::validation::Error::EmptyExistingPath(_) => {
-                            (StatusCode::BAD_REQUEST, "EMPTY_PATH", err.to_string())
-                        },
-                        create::validation::Error::Git(_) => (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            "GIT_ERROR",
-                            err.to_string(),
-                        ),
-                        create::validation::Error::MissingAuthorEmail => (
-                            StatusCode::BAD_REQUEST,
-                            "MISSING_AUTHOR_EMAIL",
-                            err.to_string(),
-                        ),
-                        create::validation::Error::MissingGitConfig => (
-                            StatusCode::BAD_REQUEST,
-                            "MISSING_GIT_CONFIG",
-                            err.to_string(),
-                        ),
-                        create::validation::Error::MissingAuthorName => (
-                            StatusCode::BAD_REQUEST,
-                            "MISSING_AUTHOR_NAME",
-                            err.to_string(),
-                        ),
-                        create::validation::Error::MissingDefaultBranch { .. } => (
-                            StatusCode::BAD_REQUEST,
-                            "MISSING_DEFAULT_BRANCH",
-                            err.to_string(),
-                        ),
-                        create::validation::Error::MissingUrl => {
-                            (StatusCode::BAD_REQUEST, "MISSING_URL", err.to_string())
-                        },
-                        create::validation::Error::PathDoesNotExist(_) => (
-                            StatusCode::NOT_FOUND,
-                            "PATH_DOES_NOT_EXIST",
-                            err.to_string(),
-                        ),
-                        create::validation::Error::NotARepo(_) => {
-                            (StatusCode::BAD_REQUEST, "NOT_A_REPO", err.to_string())
-                        },
-                        create::validation::Error::Io(err) => {
-                            (StatusCode::BAD_REQUEST, "IO_ERROR", err.to_string())
-                        },
-                        create::validation::Error::UrlMismatch { .. } => {
-                            (StatusCode::BAD_REQUEST, "URL_MISMATCH", err.to_string())
-                        },
+                    coco::state::Error::Create(create::Error::Validation(err)) => {
+                        let (code, variant) = validation(err);
+                        (code, variant, err.to_string())
                     },
                     coco::state::Error::Storage(state::error::storage::Error::AlreadyExists(
                         urn,
@@ -168,6 +152,12 @@ pub async fn recover(err: Rejection) -> Result<impl Reply, Infallible> {
                         "NOT_FOUND",
                         "entity not found".to_string(),
                     ),
+                    coco::state::Error::DeviceNotLinked { .. } => {
+                        (StatusCode::FORBIDDEN, "DEVICE_NOT_LINKED", err.to_string())
+                    },
+                    coco::state::Error::Profile(_) => {
+                        (StatusCode::BAD_REQUEST, "INVALID_PROFILE", err.to_string())
+                    },
                     coco::state::Error::Git(git_error) => (
                         StatusCode::BAD_REQUEST,
                         "GIT_ERROR",
@@ -183,6 +173,11 @@ pub async fn recover(err: Rejection) -> Result<impl Reply, Infallible> {
                         "GIT_ERROR",
                         coco::source::Error::NoBranches.to_string(),
                     ),
+                    coco::state::Error::Source(coco::source::Error::CommitNotFound(oid)) => (
+                        StatusCode::NOT_FOUND,
+                        "NOT_FOUND",
+                        format!("the commit '{}' was not found", oid),
+                    ),
                     coco::state::Error::Source(coco::source::Error::PathNotFound(path)) => {
                         (StatusCode::NOT_FOUND, "NOT_FOUND", path.to_string())
                     },
@@ -196,6 +191,26 @@ pub async fn recover(err: Rejection) -> Result<impl Reply, Infallible> {
                         )
                     },
                 },
+                error::Error::Import(import_err) => match import_err {
+                    import::Error::Git(_) => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "GIT_ERROR",
+                        err.to_string(),
+                    ),
+                    import::Error::Io(_) => (StatusCode::BAD_REQUEST, "IO_ERROR", err.to_string()),
+                    import::Error::NotADirectory(_) => {
+                        (StatusCode::BAD_REQUEST, "NOT_A_DIRECTORY", err.to_string())
+                    },
+                    import::Error::Validation(validation_err) => {
+                        let (code, variant) = validation(validation_err);
+                        (code, variant, err.to_string())
+                    },
+                },
+                error::Error::Io(_) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "INTERNAL_ERROR",
+                    err.to_string(),
+                ),
                 error::Error::Keystore(keystore_err) => {
                     if keystore_err.is_invalid_passphrase() {
                         (
@@ -217,9 +232,53 @@ pub async fn recover(err: Rejection) -> Result<impl Reply, Infallible> {
                         )
                     }
                 },
+                error::Error::Migration(migration_err) => match migration_err {
+                    migration::Error::Json(_) => {
+                        (StatusCode::BAD_REQUEST, "INVALID_BUNDLE", err.to_string())
+                    },
+                    migration::Error::UnsupportedVersion(_) => (
+                        StatusCode::BAD_REQUEST,
+                        "UNSUPPORTED_BUNDLE_VERSION",
+                        err.to_string(),
+                    ),
+                },
+                error::Error::TokenExists(_) => {
+                    (StatusCode::CONFLICT, "TOKEN_EXISTS", err.to_string())
+                },
+                error::Error::TokenNotFound(_) => {
+                    (StatusCode::NOT_FOUND, "TOKEN_NOT_FOUND", err.to_string())
+                },
+                error::Error::InvalidWebhook(_) => {
+                    (StatusCode::BAD_REQUEST, "INVALID_WEBHOOK", err.to_string())
+                },
+                error::Error::MissingWebhookSecret(_) => (
+                    StatusCode::BAD_REQUEST,
+                    "MISSING_WEBHOOK_SECRET",
+                    err.to_string(),
+                ),
+                error::Error::NotificationNotFound(_) => (
+                    StatusCode::NOT_FOUND,
+                    "NOTIFICATION_NOT_FOUND",
+                    err.to_string(),
+                ),
+                error::Error::KeyManagedByAgent => (
+                    StatusCode::CONFLICT,
+                    "KEY_MANAGED_BY_AGENT",
+                    err.to_string(),
+                ),
+                error::Error::TooManyAttempts { .. } => (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "TOO_MANY_ATTEMPTS",
+                    err.to_string(),
+                ),
                 error::Error::KeystoreSealed
+                | error::Error::KeystoreUnsealed
                 | error::Error::WrongPassphrase
-                | error::Error::InvalidAuthCookie => {
+                | error::Error::InvalidAuthCookie
+                | error::Error::InvalidCsrfToken
+                | error::Error::InvalidApiToken
+                | error::Error::InsufficientScope
+                | error::Error::NotSessionIdentity => {
                     (StatusCode::FORBIDDEN, "FORBIDDEN", err.to_string())
                 },
                 _ => {
@@ -240,16 +299,62 @@ pub async fn recover(err: Rejection) -> Result<impl Reply, Infallible> {
             )
         }
     };
+    let retry_after = match err.find::<error::Error>() {
+        Some(error::Error::TooManyAttempts { retry_after }) => Some(*retry_after),
+        _ => None,
+    };
     let res = reply::json(&Error {
         message,
         variant: variant.to_string(),
+        retry_after,
     });
 
-    Ok(reply::with_header(
+    let mut response = reply::with_header(
         reply::with_status(res, code),
         "content-type",
         "application/json",
-    ))
+    )
+    .into_response();
+    if let Some(retry_after) = retry_after {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, header::HeaderValue::from(retry_after));
+    }
+
+    Ok(response)
+}
+
+/// Status code and variant for a [`create::validation::Error`], shared by [`recover`] and
+/// endpoints reporting validation problems without rejecting.
+#[must_use]
+pub fn validation(err: &create::validation::Error) -> (StatusCode, &'static str) {
+    match err {
+        create::validation::Error::AlreadExists(_) => (StatusCode::CONFLICT, "PATH_EXISTS"),
+        create::validation::Error::EmptyExistingPath(_) => (StatusCode::BAD_REQUEST, "EMPTY_PATH"),
+        create::validation::Error::Git(_) => (StatusCode::INTERNAL_SERVER_ERROR, "GIT_ERROR"),
+        create::validation::Error::MissingAuthorEmail => {
+            (StatusCode::BAD_REQUEST, "MISSING_AUTHOR_EMAIL")
+        },
+        create::validation::Error::MissingGitConfig => {
+            (StatusCode::BAD_REQUEST, "MISSING_GIT_CONFIG")
+        },
+        create::validation::Error::MissingAuthorName => {
+            (StatusCode::BAD_REQUEST, "MISSING_AUTHOR_NAME")
+        },
+        create::validation::Error::MissingDefaultBranch { .. } => {
+            (StatusCode::BAD_REQUEST, "MISSING_DEFAULT_BRANCH")
+        },
+        create::validation::Error::MissingUrl => (StatusCode::BAD_REQUEST, "MISSING_URL"),
+        create::validation::Error::PathDoesNotExist(_) => {
+            (StatusCode::NOT_FOUND, "PATH_DOES_NOT_EXIST")
+        },
+        create::validation::Error::RadRemoteExists { .. } => {
+            (StatusCode::CONFLICT, "RAD_REMOTE_EXISTS")
+        },
+        create::validation::Error::NotARepo(_) => (StatusCode::BAD_REQUEST, "NOT_A_REPO"),
+        create::validation::Error::Io(_) => (StatusCode::BAD_REQUEST, "IO_ERROR"),
+        create::validation::Error::UrlMismatch { .. } => (StatusCode::BAD_REQUEST, "URL_MISMATCH"),
+    }
 }
 
 #[allow(clippy::unwrap_used)]
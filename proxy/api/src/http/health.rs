@@ -0,0 +1,151 @@
+//! Endpoint reporting the [`crate::health::Health`] of the proxy.
+
+use schemars::gen::SchemaGenerator;
+use warp::{filters::BoxedFilter, path, Filter, Reply};
+
+use crate::{context, health, http, http::doc};
+
+/// `GET /`
+pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
+    warp::get()
+        .and(path::end())
+        .and(http::with_context(ctx))
+        .and_then(handler::get)
+        .boxed()
+}
+
+/// Documentation of the health route.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![doc::Route::new(
+        "GET",
+        "/",
+        "Report the health of the proxy and its subsystems, with status 503 if unhealthy",
+    )
+    .response::<health::Health>(200, gen)
+    .response::<health::Health>(503, gen)]
+}
+
+/// Health handlers for conversion between core domain and HTTP request fulfilment.
+mod handler {
+    use warp::{http::StatusCode, reply, Rejection, Reply};
+
+    use crate::{context, health};
+
+    /// Check the health of the proxy. Responds with `503 Service Unavailable` if it is unhealthy.
+    pub async fn get(ctx: context::Context) -> Result<impl Reply, Rejection> {
+        let health = health::check(&ctx).await;
+        let status = if health.healthy {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+
+        Ok(reply::with_status(reply::json(&health), status))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use warp::http::StatusCode;
+
+    use crate::{context, http::test::request};
+
+    /// Install a fake git remote helper with the permission bits `mode` in the bin directory of
+    /// `ctx`.
+    fn install_git_helper(ctx: &context::Unsealed, mode: u32) -> Result<(), std::io::Error> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        std::fs::create_dir_all(&ctx.bin_dir)?;
+        let path = ctx.bin_dir.join(coco::git_helper::GIT_REMOTE_RAD);
+        std::fs::write(&path, "#!/bin/sh\n")?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))
+    }
+
+    #[tokio::test]
+    async fn get() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        install_git_helper(&ctx, 0o755)?;
+        let api = super::filters(ctx.into());
+
+        let res = request().method("GET").path("/").reply(&api).await;
+        let health: serde_json::Value = serde_json::from_slice(res.body())?;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(health["healthy"], json!(true));
+        assert_eq!(health["ready"], json!(true));
+        assert_eq!(health["keystore"], json!({ "sealed": false }));
+        assert_eq!(health["store"], json!({ "ok": true, "error": null }));
+        assert_eq!(health["monorepo"], json!({ "ok": true, "error": null }));
+        assert_eq!(health["gitHelper"], json!({ "ok": true, "error": null }));
+        assert_eq!(health["peer"]["running"], json!(true));
+        assert!(health["peer"]["status"].is_object());
+        assert!(health["peer"]["statusSince"].is_u64());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn git_helper_missing() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.into());
+
+        let res = request().method("GET").path("/").reply(&api).await;
+        let health: serde_json::Value = serde_json::from_slice(res.body())?;
+
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health["healthy"], json!(false));
+        assert_eq!(health["ready"], json!(false));
+        assert_eq!(health["gitHelper"]["ok"], json!(false));
+        assert!(health["gitHelper"]["error"]
+            .as_str()
+            .map_or(false, |error| error.contains("is missing")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn git_helper_not_executable() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        install_git_helper(&ctx, 0o644)?;
+        let api = super::filters(ctx.into());
+
+        let res = request().method("GET").path("/").reply(&api).await;
+        let health: serde_json::Value = serde_json::from_slice(res.body())?;
+
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health["healthy"], json!(false));
+        assert_eq!(health["gitHelper"]["ok"], json!(false));
+        assert!(health["gitHelper"]["error"]
+            .as_str()
+            .map_or(false, |error| error.ends_with("is not executable")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn peer_gone() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let mut ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let (sender, _) = tokio::sync::mpsc::channel(1);
+        ctx.peer_control = coco::PeerControl::new(sender);
+        let api = super::filters(ctx.into());
+
+        let res = request().method("GET").path("/").reply(&api).await;
+        let health: serde_json::Value = serde_json::from_slice(res.body())?;
+
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health["healthy"], json!(false));
+        assert_eq!(health["ready"], json!(false));
+        assert_eq!(
+            health["peer"],
+            json!({ "running": false, "status": null, "statusSince": null })
+        );
+
+        Ok(())
+    }
+}
@@ -1,18 +1,71 @@
 //! Manage the state and stateful interactions with the underlying peer API of librad.
 
+use schemars::{gen::SchemaGenerator, JsonSchema};
 use serde::{Deserialize, Serialize};
 use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
 
-use crate::{context, http};
+use crate::{context, http, http::doc, identity};
 
 /// Combination of all identity routes.
 pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
     get_filter(ctx.clone())
         .or(create_filter(ctx.clone()))
-        .or(list_filter(ctx))
+        .or(list_filter(ctx.clone()))
+        .or(update_filter(ctx.clone()))
+        .or(link_device_filter(ctx.clone()))
+        .or(adopt_filter(ctx))
         .boxed()
 }
 
+/// Documentation of all identity routes.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new("POST", "/", "Create the identity of the session")
+            .request::<CreateInput>(gen)
+            .response::<identity::Identity>(201, gen)
+            .errors(&[(403, "FORBIDDEN"), (409, "ENTITY_EXISTS")]),
+        doc::Route::new("GET", "/{urn}", "Get an identity")
+            .response::<identity::Identity>(200, gen)
+            .errors(doc::STATE),
+        doc::Route::new("GET", "/", "List all known identities")
+            .response::<Vec<identity::Identity>>(200, gen)
+            .errors(doc::AUTH),
+        doc::Route::new(
+            "PUT",
+            "/{urn}",
+            "Update the metadata of the identity of the session",
+        )
+        .request::<coco::user::Profile>(gen)
+        .response::<identity::Identity>(200, gen)
+        .errors(&[
+            (400, "INVALID_PROFILE"),
+            (403, "FORBIDDEN"),
+            (404, "NOT_FOUND"),
+        ]),
+        doc::Route::new(
+            "POST",
+            "/{urn}/devices",
+            "Link the key of another device to the identity",
+        )
+        .request::<LinkDeviceInput>(gen)
+        .response::<identity::Identity>(200, gen)
+        .errors(doc::STATE),
+        doc::Route::new(
+            "POST",
+            "/adopt",
+            "Adopt an existing identity for the session",
+        )
+        .request::<AdoptInput>(gen)
+        .response::<identity::Identity>(201, gen)
+        .errors(&[
+            (400, "BAD_REQUEST"),
+            (403, "FORBIDDEN"),
+            (403, "DEVICE_NOT_LINKED"),
+            (409, "ENTITY_EXISTS"),
+        ]),
+    ]
+}
+
 /// `POST /`
 fn create_filter(
     ctx: context::Context,
@@ -24,7 +77,7 @@ fn create_filter(
         .and_then(handler::create)
 }
 
-/// `GET /<id>`
+/// `GET /<urn>`
 fn get_filter(
     ctx: context::Context,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
@@ -45,6 +98,45 @@ fn list_filter(
         .and_then(handler::list)
 }
 
+/// `PUT /<urn>`
+fn update_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path::param::<coco::Urn>()
+        .and(path::end())
+        .and(warp::put())
+        .and(http::with_context_unsealed(ctx.clone()))
+        .and(http::with_owner_guard(ctx))
+        .and(warp::body::json())
+        .and_then(handler::update)
+}
+
+/// `POST /<urn>/devices`
+fn link_device_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path::param::<coco::Urn>()
+        .and(path("devices"))
+        .and(path::end())
+        .and(warp::post())
+        .and(http::with_context_unsealed(ctx.clone()))
+        .and(http::with_owner_guard(ctx))
+        .and(warp::body::json())
+        .and_then(handler::link_device)
+}
+
+/// `POST /adopt`
+fn adopt_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("adopt")
+        .and(path::end())
+        .and(warp::post())
+        .and(http::with_context_unsealed(ctx))
+        .and(warp::body::json())
+        .and_then(handler::adopt)
+}
+
 /// Identity handlers for conversion between core domain and http request fullfilment.
 mod handler {
     use warp::{http::StatusCode, reply, Rejection, Reply};
@@ -80,28 +172,108 @@ mod handler {
         let users = identity::list(&ctx.state).await?;
         Ok(reply::json(&users))
     }
+
+    /// Link the device with the given peer id to the [`identity::Identity`] of the current
+    /// session.
+    pub async fn link_device(
+        id: coco::Urn,
+        ctx: context::Unsealed,
+        owner: coco::user::User,
+        super::LinkDeviceInput { peer_id }: super::LinkDeviceInput,
+    ) -> Result<impl Reply, Rejection> {
+        if owner.urn() != id {
+            return Err(Rejection::from(error::Error::NotSessionIdentity));
+        }
+
+        let id = identity::link_device(&ctx.state, &owner, peer_id).await?;
+        session::set_identity(&ctx.store, id.clone())?;
+
+        Ok(reply::json(&id))
+    }
+
+    /// Adopt the [`identity::Identity`] another device linked this one to and start a session
+    /// with it.
+    pub async fn adopt(
+        ctx: context::Unsealed,
+        input: super::AdoptInput,
+    ) -> Result<impl Reply, Rejection> {
+        if let Some(session) = session::get_current(&ctx.store)? {
+            return Err(Rejection::from(error::Error::from(
+                coco::state::Error::already_exists(session.identity.urn),
+            )));
+        }
+
+        let id = identity::adopt(&ctx.state, input.urn, input.peer_id, input.addr_hints).await?;
+
+        session::initialize(&ctx.store, id.clone(), &ctx.default_seeds)?;
+
+        Ok(reply::with_status(reply::json(&id), StatusCode::CREATED))
+    }
+
+    /// Edit the profile of the [`identity::Identity`] of the current session.
+    pub async fn update(
+        id: coco::Urn,
+        ctx: context::Unsealed,
+        owner: coco::user::User,
+        profile: coco::user::Profile,
+    ) -> Result<impl Reply, Rejection> {
+        if owner.urn() != id {
+            return Err(Rejection::from(error::Error::NotSessionIdentity));
+        }
+
+        let id = identity::update(&ctx.state, &owner, profile).await?;
+        session::set_identity(&ctx.store, id.clone())?;
+
+        Ok(reply::json(&id))
+    }
 }
 
 // TODO(xla): Implement Deserialize on identity::Metadata and drop this type entirely, this will
 // help to avoid duplicate efforts for documentation.
 /// Bundled input data for identity creation.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(rename = "CreateIdentityInput")]
 #[serde(rename_all = "camelCase")]
 pub struct CreateInput {
     /// Handle the user wants to go by.
     handle: String,
 }
 
+/// Bundled input data for linking a device.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkDeviceInput {
+    /// The peer id of the device to link.
+    #[schemars(with = "String")]
+    peer_id: coco::PeerId,
+}
+
+/// Bundled input data for adopting an identity linked on another device.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AdoptInput {
+    /// The identity to adopt.
+    #[schemars(with = "String")]
+    urn: coco::Urn,
+    /// The peer id of the device which linked this one.
+    #[schemars(with = "String")]
+    peer_id: coco::PeerId,
+    /// Addresses the device which linked this one can be reached at.
+    #[serde(default)]
+    #[schemars(with = "Vec<String>")]
+    addr_hints: Vec<std::net::SocketAddr>,
+}
+
 #[allow(clippy::non_ascii_literal, clippy::unwrap_used)]
 #[cfg(test)]
 mod test {
     use pretty_assertions::assert_eq;
     use serde_json::{json, Value};
-    use warp::{http::StatusCode, test::request};
+    use warp::http::StatusCode;
 
     use radicle_avatar as avatar;
 
-    use crate::{context, error, http, identity, session};
+    use crate::{context, error, http, http::test::request, identity, session};
 
     #[tokio::test]
     async fn create() -> Result<(), error::Error> {
@@ -180,7 +352,10 @@ mod test {
                 peer_id,
                 urn: urn.clone(),
                 shareable_entity_identifier,
-                metadata: identity::Metadata { handle },
+                metadata: identity::Metadata {
+                    handle,
+                    profile: coco::user::Profile::default(),
+                },
                 avatar_fallback: avatar::Avatar::from(&urn.to_string(), avatar::Usage::Identity),
             })
         );
@@ -188,6 +363,159 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn update() -> Result<(), error::Error> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into());
+
+        let session = session::initialize_test(&ctx, "cloudhead").await;
+        let urn = session.identity.urn;
+
+        let res = request()
+            .method("PUT")
+            .path(&format!("/{}", urn))
+            .json(&json!({
+                "displayName": "Alexis Sellier",
+                "bio": "radicle",
+                "links": ["https://radicle.xyz"],
+            }))
+            .reply(&api)
+            .await;
+
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(
+                have["metadata"],
+                json!({
+                    "handle": "cloudhead",
+                    "displayName": "Alexis Sellier",
+                    "bio": "radicle",
+                    "links": ["https://radicle.xyz/"],
+                })
+            );
+        });
+        let session = session::get_current(&ctx.store)?.expect("no session exists");
+        assert_eq!(
+            session.identity.metadata.profile.display_name,
+            Some("Alexis Sellier".to_string())
+        );
+        assert_eq!(
+            session.identity.avatar_fallback,
+            avatar::Avatar::from("Alexis Sellier", avatar::Usage::Identity)
+        );
+
+        let res = request()
+            .method("PUT")
+            .path(&format!("/{}", urn))
+            .json(&json!({ "links": ["radicle.xyz"] }))
+            .reply(&api)
+            .await;
+
+        http::test::assert_response(&res, StatusCode::BAD_REQUEST, |have| {
+            assert_eq!(have["variant"], "INVALID_PROFILE");
+        });
+        let session = session::get_current(&ctx.store)?.expect("no session exists");
+        assert_eq!(
+            session.identity.metadata.profile.links,
+            vec!["https://radicle.xyz/".to_string()]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn link_device() -> Result<(), error::Error> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into());
+
+        let session = session::initialize_test(&ctx, "cloudhead").await;
+        let urn = session.identity.urn;
+        let device = coco::PeerId::from(coco::keys::SecretKey::new());
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/devices", urn))
+            .json(&super::LinkDeviceInput { peer_id: device })
+            .reply(&api)
+            .await;
+
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(have["urn"], json!(urn));
+        });
+        let user = ctx.state.get_user(urn).await?;
+        assert!(user.keys().contains(device.as_public_key()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn adopt() -> Result<(), error::Error> {
+        let laptop_dir = tempfile::tempdir()?;
+        let laptop = context::Unsealed::tmp(&laptop_dir).await?;
+        let session = session::initialize_test(&laptop, "cloudhead").await;
+        let urn = session.identity.urn;
+
+        let desktop_dir = tempfile::tempdir()?;
+        let desktop = context::Unsealed::tmp(&desktop_dir).await?;
+        let api = super::filters(desktop.clone().into());
+        let input = super::AdoptInput {
+            urn: urn.clone(),
+            peer_id: laptop.state.peer_id(),
+            addr_hints: vec![laptop.state.listen_addr()],
+        };
+
+        let res = request()
+            .method("POST")
+            .path("/adopt")
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::FORBIDDEN, |have| {
+            assert_eq!(have["variant"], json!("DEVICE_NOT_LINKED"));
+        });
+        assert!(session::get_current(&desktop.store)?.is_none());
+
+        let owner = {
+            let user = laptop
+                .state
+                .default_owner()
+                .await
+                .expect("no default owner");
+            coco::user::verify(user)?
+        };
+        let _linked = identity::link_device(&laptop.state, &owner, desktop.state.peer_id()).await?;
+
+        let res = request()
+            .method("POST")
+            .path("/adopt")
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |have| {
+            assert_eq!(have["urn"], json!(urn));
+            assert_eq!(have["peerId"], json!(desktop.state.peer_id()));
+        });
+        assert_eq!(
+            desktop.state.default_owner().await.map(|owner| owner.urn()),
+            Some(urn.clone())
+        );
+        let session = session::get_current(&desktop.store)?.expect("no session exists");
+        assert_eq!(session.identity.urn, urn);
+
+        let res = request()
+            .method("POST")
+            .path("/adopt")
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CONFLICT, |have| {
+            assert_eq!(have["variant"], json!("ENTITY_EXISTS"));
+        });
+
+        Ok(())
+    }
+
     // TODO(xla): Reintroduce when tracking is properly supported at the level of state
     // manipulation.
     #[ignore]
@@ -1,13 +1,84 @@
 //! Endpoints for handling the keystore.
 
+use schemars::{gen::SchemaGenerator, JsonSchema};
 use serde::{Deserialize, Serialize};
 use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
 
-use crate::{context, http};
+use crate::{context, http, http::doc, migration, notification::Subscriptions};
 
 /// Combination of all keystore filters.
-pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
-    unseal_filter(ctx.clone()).or(create_filter(ctx)).boxed()
+pub fn filters(ctx: context::Context, subscriptions: Subscriptions) -> BoxedFilter<(impl Reply,)> {
+    unseal_filter(ctx.clone())
+        .or(seal_filter(ctx.clone(), subscriptions))
+        .or(csrf_filter(ctx.clone()))
+        .or(passphrase_filter(ctx.clone()))
+        .or(export_filter(ctx.clone()))
+        .or(import_filter(ctx.clone()))
+        .or(create_filter(ctx))
+        .boxed()
+}
+
+/// Documentation of all keystore filters.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new(
+            "POST",
+            "/unseal",
+            "Unseal the keystore, setting the auth cookie and passing the CSRF token",
+        )
+        .request::<UnsealInput>(gen)
+        .empty(204)
+        .errors(&[
+            (403, "FORBIDDEN"),
+            (403, "INCORRECT_PASSPHRASE"),
+            (429, "TOO_MANY_ATTEMPTS"),
+        ]),
+        doc::Route::new("POST", "/seal", "Seal the keystore")
+            .empty(204)
+            .errors(doc::AUTH),
+        doc::Route::new("GET", "/csrf", "Get the CSRF token of the session")
+            .response::<CsrfToken>(200, gen)
+            .errors(doc::AUTH),
+        doc::Route::new("PUT", "/passphrase", "Change the passphrase of the key")
+            .request::<ChangePassphraseInput>(gen)
+            .empty(204)
+            .errors(&[
+                (403, "FORBIDDEN"),
+                (403, "INCORRECT_PASSPHRASE"),
+                (409, "KEY_MANAGED_BY_AGENT"),
+                (429, "TOO_MANY_ATTEMPTS"),
+            ]),
+        doc::Route::new(
+            "POST",
+            "/export",
+            "Bundle key, identity and session for another machine",
+        )
+        .request::<ExportInput>(gen)
+        .response::<migration::Bundle>(200, gen)
+        .errors(doc::OWNER),
+        doc::Route::new(
+            "POST",
+            "/import",
+            "Restore key, identity and session from a bundle",
+        )
+        .request::<ImportInput>(gen)
+        .empty(204)
+        .errors(&[
+            (400, "INVALID_BUNDLE"),
+            (400, "UNSUPPORTED_BUNDLE_VERSION"),
+            (403, "FORBIDDEN"),
+            (409, "KEY_EXISTS"),
+            (429, "TOO_MANY_ATTEMPTS"),
+        ]),
+        doc::Route::new(
+            "POST",
+            "/",
+            "Create the key, setting the auth cookie and passing the CSRF token",
+        )
+        .request::<CreateInput>(gen)
+        .empty(204)
+        .errors(&[(409, "KEY_EXISTS"), (409, "KEY_MANAGED_BY_AGENT")]),
+    ]
 }
 
 /// `POST /unseal`
@@ -22,6 +93,67 @@ fn unseal_filter(
         .and_then(handler::unseal)
 }
 
+/// `POST /seal`
+fn seal_filter(
+    ctx: context::Context,
+    subscriptions: Subscriptions,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("seal")
+        .and(warp::post())
+        .and(path::end())
+        .and(http::with_context_unsealed(ctx))
+        .and(warp::any().map(move || subscriptions.clone()))
+        .and_then(handler::seal)
+}
+
+/// `GET /csrf`
+fn csrf_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("csrf")
+        .and(warp::get())
+        .and(path::end())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::csrf)
+}
+
+/// `PUT /passphrase`
+fn passphrase_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("passphrase")
+        .and(warp::put())
+        .and(path::end())
+        .and(http::with_context_unsealed(ctx))
+        .and(warp::body::json())
+        .and_then(handler::change_passphrase)
+}
+
+/// `POST /export`
+fn export_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("export")
+        .and(warp::post())
+        .and(path::end())
+        .and(http::with_context_unsealed(ctx.clone()))
+        .and(http::with_owner_guard(ctx))
+        .and(warp::body::json())
+        .and_then(handler::export)
+}
+
+/// `POST /import`
+fn import_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("import")
+        .and(warp::post())
+        .and(path::end())
+        .and(http::with_context_sealed(ctx))
+        .and(warp::body::json())
+        .and_then(handler::import)
+}
+
 /// `POST /`
 fn create_filter(
     ctx: context::Context,
@@ -37,7 +169,10 @@ fn create_filter(
 mod handler {
     use warp::{http::StatusCode, reply, Rejection, Reply};
 
-    use crate::context;
+    use crate::{
+        context, migration,
+        notification::{Keystore, Notification, SealReason, Subscriptions},
+    };
 
     /// Unseal the keystore.
     pub async fn unseal(
@@ -45,12 +180,58 @@ mod handler {
         input: super::UnsealInput,
     ) -> Result<impl Reply, Rejection> {
         let token = ctx.unseal_keystore(input.passphrase).await?;
-        Ok(warp::reply::with_header(
-            reply::with_status(reply(), StatusCode::NO_CONTENT),
-            "Set-Cookie",
-            super::format_cookie_header(&token),
-        )
-        .into_response())
+        Ok(super::session_reply(&ctx, &token).await)
+    }
+
+    /// Seal the keystore, dropping the key until it is unsealed again.
+    pub async fn seal(
+        ctx: context::Unsealed,
+        subscriptions: Subscriptions,
+    ) -> Result<impl Reply, Rejection> {
+        subscriptions
+            .broadcast(Notification::Keystore(Keystore::Sealed {
+                reason: SealReason::Requested,
+            }))
+            .await;
+        context::Context::from(ctx).seal().await;
+        Ok(reply::with_status(reply(), StatusCode::NO_CONTENT))
+    }
+
+    /// Get the CSRF token of the current session.
+    pub async fn csrf(ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
+        let token = context::Context::from(ctx).csrf_token().await;
+        Ok(reply::json(&super::CsrfToken { token }))
+    }
+
+    /// Re-encrypt the key with a new passphrase.
+    pub async fn change_passphrase(
+        ctx: context::Unsealed,
+        input: super::ChangePassphraseInput,
+    ) -> Result<impl Reply, Rejection> {
+        context::Context::from(ctx)
+            .change_passphrase(input.current_passphrase, input.new_passphrase)
+            .await?;
+        Ok(reply::with_status(reply(), StatusCode::NO_CONTENT))
+    }
+
+    /// Bundle the key, the identity and the session for another machine.
+    pub async fn export(
+        ctx: context::Unsealed,
+        owner: coco::user::User,
+        input: super::ExportInput,
+    ) -> Result<impl Reply, Rejection> {
+        let bundle = migration::export(&ctx, &owner, input.passphrase).await?;
+        Ok(reply::json(&bundle))
+    }
+
+    /// Restore the key, the identity and the session from a bundle created on another machine.
+    /// The keystore has to be unsealed afterwards with the passphrase of the imported key.
+    pub async fn import(
+        ctx: context::Sealed,
+        input: super::ImportInput,
+    ) -> Result<impl Reply, Rejection> {
+        migration::import(&ctx, input.bundle, input.passphrase).await?;
+        Ok(reply::with_status(reply(), StatusCode::NO_CONTENT))
     }
 
     /// Initialize the keystore with a new key.
@@ -59,32 +240,344 @@ mod handler {
         input: super::CreateInput,
     ) -> Result<impl Reply, Rejection> {
         let token = ctx.create_key(input.passphrase).await?;
-        Ok(warp::reply::with_header(
-            reply::with_status(reply(), StatusCode::NO_CONTENT),
-            "Set-Cookie",
-            super::format_cookie_header(&token),
-        )
-        .into_response())
+        Ok(super::session_reply(&ctx, &token).await)
     }
 }
 
 /// Bundled input data for unseal request.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct UnsealInput {
     /// Passphrase to unlock the keystore.
+    #[schemars(with = "String")]
     passphrase: coco::keystore::SecUtf8,
 }
 
 /// Bundled input data for `create` request.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(rename = "CreateKeyInput")]
 #[serde(rename_all = "camelCase")]
 pub struct CreateInput {
     /// Passphrase to encrypt the keystore with.
+    #[schemars(with = "String")]
     passphrase: coco::keystore::SecUtf8,
 }
 
+/// Bundled input data for `change_passphrase` request.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePassphraseInput {
+    /// Passphrase the key is currently encrypted with.
+    #[schemars(with = "String")]
+    current_passphrase: coco::keystore::SecUtf8,
+    /// Passphrase to encrypt the key with from now on.
+    #[schemars(with = "String")]
+    new_passphrase: coco::keystore::SecUtf8,
+}
+
+/// Bundled input data for `export` request.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportInput {
+    /// Passphrase to protect the bundle with.
+    #[schemars(with = "String")]
+    passphrase: coco::keystore::SecUtf8,
+}
+
+/// Bundled input data for `import` request.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportInput {
+    /// The bundle created by an export.
+    bundle: migration::Bundle,
+    /// Passphrase the bundle was protected with.
+    #[schemars(with = "String")]
+    passphrase: coco::keystore::SecUtf8,
+}
+
+/// CSRF token of the current session.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CsrfToken {
+    /// The token to send in the [`http::CSRF_HEADER`], `None` if there is no session.
+    token: Option<String>,
+}
+
+/// Empty response for a new session, setting the auth cookie to `token` and passing the CSRF
+/// token of the session in the [`http::CSRF_HEADER`].
+async fn session_reply(ctx: &context::Context, token: &str) -> warp::reply::Response {
+    let csrf_token = ctx.csrf_token().await.unwrap_or_default();
+    let reply = warp::reply::with_status(warp::reply(), warp::http::StatusCode::NO_CONTENT);
+    let reply = warp::reply::with_header(reply, "Set-Cookie", format_cookie_header(token));
+    warp::reply::with_header(reply, http::CSRF_HEADER, csrf_token).into_response()
+}
+
 /// Format the cookie header attributes.
 fn format_cookie_header(token: &str) -> String {
     format!("auth-token={}; Path=/", token)
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use warp::{http::StatusCode, Filter as _};
+
+    use coco::keystore::{Keystore as _, SecUtf8};
+
+    use crate::{
+        context, http,
+        http::test::request,
+        migration,
+        notification::{Keystore, Notification, SealReason, Subscriptions},
+        session,
+    };
+
+    #[tokio::test]
+    async fn seal() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let subscriptions = Subscriptions::default();
+        let mut notifications = subscriptions.subscribe().await;
+        let api = super::filters(ctx.into(), subscriptions);
+
+        let res = request().method("POST").path("/seal").reply(&api).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        let notification = notifications.recv().await.map(|event| event.notification);
+        assert!(matches!(
+            notification,
+            Some(Notification::Keystore(Keystore::Sealed {
+                reason: SealReason::Requested
+            }))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn change_passphrase() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        ctx.keystore.create_key(SecUtf8::from("asdf"))?;
+        let keystore = ctx.keystore.clone();
+        let api =
+            super::filters(ctx.into(), Subscriptions::default()).recover(http::error::recover);
+
+        // Requires a session and its CSRF token.
+        let res = warp::test::request()
+            .method("PUT")
+            .path("/passphrase")
+            .json(&json!({ "currentPassphrase": "asdf", "newPassphrase": "qwerty" }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = warp::test::request()
+            .method("PUT")
+            .path("/passphrase")
+            .header("cookie", format!("auth-token={}", context::TEST_AUTH_TOKEN))
+            .json(&json!({ "currentPassphrase": "asdf", "newPassphrase": "qwerty" }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = request()
+            .method("PUT")
+            .path("/passphrase")
+            .json(&json!({ "currentPassphrase": "wrong", "newPassphrase": "qwerty" }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = request()
+            .method("PUT")
+            .path("/passphrase")
+            .json(&json!({ "currentPassphrase": "asdf", "newPassphrase": "qwerty" }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(keystore.get(SecUtf8::from("qwerty")).is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn key_managed_by_agent() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let mut ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        ctx.signer_socket = Some(tmp_dir.path().join("agent.sock"));
+        let keystore = ctx.keystore.clone();
+        let api =
+            super::filters(ctx.into(), Subscriptions::default()).recover(http::error::recover);
+
+        // The key never enters the proxy when an agent signs for the peer.
+        let res = request()
+            .method("POST")
+            .path("/")
+            .json(&json!({ "passphrase": "asdf" }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CONFLICT, |have| {
+            assert_eq!(have["variant"], "KEY_MANAGED_BY_AGENT");
+        });
+        assert!(!keystore.has_key()?);
+
+        let res = request()
+            .method("PUT")
+            .path("/passphrase")
+            .json(&json!({ "currentPassphrase": "asdf", "newPassphrase": "qwerty" }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CONFLICT, |have| {
+            assert_eq!(have["variant"], "KEY_MANAGED_BY_AGENT");
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn passphrase_backoff() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        ctx.keystore.create_key(SecUtf8::from("asdf"))?;
+        let api =
+            super::filters(ctx.into(), Subscriptions::default()).recover(http::error::recover);
+
+        for _ in 0..crate::keystore::FREE_ATTEMPTS {
+            let res = request()
+                .method("PUT")
+                .path("/passphrase")
+                .json(&json!({ "currentPassphrase": "wrong", "newPassphrase": "qwerty" }))
+                .reply(&api)
+                .await;
+            assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        }
+
+        let res = request()
+            .method("PUT")
+            .path("/passphrase")
+            .json(&json!({ "currentPassphrase": "asdf", "newPassphrase": "qwerty" }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn export_import() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let keystore = coco::keystore::file(coco::Paths::from_root(tmp_dir.path().join("old"))?);
+        let key = keystore.create_key(SecUtf8::from("asdf"))?;
+        let mut ctx = context::Unsealed::tmp_with_key(&tmp_dir, key).await?;
+        ctx.keystore = Arc::new(keystore);
+        let session = session::initialize_test(&ctx, "cloudhead").await;
+        // Restoring has to replay the whole history of the user.
+        let owner = coco::user::verify(ctx.state.get_user(session.identity.urn.clone()).await?)?;
+        let profile = coco::user::Profile {
+            display_name: Some("Alexis".to_string()),
+            ..coco::user::Profile::default()
+        };
+        let owner = ctx.state.update_owner_profile(&owner, profile).await?;
+        assert_eq!(owner.revision(), 2);
+        let api = super::filters(ctx.into(), Subscriptions::default());
+
+        let res = request()
+            .method("POST")
+            .path("/export")
+            .json(&json!({ "passphrase": "migrate" }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let bundle: serde_json::Value = serde_json::from_slice(res.body())?;
+
+        let new_dir = tempfile::tempdir()?;
+        let mut new_ctx = context::Sealed::tmp(&new_dir)?;
+        let new_keystore = Arc::new(coco::keystore::file(coco::Paths::from_root(
+            new_dir.path().join("new"),
+        )?));
+        new_ctx.keystore = new_keystore.clone();
+        let new_store = new_ctx.store.clone();
+        let new_api =
+            super::filters(new_ctx.into(), Subscriptions::default()).recover(http::error::recover);
+
+        // Without a session the bundle has to be sent as JSON.
+        let res = request()
+            .method("POST")
+            .path("/import")
+            .body(json!({ "bundle": bundle, "passphrase": "migrate" }).to_string())
+            .reply(&new_api)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = request()
+            .method("POST")
+            .path("/import")
+            .json(&json!({ "bundle": bundle, "passphrase": "wrong" }))
+            .reply(&new_api)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = request()
+            .method("POST")
+            .path("/import")
+            .json(&json!({ "bundle": bundle, "passphrase": "migrate" }))
+            .reply(&new_api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            new_keystore.get(SecUtf8::from("asdf"))?.public(),
+            key.public()
+        );
+        assert_eq!(session::get_current(&new_store)?, Some(session.clone()));
+
+        // A key exists now.
+        let res = request()
+            .method("POST")
+            .path("/import")
+            .json(&json!({ "bundle": bundle, "passphrase": "migrate" }))
+            .reply(&new_api)
+            .await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        // Once the peer runs with the imported key, the user is restored.
+        let peer_dir = tempfile::tempdir()?;
+        let peer_ctx = context::Unsealed::tmp_with_key(&peer_dir, key).await?;
+        migration::restore_pending(&new_store, &peer_ctx.state).await?;
+        let restored = peer_ctx.state.get_user(owner.urn()).await?;
+        assert_eq!(restored.revision(), 2);
+        assert_eq!(
+            coco::user::Profile::of(&restored).and_then(|profile| profile.display_name),
+            Some("Alexis".to_string())
+        );
+        assert_eq!(
+            session::get_current(&new_store)?.map(|session| session.identity.urn),
+            Some(owner.urn())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_requires_sealed_keystore() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api =
+            super::filters(ctx.into(), Subscriptions::default()).recover(http::error::recover);
+
+        let res = request()
+            .method("POST")
+            .path("/import")
+            .json(&json!({
+                "bundle": { "version": 1, "sealed": null },
+                "passphrase": "migrate"
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+//! Endpoint exposing [`crate::metrics::Metrics`] for scraping by Prometheus.
+
+use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
+
+use crate::{context, http, http::doc};
+
+/// Documentation of the metrics endpoint.
+#[must_use]
+pub fn docs() -> Vec<doc::Route> {
+    vec![
+        doc::Route::new("GET", "/", "Get the metrics in the Prometheus text format")
+            .body(200, "text/plain"),
+    ]
+}
+
+/// `GET /`
+pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
+    warp::get()
+        .and(path::end())
+        .and(http::with_context(ctx))
+        .and_then(handler::get)
+        .boxed()
+}
+
+/// Metrics handlers for conversion between core domain and HTTP request fulfilment.
+mod handler {
+    use warp::{Rejection, Reply};
+
+    use crate::{context, metrics};
+
+    /// Render the metrics, including the state of the peer if it is running.
+    pub async fn get(ctx: context::Context) -> Result<impl Reply, Rejection> {
+        let peer = match &ctx {
+            context::Context::Unsealed(unsealed) => {
+                let mut peer_control = unsealed.peer_control.clone();
+                Some(metrics::PeerState {
+                    status: peer_control.current_status().await,
+                    connected_peers: peer_control.connected_peers().await.len(),
+                    requests: peer_control.get_project_requests().await,
+                })
+            },
+            context::Context::Sealed(_) => None,
+        };
+
+        Ok(warp::http::Response::builder()
+            .header("content-type", "text/plain; version=0.0.4")
+            .body(ctx.metrics().render(peer)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use warp::http::StatusCode;
+
+    use crate::{context, http::test::request};
+
+    #[tokio::test]
+    async fn get() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.into());
+
+        let res = request().method("GET").path("/").reply(&api).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body = String::from_utf8(res.body().to_vec())?;
+        assert!(body.contains("radicle_proxy_peer_connected_peers 0\n"));
+        assert!(body.contains("radicle_proxy_waiting_room_requests{state=\"cloned\"} 0\n"));
+
+        Ok(())
+    }
+}
@@ -1,16 +1,56 @@
 //! Unidirectional stream of events happening in the proxy. This enables exposing tailing logs to
 //! users, or widgets which show topology information like how many and what peers are connected.
 
-use warp::{filters::BoxedFilter, path, Filter, Reply};
+use schemars::gen::SchemaGenerator;
+use serde::{Deserialize, Serialize};
+use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
 
-use crate::{context, http, notification::Subscriptions};
+use crate::{
+    context, http,
+    http::doc,
+    notification::{inbox, Notification, Subscriptions},
+};
 
-/// SSE based notifications endpoint.
+/// SSE based notifications endpoint and the persistent inbox.
 pub fn filters(ctx: context::Context, subscriptions: Subscriptions) -> BoxedFilter<(impl Reply,)> {
-    local_peer_status_stream(ctx, subscriptions)
+    local_peer_status_stream(ctx.clone(), subscriptions)
+        .or(inbox_filter(ctx.clone()))
+        .or(read_all_filter(ctx.clone()))
+        .or(read_filter(ctx.clone()))
+        .or(dismiss_filter(ctx))
+        .boxed()
+}
+
+/// Documentation of the event stream and the inbox.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new(
+            "GET",
+            "/local_peer_events",
+            "Resumable stream of server-sent events about the local peer",
+        )
+        .events::<Notification>(gen)
+        .errors(&[(400, "INVALID_QUERY"), (403, "FORBIDDEN")]),
+        doc::Route::new(
+            "GET",
+            "/inbox",
+            "List a page of the persisted notifications, newest first",
+        )
+        .response::<inbox::Page>(200, gen)
+        .errors(&[(400, "INVALID_QUERY"), (403, "FORBIDDEN")]),
+        doc::Route::new("PUT", "/inbox/read", "Mark all notifications as read")
+            .empty(204)
+            .errors(doc::AUTH),
+        doc::Route::new("PUT", "/inbox/{id}/read", "Mark a notification as read")
+            .empty(204)
+            .errors(&[(403, "FORBIDDEN"), (404, "NOTIFICATION_NOT_FOUND")]),
+        doc::Route::new("DELETE", "/inbox/{id}", "Dismiss a notification")
+            .empty(204)
+            .errors(&[(403, "FORBIDDEN"), (404, "NOTIFICATION_NOT_FOUND")]),
+    ]
 }
 
-/// `GET /local_peer_events`
+/// `GET /local_peer_events?types=<types>&urn=<urn>`
 pub fn local_peer_status_stream(
     ctx: context::Context,
     subscriptions: Subscriptions,
@@ -18,45 +58,325 @@ pub fn local_peer_status_stream(
     path!("local_peer_events")
         .and(http::with_context_unsealed(ctx))
         .and(warp::any().map(move || subscriptions.clone()))
+        .and(warp::header::optional::<u64>("last-event-id"))
+        .and(http::with_qs_opt::<EventsQuery>())
         .and_then(handler::local_peer_events)
         .boxed()
 }
 
-/// Notification handlers to serve event streams.
+/// `GET /inbox?before=<id>&limit=<limit>&unread=<bool>`
+fn inbox_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("inbox")
+        .and(path::end())
+        .and(warp::get())
+        .and(http::with_context_unsealed(ctx))
+        .and(http::with_qs_opt::<InboxQuery>())
+        .and_then(handler::inbox)
+}
+
+/// `PUT /inbox/read`
+fn read_all_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("inbox" / "read")
+        .and(warp::put())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::read_all)
+}
+
+/// `PUT /inbox/<id>/read`
+fn read_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("inbox" / u64 / "read")
+        .and(warp::put())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::read)
+}
+
+/// `DELETE /inbox/<id>`
+fn dismiss_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("inbox" / u64)
+        .and(warp::delete())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::dismiss)
+}
+
+/// Notification handlers to serve event streams and the inbox.
 mod handler {
     use std::convert::Infallible;
 
     use futures::StreamExt as _;
-    use warp::{sse, Rejection, Reply};
+    use warp::{
+        http::StatusCode,
+        reply,
+        sse::{self, ServerSentEvent as _},
+        Rejection, Reply,
+    };
 
     use crate::{
         context,
-        notification::{self, Notification, Subscriptions},
+        notification::{self, inbox, Notification, Subscriptions},
     };
 
-    /// Sets up local peer events notification stream.
+    /// List a page of the notifications in the inbox.
+    pub async fn inbox(
+        ctx: context::Unsealed,
+        query: Option<super::InboxQuery>,
+    ) -> Result<impl Reply, Rejection> {
+        let query = query.unwrap_or_default();
+        let page = inbox::list(
+            &ctx.store,
+            query.before,
+            query.limit.unwrap_or(inbox::DEFAULT_LIMIT),
+            query.unread.unwrap_or(false),
+        )?;
+
+        Ok(reply::json(&page))
+    }
+
+    /// Mark all notifications in the inbox as read.
+    pub async fn read_all(ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
+        inbox::mark_all_read(&ctx.store)?;
+        Ok(reply::with_status(reply(), StatusCode::NO_CONTENT))
+    }
+
+    /// Mark the notification with `id` as read.
+    pub async fn read(id: u64, ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
+        inbox::mark_read(&ctx.store, id)?;
+        Ok(reply::with_status(reply(), StatusCode::NO_CONTENT))
+    }
+
+    /// Remove the notification with `id` from the inbox.
+    pub async fn dismiss(id: u64, ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
+        inbox::dismiss(&ctx.store, id)?;
+        Ok(reply::with_status(reply(), StatusCode::NO_CONTENT))
+    }
+
+    /// Sets up local peer events notification stream. A client reconnecting with the id of the
+    /// last event it received in `Last-Event-ID` first gets the events it missed, as far as they
+    /// are still in the backlog. The current status of the peer is sent without an id, so it
+    /// doesn't change the id a client resumes from.
     pub async fn local_peer_events(
         ctx: context::Unsealed,
         subscriptions: Subscriptions,
+        last_event_id: Option<u64>,
+        query: Option<super::EventsQuery>,
     ) -> Result<impl Reply, Rejection> {
+        let query = query.unwrap_or_default();
         let mut peer_control = ctx.peer_control;
         let current_status = peer_control.current_status().await;
-        let subscriber = subscriptions.subscribe().await;
-
-        let initial = futures::stream::iter(vec![Notification::LocalPeer(
-            notification::LocalPeer::StatusChanged {
-                old: current_status.clone(),
-                new: current_status,
-            },
-        )]);
-        let filter = |notification: Notification| async move {
-            match notification.clone() {
-                Notification::LocalPeer(event) => Some(Ok::<_, Infallible>(sse::json(event))),
-            }
+        let (_, missed, subscriber) = subscriptions.subscribe_since(last_event_id).await;
+
+        // The current status follows the missed events, which may contain outdated ones.
+        let current = Notification::LocalPeer(notification::LocalPeer::StatusChanged {
+            old: current_status.clone(),
+            new: current_status,
+        });
+        let filter = move |(id, notification): (Option<u64>, Notification)| {
+            let sse_event = notification
+                .to_json()
+                .ok()
+                .filter(|data| query.matches(data))
+                .map(|data| {
+                    Ok::<_, Infallible>(match id {
+                        Some(id) => (sse::id(id.to_string()), sse::json(data)).into_a(),
+                        None => sse::json(data).into_b(),
+                    })
+                });
+            async move { sse_event }
         };
 
         Ok(sse::reply(
-            sse::keep_alive().stream(initial.chain(subscriber).filter_map(filter)),
+            sse::keep_alive().stream(
+                futures::stream::iter(missed)
+                    .map(|event| (Some(event.id), event.notification))
+                    .chain(futures::stream::iter(vec![(None, current)]))
+                    .chain(subscriber.map(|event| (Some(event.id), event.notification)))
+                    .filter_map(filter),
+            ),
         ))
     }
 }
+
+/// Filters for the events of the notification stream.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventsQuery {
+    /// Comma separated list of the event types to send, e.g. `projectUpdated,requestCloned`.
+    types: Option<String>,
+    /// Only send events about the project with this urn.
+    urn: Option<String>,
+}
+
+impl EventsQuery {
+    /// Returns `true` if the event serialised to `data` passes the filters.
+    fn matches(&self, data: &serde_json::Value) -> bool {
+        let type_matches = self.types.as_ref().map_or(true, |types| {
+            data["type"].as_str().map_or(false, |kind| {
+                types.split(',').any(|allowed| allowed.trim() == kind)
+            })
+        });
+        let urn_matches = self
+            .urn
+            .as_ref()
+            .map_or(true, |urn| data["urn"].as_str() == Some(urn.as_str()));
+
+        type_matches && urn_matches
+    }
+}
+
+/// Query for a page of the inbox.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboxQuery {
+    /// Only list notifications older than the one with this id.
+    before: Option<u64>,
+    /// Maximum number of notifications to list.
+    limit: Option<usize>,
+    /// Only list unread notifications.
+    unread: Option<bool>,
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::body::HttpBody as _;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    use warp::{http::StatusCode, Reply as _};
+
+    use crate::{
+        context, http,
+        http::test::request,
+        notification::{inbox, LocalPeer, Notification, Subscriptions},
+    };
+
+    #[test]
+    fn filter_events() {
+        let query = super::EventsQuery {
+            types: Some("requestCloned,requestTimedOut".to_string()),
+            urn: Some("rad:git:hwd1".to_string()),
+        };
+
+        assert!(query.matches(&json!({ "type": "requestCloned", "urn": "rad:git:hwd1" })));
+        assert!(!query.matches(&json!({ "type": "requestCloned", "urn": "rad:git:other" })));
+        assert!(!query.matches(&json!({ "type": "statusChanged" })));
+        assert!(super::EventsQuery::default().matches(&json!({ "type": "statusChanged" })));
+    }
+
+    #[tokio::test]
+    async fn resume_events() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let subscriptions = Subscriptions::default();
+        let api = super::filters(ctx.into(), subscriptions.clone());
+
+        let urn = coco::Urn::new(
+            coco::Hash::hash(b"project"),
+            coco::uri::Protocol::Git,
+            coco::uri::Path::empty(),
+        );
+        let timed_out = || Notification::LocalPeer(LocalPeer::RequestTimedOut { urn: urn.clone() });
+        subscriptions.broadcast(timed_out()).await;
+        let (seen, _, _) = subscriptions.subscribe_since(None).await;
+        subscriptions.broadcast(timed_out()).await;
+
+        let reply = request()
+            .method("GET")
+            .path("/local_peer_events")
+            .header("last-event-id", seen.to_string())
+            .filter(&api)
+            .await
+            .expect("failed to subscribe to the events");
+        let mut body = reply.into_response().into_body();
+        let mut stream = String::new();
+        while stream.matches("\n\n").count() < 2 {
+            let chunk = body.data().await.expect("the event stream ended")?;
+            stream.push_str(std::str::from_utf8(&chunk)?);
+        }
+
+        // Only the missed event is replayed, followed by the current status without an id.
+        let events = stream
+            .split("\n\n")
+            .filter(|event| !event.is_empty())
+            .map(|event| {
+                let mut id = None;
+                let mut data = Value::Null;
+                for line in event.lines() {
+                    if let Some(value) = line.strip_prefix("id:") {
+                        id = Some(value.to_string());
+                    } else if let Some(value) = line.strip_prefix("data:") {
+                        data = serde_json::from_str(value).expect("failed to parse data");
+                    }
+                }
+                (id, data["type"].clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (Some((seen + 1).to_string()), json!("requestTimedOut")),
+                (None, json!("statusChanged")),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn inbox() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+
+        let urn = coco::Urn::new(
+            coco::Hash::hash(b"project"),
+            coco::uri::Protocol::Git,
+            coco::uri::Path::empty(),
+        );
+        let item = inbox::push(&ctx.store, LocalPeer::RequestTimedOut { urn: urn.clone() })?;
+
+        let res = request()
+            .method("GET")
+            .path("/inbox?unread=true")
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(
+                have,
+                json!({
+                    "items": [{
+                        "id": item.id,
+                        "timestamp": item.timestamp,
+                        "read": false,
+                        "event": { "type": "requestTimedOut", "urn": urn },
+                    }],
+                    "unread": 1,
+                    "next": null,
+                })
+            );
+        });
+
+        let res = request()
+            .method("PUT")
+            .path(&format!("/inbox/{}/read", item.id))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(inbox::list(&ctx.store, None, 10, true)?.items, vec![]);
+
+        let res = request()
+            .method("DELETE")
+            .path(&format!("/inbox/{}", item.id))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(inbox::list(&ctx.store, None, 10, false)?.items, vec![]);
+
+        Ok(())
+    }
+}
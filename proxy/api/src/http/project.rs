@@ -2,19 +2,27 @@
 
 use std::path::PathBuf;
 
+use schemars::{gen::SchemaGenerator, JsonSchema};
 use serde::{Deserialize, Serialize};
 use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
 
-use crate::{context, http};
+use crate::{
+    context, http,
+    http::doc,
+    notification::Subscriptions,
+    project::{self, working_copy},
+};
 
+mod import;
 mod request;
 
 /// Combination of all routes.
-pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
+pub fn filters(ctx: context::Context, subscriptions: Subscriptions) -> BoxedFilter<(impl Reply,)> {
     checkout_filter(ctx.clone())
         .or(create_filter(ctx.clone()))
         .or(failed_filter(ctx.clone()))
         .or(get_filter(ctx.clone()))
+        .or(path("import").and(import::filters(ctx.clone(), subscriptions)))
         .or(owner_contributed_filter(ctx.clone()))
         .or(owner_tracked_filter(ctx.clone()))
         .or(peers_filter(ctx.clone()))
@@ -22,10 +30,115 @@ pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
         .or(track_filter(ctx.clone()))
         .or(track_filter(ctx.clone()))
         .or(untrack_filter(ctx.clone()))
-        .or(user_filter(ctx))
+        .or(user_filter(ctx.clone()))
+        .or(validate_filter(ctx.clone()))
+        .or(working_copies_filter(ctx))
         .boxed()
 }
 
+/// Serialized form of [`project::Full`] in the documentation.
+type FullSchema = project::Project<coco::source::StatsSchema>;
+
+/// Documentation of all routes.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    let mut routes = vec![
+        doc::Route::new(
+            "POST",
+            "/{urn}/checkout",
+            "Check out a working copy of a project",
+        )
+        .request::<CheckoutInput>(gen)
+        .response::<PathBuf>(201, gen)
+        .response::<coco::project::checkout::Update>(200, gen)
+        .errors(&[
+            (403, "FORBIDDEN"),
+            (404, "NOT_FOUND"),
+            (404, "REVISION_NOT_FOUND"),
+            (409, "WORKING_DIRECTORY_EXISTS"),
+            (500, "FETCH_FAILED"),
+        ]),
+        doc::Route::new(
+            "POST",
+            "/",
+            "Create a project from a new or existing repository",
+        )
+        .request::<coco::project::Create>(gen)
+        .response::<FullSchema>(201, gen)
+        .errors(&[
+            (400, "MISSING_DEFAULT_BRANCH"),
+            (400, "NOT_A_REPO"),
+            (401, "UNAUTHORIZED"),
+            (403, "FORBIDDEN"),
+            (404, "PATH_DOES_NOT_EXIST"),
+            (409, "ENTITY_EXISTS"),
+            (409, "PATH_EXISTS"),
+        ]),
+        doc::Route::new("GET", "/failed", "List projects which failed to load")
+            .response::<Vec<project::Failure>>(200, gen)
+            .errors(doc::AUTH),
+        doc::Route::new("GET", "/{urn}", "Get a project")
+            .response::<FullSchema>(200, gen)
+            .errors(doc::STATE),
+        doc::Route::new(
+            "GET",
+            "/contributed",
+            "List projects the session identity contributed to",
+        )
+        .response::<Vec<FullSchema>>(200, gen)
+        .errors(doc::AUTH),
+        doc::Route::new(
+            "GET",
+            "/tracked",
+            "List projects tracked by the session identity",
+        )
+        .response::<Vec<FullSchema>>(200, gen)
+        .errors(doc::AUTH),
+        doc::Route::new("GET", "/{urn}/peers", "List the peers of a project")
+            .response::<Vec<project::Peer>>(200, gen)
+            .errors(doc::STATE),
+        doc::Route::new("PUT", "/{urn}/track/{peer_id}", "Track a peer of a project")
+            .response::<bool>(200, gen)
+            .errors(doc::STATE),
+        doc::Route::new(
+            "PUT",
+            "/{urn}/untrack/{peer_id}",
+            "Stop tracking a peer of a project",
+        )
+        .response::<bool>(200, gen)
+        .errors(doc::STATE),
+        doc::Route::new("GET", "/user/{urn}", "List projects tracked by an identity")
+            .response::<Vec<FullSchema>>(200, gen)
+            .errors(doc::STATE),
+        doc::Route::new(
+            "POST",
+            "/validate",
+            "Check the input for a project creation without creating it",
+        )
+        .request::<coco::project::Create>(gen)
+        .response::<Vec<http::error::Error>>(200, gen)
+        .errors(doc::OWNER),
+        doc::Route::new(
+            "GET",
+            "/{urn}/working-copies",
+            "List the known working copies of a project",
+        )
+        .response::<Vec<working_copy::Entry>>(200, gen)
+        .errors(doc::AUTH),
+    ];
+    routes.extend(
+        import::docs(gen)
+            .into_iter()
+            .map(|route| route.mount("/import")),
+    );
+    routes.extend(
+        request::docs(gen)
+            .into_iter()
+            .map(|route| route.mount("/requests")),
+    );
+
+    routes
+}
+
 /// `POST /<urn>/checkout`
 fn checkout_filter(
     ctx: context::Context,
@@ -145,26 +258,75 @@ fn user_filter(
         .and_then(handler::list_user)
 }
 
+/// `POST /validate`
+fn validate_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("validate")
+        .and(path::end())
+        .and(warp::post())
+        .and(http::with_context_unsealed(ctx.clone()))
+        .and(http::with_owner_guard(ctx))
+        .and(warp::body::json())
+        .and_then(handler::validate)
+}
+
+/// `GET /<urn>/working-copies`
+fn working_copies_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path::param::<coco::Urn>()
+        .and(path("working-copies"))
+        .and(path::end())
+        .and(warp::get())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::working_copies)
+}
+
 /// Project handlers to implement conversion and translation between core domain and http request
 /// fullfilment.
 mod handler {
     use warp::{http::StatusCode, reply, Rejection, Reply};
 
-    use crate::{context, error::Error, http, project};
-
-    /// Checkout a [`project::Project`]'s source code.
+    use crate::{
+        context,
+        error::Error,
+        http,
+        project::{
+            self,
+            working_copy::{self, WorkingCopy},
+        },
+    };
+
+    /// Checkout a [`project::Project`]'s source code, or update the working copy if it was checked
+    /// out before.
     pub async fn checkout(
         urn: coco::Urn,
         ctx: context::Unsealed,
-        super::CheckoutInput { path, peer_id }: super::CheckoutInput,
+        super::CheckoutInput {
+            path,
+            peer_id,
+            target,
+        }: super::CheckoutInput,
     ) -> Result<impl Reply, Rejection> {
         let peer_id = http::guard_self_peer_id(&ctx.state, peer_id);
-        let path = ctx
+        let outcome = ctx
             .state
-            .checkout(urn, peer_id, path)
+            .checkout(urn.clone(), peer_id, path, target)
             .await
             .map_err(Error::from)?;
-        Ok(reply::with_status(reply::json(&path), StatusCode::CREATED))
+        let reply = match &outcome {
+            coco::project::checkout::Outcome::Cloned(path) => {
+                reply::with_status(reply::json(path), StatusCode::CREATED)
+            },
+            coco::project::checkout::Outcome::Updated(update) => {
+                reply::with_status(reply::json(update), StatusCode::OK)
+            },
+        };
+        let path = outcome.into_path();
+        register_working_copy(&ctx.store, &urn, WorkingCopy::new(path, peer_id));
+
+        Ok(reply)
     }
 
     /// Create a new [`project::Project`].
@@ -173,12 +335,14 @@ mod handler {
         owner: coco::user::User,
         input: coco::project::Create,
     ) -> Result<impl Reply, Rejection> {
+        let path = input.repo.full_path();
         let meta = ctx
             .state
             .init_project(&owner, input)
             .await
             .map_err(Error::from)?;
         let urn = meta.urn();
+        register_working_copy(&ctx.store, &urn, WorkingCopy::new(path, None));
 
         let branch = ctx
             .state
@@ -272,6 +436,52 @@ mod handler {
         ctx.state.untrack(urn, peer_id).await.map_err(Error::from)?;
         Ok(reply::json(&true))
     }
+
+    /// Report every problem that would prevent creating a [`project::Project`] from `input`,
+    /// without creating it. An empty list means the creation is expected to succeed.
+    pub async fn validate(
+        ctx: context::Unsealed,
+        owner: coco::user::User,
+        input: coco::project::Create,
+    ) -> Result<impl Reply, Rejection> {
+        let problems = ctx
+            .state
+            .validate_project(&owner, &input)
+            .map_err(Error::from)?
+            .iter()
+            .map(|err| {
+                let (_code, variant) = http::error::validation(err);
+                http::error::Error {
+                    message: err.to_string(),
+                    variant: variant.to_string(),
+                    retry_after: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(reply::json(&problems))
+    }
+
+    /// List the working copies of the project with their current status.
+    pub async fn working_copies(
+        urn: coco::Urn,
+        ctx: context::Unsealed,
+    ) -> Result<impl Reply, Rejection> {
+        Ok(reply::json(&working_copy::entries(&ctx.store, &urn)?))
+    }
+
+    /// Register `working_copy` for the project identified by `urn`. The working copy was already
+    /// created on disk, so a failure is only logged instead of failing the request.
+    fn register_working_copy(store: &kv::Store, urn: &coco::Urn, working_copy: WorkingCopy) {
+        let path = working_copy.path.clone();
+        if let Err(err) = working_copy::register(store, urn, working_copy) {
+            log::warn!(
+                "Failed to register working copy @ '{}': {}",
+                path.display(),
+                err
+            );
+        }
+    }
 }
 
 /// Bundled input data for project creation.
@@ -286,13 +496,17 @@ pub struct CreateInput {
 }
 
 /// Bundled input data for project checkout.
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CheckoutInput {
     /// Location on the filesystem where the working copy should be created.
     path: PathBuf,
     /// Which peer are we checking out from. If it's `None`, we're checking out our own project.
+    #[schemars(with = "Option<String>")]
     peer_id: Option<coco::PeerId>,
+    /// Which branch, tag or commit of the peer to check out. If it's `None`, we're checking out
+    /// the default branch.
+    target: Option<coco::project::checkout::Target>,
 }
 
 /// User provided metadata for project manipulation.
@@ -312,11 +526,13 @@ pub struct MetadataInput {
 mod test {
     use pretty_assertions::assert_eq;
     use serde_json::{json, Value};
-    use warp::{http::StatusCode, test::request};
+    use warp::http::StatusCode;
 
     use radicle_surf::vcs::git::git2;
 
-    use crate::{context, http, identity, project, session};
+    use crate::{
+        context, http, http::test::request, identity, notification::Subscriptions, project, session,
+    };
 
     #[tokio::test]
     async fn checkout() -> Result<(), Box<dyn std::error::Error>> {
@@ -324,7 +540,7 @@ mod test {
         let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
         let dir = tempfile::tempdir_in(repos_dir.path())?;
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         let urn = {
             let handle = "cloudhead";
@@ -349,6 +565,7 @@ mod test {
         let input = super::CheckoutInput {
             path: dir.path().to_path_buf(),
             peer_id: None,
+            target: None,
         };
         let res = request()
             .method("POST")
@@ -403,13 +620,437 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn checkout_branch() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+
+        let urn = replicate_platinum(&ctx).await?;
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({
+                "path": dir.path(),
+                "target": {
+                    "type": "branch",
+                    "name": "dev",
+                },
+            }))
+            .reply(&api)
+            .await;
+
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+
+        let repo = git2::Repository::open(dir.path().join("git-platinum"))?;
+        let head = repo.head()?;
+        assert_eq!(head.shorthand(), Some("dev"));
+        let upstream = repo
+            .find_branch("dev", git2::BranchType::Local)?
+            .upstream()?;
+        assert_eq!(upstream.name()?, Some("rad/dev"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checkout_tag() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+        let urn = replicate_platinum(&ctx).await?;
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({
+                "path": dir.path(),
+                "target": {
+                    "type": "tag",
+                    "name": "v0.1.0",
+                },
+            }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+
+        let repo = git2::Repository::open(dir.path().join("git-platinum"))?;
+        let tag = repo.find_reference("refs/tags/v0.1.0")?.peel_to_commit()?;
+        assert!(repo.head_detached()?);
+        assert_eq!(repo.head()?.peel_to_commit()?.id(), tag.id());
+        assert!(repo.statuses(None)?.is_empty());
+
+        let missing = tempfile::tempdir_in(repos_dir.path())?;
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({
+                "path": missing.path(),
+                "target": {
+                    "type": "tag",
+                    "name": "v9.9.9",
+                },
+            }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::NOT_FOUND, |have| {
+            assert_eq!(have["variant"], json!("REVISION_NOT_FOUND"));
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checkout_commit() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+        let urn = replicate_platinum(&ctx).await?;
+        let sha = "3873745c8f6ffb45c990eb23b491d4b4b6182f95";
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({
+                "path": dir.path(),
+                "target": {
+                    "type": "commit",
+                    "sha": sha,
+                },
+            }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+
+        let repo = git2::Repository::open(dir.path().join("git-platinum"))?;
+        assert!(repo.head_detached()?);
+        assert_eq!(repo.head()?.peel_to_commit()?.id().to_string(), sha);
+        assert!(repo.statuses(None)?.is_empty());
+
+        // Checking out the commit again keeps `HEAD` detached at it.
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({
+                "path": dir.path(),
+                "target": {
+                    "type": "commit",
+                    "sha": sha,
+                },
+            }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(have["branch"], json!(null));
+            assert_eq!(have["fastForward"], json!({ "type": "skipped" }));
+        });
+
+        let missing = tempfile::tempdir_in(repos_dir.path())?;
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({
+                "path": missing.path(),
+                "target": {
+                    "type": "commit",
+                    "sha": "0000000000000000000000000000000000000001",
+                },
+            }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::NOT_FOUND, |have| {
+            assert_eq!(have["variant"], json!("REVISION_NOT_FOUND"));
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checkout_update() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+
+        let urn = replicate_platinum(&ctx).await?;
+
+        let input = json!({ "path": dir.path() });
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(have["branch"], json!("master"));
+            assert_eq!(have["fetched"], json!(["rad"]));
+            assert_eq!(have["created"], json!([]));
+            assert_eq!(have["fastForward"], json!({ "type": "upToDate" }));
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checkout_update_target() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+        let urn = replicate_platinum(&ctx).await?;
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({ "path": dir.path() }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({
+                "path": dir.path(),
+                "target": {
+                    "type": "branch",
+                    "name": "dev",
+                },
+            }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(have["branch"], json!("dev"));
+            assert_eq!(have["fastForward"], json!({ "type": "upToDate" }));
+        });
+
+        let repo = git2::Repository::open(dir.path().join("git-platinum"))?;
+        assert_eq!(repo.head()?.shorthand(), Some("dev"));
+        let upstream = repo
+            .find_branch("dev", git2::BranchType::Local)?
+            .upstream()?;
+        assert_eq!(upstream.name()?, Some("rad/dev"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checkout_update_forwarded() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+        let urn = replicate_platinum(&ctx).await?;
+
+        let input = json!({ "path": dir.path() });
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+        let repo = git2::Repository::open(dir.path().join("git-platinum"))?;
+        let (parent, tip) = rewind(&repo)?;
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(have["branch"], json!("master"));
+            assert_eq!(
+                have["fastForward"],
+                json!({
+                    "type": "forwarded",
+                    "from": parent.to_string(),
+                    "to": tip.to_string(),
+                })
+            );
+        });
+        assert_eq!(repo.head()?.peel_to_commit()?.id(), tip);
+        assert!(repo.statuses(None)?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checkout_update_diverged() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+        let urn = replicate_platinum(&ctx).await?;
+
+        let input = json!({ "path": dir.path() });
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+        let repo = git2::Repository::open(dir.path().join("git-platinum"))?;
+        let (parent, _tip) = rewind(&repo)?;
+
+        // Commit on top of the rewound branch, so it has a commit its upstream doesn't have.
+        std::fs::write(dir.path().join("git-platinum").join("new.txt"), "new")?;
+        let mut index = repo.index()?;
+        index.add_path(std::path::Path::new("new.txt"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = git2::Signature::now("cloudhead", "cloudhead@radicle.xyz")?;
+        let local = repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Add new.txt",
+            &tree,
+            &[&repo.find_commit(parent)?],
+        )?;
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(have["branch"], json!("master"));
+            assert_eq!(have["fastForward"], json!({ "type": "diverged" }));
+        });
+        assert_eq!(repo.head()?.peel_to_commit()?.id(), local);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checkout_update_conflict() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+        let urn = replicate_platinum(&ctx).await?;
+
+        let input = json!({ "path": dir.path() });
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+        let repo = git2::Repository::open(dir.path().join("git-platinum"))?;
+        let (parent, tip) = rewind(&repo)?;
+
+        // Change a file locally which the fast-forward would overwrite.
+        let diff = repo.diff_tree_to_tree(
+            Some(&repo.find_commit(parent)?.tree()?),
+            Some(&repo.find_commit(tip)?.tree()?),
+            None,
+        )?;
+        let changed = diff
+            .deltas()
+            .find_map(|delta| delta.new_file().path().map(std::path::Path::to_path_buf))
+            .unwrap();
+        let changed = dir.path().join("git-platinum").join(changed);
+        if let Some(parent_dir) = changed.parent() {
+            std::fs::create_dir_all(parent_dir)?;
+        }
+        std::fs::write(&changed, "local change")?;
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&input)
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(have["branch"], json!("master"));
+            assert_eq!(have["fastForward"], json!({ "type": "conflict" }));
+        });
+        assert_eq!(repo.head()?.peel_to_commit()?.id(), parent);
+        assert_eq!(std::fs::read_to_string(&changed)?, "local change");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn working_copies() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let dir = tempfile::tempdir_in(repos_dir.path())?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+
+        let urn = replicate_platinum(&ctx).await?;
+
+        let res = request()
+            .method("POST")
+            .path(&format!("/{}/checkout", urn))
+            .json(&json!({ "path": dir.path() }))
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::CREATED, |_| {});
+        std::fs::write(dir.path().join("git-platinum").join("new.txt"), "new")?;
+
+        let res = request()
+            .method("GET")
+            .path(&format!("/{}/working-copies", urn))
+            .reply(&api)
+            .await;
+        let working_copies = project::working_copy::list(&ctx.store, &urn)?;
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(
+                have,
+                json!([{
+                    "path": dir.path().join("git-platinum"),
+                    "peerId": null,
+                    "timestamp": working_copies[0].timestamp,
+                    "status": {
+                        "head": "master",
+                        "dirty": true,
+                        "divergence": {
+                            "ahead": 0,
+                            "behind": 0,
+                        },
+                    },
+                }])
+            );
+        });
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn create_new() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempfile::tempdir()?;
         let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
         let dir = tempfile::tempdir_in(repos_dir.path())?;
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         {
             let handle = "cloudhead";
@@ -470,7 +1111,7 @@ mod test {
         let dir = tempfile::tempdir_in(repos_dir.path())?;
         let repo_path = dir.path().join("Upstream");
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         {
             let handle = "cloudhead";
@@ -545,7 +1186,7 @@ mod test {
     async fn get() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempfile::tempdir()?;
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         let urn = {
             let owner = ctx.state.init_owner("cloudhead").await?;
@@ -582,7 +1223,7 @@ mod test {
     async fn list_for_user() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempfile::tempdir()?;
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         let owner = ctx.state.init_owner("cloudhead").await?;
         coco::control::setup_fixtures(&ctx.state, &owner).await?;
@@ -612,7 +1253,7 @@ mod test {
     async fn list_contributed() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempfile::tempdir()?;
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         let owner = ctx.state.init_owner("cloudhead").await?;
 
@@ -637,7 +1278,7 @@ mod test {
     async fn track() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempfile::tempdir()?;
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         let owner = ctx.state.init_owner("cloudhead").await?;
         coco::control::setup_fixtures(&ctx.state, &owner).await?;
@@ -665,7 +1306,7 @@ mod test {
     async fn untrack() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempfile::tempdir()?;
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         let owner = ctx.state.init_owner("cloudhead").await?;
         coco::control::setup_fixtures(&ctx.state, &owner).await?;
@@ -693,7 +1334,7 @@ mod test {
     async fn untrack_after_track() -> Result<(), Box<dyn std::error::Error>> {
         let tmp_dir = tempfile::tempdir()?;
         let ctx = context::Unsealed::tmp(&tmp_dir).await?;
-        let api = super::filters(ctx.clone().into());
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
 
         let owner = ctx.state.init_owner("cloudhead").await?;
         coco::control::setup_fixtures(&ctx.state, &owner).await?;
@@ -730,4 +1371,92 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn validate() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let repo_path = repos_dir.path().join("Upstream");
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into(), Subscriptions::default());
+
+        {
+            let handle = "cloudhead";
+            let id = identity::create(&ctx.state, handle).await?;
+            session::initialize(&ctx.store, id, &ctx.default_seeds)?;
+        };
+
+        coco::control::clone_platinum(repo_path.clone())?;
+        let repo = git2::Repository::open(&repo_path)?;
+        repo.remote(coco::config::RAD_REMOTE, "rad://elsewhere.git")?;
+
+        let project = json!({
+            "repo": {
+                "type": "existing",
+                "path": repo_path,
+            },
+            "description": "Desktop client for radicle.",
+            "defaultBranch": "missing",
+        });
+
+        let res = request()
+            .method("POST")
+            .path("/validate")
+            .json(&project)
+            .reply(&api)
+            .await;
+
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            let variants = have
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|problem| problem["variant"].clone())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                variants,
+                vec![json!("MISSING_DEFAULT_BRANCH"), json!("URL_MISMATCH")]
+            );
+        });
+        assert!(project::Projects::list(&ctx.state)
+            .await?
+            .into_iter()
+            .next()
+            .is_none());
+        assert!(repo.find_reference("refs/remotes/rad/master").is_err());
+
+        Ok(())
+    }
+
+    /// Initialise the owner and the session of `ctx` and replicate the platinum fixture, returns
+    /// the urn of the project.
+    async fn replicate_platinum(
+        ctx: &context::Unsealed,
+    ) -> Result<coco::Urn, Box<dyn std::error::Error>> {
+        let owner = ctx.state.init_owner("cloudhead").await?;
+        session::initialize(
+            &ctx.store,
+            (ctx.state.peer_id(), owner.clone()).into(),
+            &ctx.default_seeds,
+        )?;
+
+        let platinum_project = coco::control::replicate_platinum(
+            &ctx.state,
+            &owner,
+            "git-platinum",
+            "fixture data",
+            coco::control::default_branch(),
+        )
+        .await?;
+        Ok(platinum_project.urn())
+    }
+
+    /// Move the checked out branch of `repo` and its working tree back by one commit, returns the
+    /// commit the branch points to now and the one it pointed to before.
+    fn rewind(repo: &git2::Repository) -> Result<(git2::Oid, git2::Oid), git2::Error> {
+        let tip = repo.head()?.peel_to_commit()?;
+        let parent = tip.parent(0)?;
+        repo.reset(parent.as_object(), git2::ResetType::Hard, None)?;
+        Ok((parent.id(), tip.id()))
+    }
 }
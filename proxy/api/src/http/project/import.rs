@@ -0,0 +1,332 @@
+//! Endpoints for creating projects from existing repositories in bulk.
+
+use std::path::PathBuf;
+
+use schemars::{gen::SchemaGenerator, JsonSchema};
+use serde::{Deserialize, Serialize};
+use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
+
+use crate::{context, http, http::doc, notification::Subscriptions};
+
+/// Combination of all routes.
+pub fn filters(ctx: context::Context, subscriptions: Subscriptions) -> BoxedFilter<(impl Reply,)> {
+    scan_filter(ctx.clone())
+        .or(create_filter(ctx, subscriptions))
+        .boxed()
+}
+
+/// Documentation of all routes.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new(
+            "POST",
+            "/scan",
+            "Find repositories which can be imported as projects",
+        )
+        .request::<ScanInput>(gen)
+        .response::<Vec<coco::project::import::Candidate>>(200, gen)
+        .errors(doc::AUTH),
+        doc::Route::new(
+            "POST",
+            "/",
+            "Start importing repositories as projects, reporting progress as notifications",
+        )
+        .request::<CreateInput>(gen)
+        .response::<Vec<PathBuf>>(202, gen)
+        .errors(doc::OWNER),
+    ]
+}
+
+/// `POST /scan`
+fn scan_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("scan")
+        .and(path::end())
+        .and(warp::post())
+        .and(http::with_context_unsealed(ctx))
+        .and(warp::body::json())
+        .and_then(handler::scan)
+}
+
+/// `POST /`
+fn create_filter(
+    ctx: context::Context,
+    subscriptions: Subscriptions,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path::end()
+        .and(warp::post())
+        .and(http::with_context_unsealed(ctx.clone()))
+        .and(http::with_owner_guard(ctx))
+        .and(warp::any().map(move || subscriptions.clone()))
+        .and(warp::body::json())
+        .and_then(handler::create)
+}
+
+/// Import handlers for conversion between core domain and http request fullfilment.
+mod handler {
+    use std::path::PathBuf;
+
+    use warp::{http::StatusCode, reply, Rejection, Reply};
+
+    use crate::{
+        context, error,
+        notification::{Import, Notification, Subscriptions},
+        project::working_copy::{self, WorkingCopy},
+    };
+
+    /// Scan a directory for repositories and report if projects can be created from them.
+    pub async fn scan(
+        _ctx: context::Unsealed,
+        super::ScanInput {
+            path,
+            default_branch,
+        }: super::ScanInput,
+    ) -> Result<impl Reply, Rejection> {
+        let candidates = tokio::task::spawn_blocking(move || {
+            coco::project::import::scan(&path, &default_branch)
+        })
+        .await
+        .map_err(|join_err| error::Error::Io(join_err.into()))?
+        .map_err(error::Error::from)?;
+
+        Ok(reply::json(&candidates))
+    }
+
+    /// Create projects for the selected repositories in the background, reporting progress as
+    /// [`Import`] notifications.
+    pub async fn create(
+        ctx: context::Unsealed,
+        owner: coco::user::User,
+        subscriptions: Subscriptions,
+        input: super::CreateInput,
+    ) -> Result<impl Reply, Rejection> {
+        let paths = input
+            .projects
+            .iter()
+            .map(|project| project.path.clone())
+            .collect::<Vec<PathBuf>>();
+
+        tokio::spawn(run(ctx, owner, subscriptions, input.projects));
+
+        Ok(reply::with_status(
+            reply::json(&paths),
+            StatusCode::ACCEPTED,
+        ))
+    }
+
+    /// Create a project for every entry of `projects` one after the other.
+    async fn run(
+        ctx: context::Unsealed,
+        owner: coco::user::User,
+        subscriptions: Subscriptions,
+        projects: Vec<super::ProjectInput>,
+    ) {
+        let mut created = 0;
+        let mut failed = 0;
+
+        subscriptions
+            .broadcast(Notification::Import(Import::Started {
+                total: projects.len(),
+            }))
+            .await;
+
+        for project in projects {
+            let path = project.path.clone();
+            let create = coco::project::Create {
+                repo: coco::project::Repo::Existing { path: project.path },
+                description: project.description,
+                default_branch: project.default_branch,
+            };
+
+            let notification = match ctx.state.init_project(&owner, create).await {
+                Ok(meta) => {
+                    created += 1;
+                    let working_copy = WorkingCopy::new(path.clone(), None);
+                    if let Err(err) = working_copy::register(&ctx.store, &meta.urn(), working_copy)
+                    {
+                        log::warn!("Failed to register '{}': {}", path.display(), err);
+                    }
+                    Import::ProjectCreated {
+                        path,
+                        urn: meta.urn(),
+                    }
+                },
+                Err(err) => {
+                    log::warn!("Failed to import '{}': {}", path.display(), err);
+                    failed += 1;
+                    Import::ProjectFailed {
+                        path,
+                        reason: err.to_string(),
+                    }
+                },
+            };
+            subscriptions
+                .broadcast(Notification::Import(notification))
+                .await;
+        }
+
+        subscriptions
+            .broadcast(Notification::Import(Import::Finished { created, failed }))
+            .await;
+    }
+}
+
+/// Bundled input data for scanning a directory.
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanInput {
+    /// Directory to look for repositories in.
+    path: PathBuf,
+    /// Branch every repository is checked for.
+    #[schemars(with = "String")]
+    default_branch: coco::git_ext::OneLevel,
+}
+
+/// Bundled input data for a bulk import.
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[schemars(rename = "ImportProjectsInput")]
+#[serde(rename_all = "camelCase")]
+pub struct CreateInput {
+    /// Repositories to create projects from.
+    projects: Vec<ProjectInput>,
+}
+
+/// Input data for a single repository of a bulk import.
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInput {
+    /// Location of the existing working copy.
+    path: PathBuf,
+    /// Long form outline.
+    description: String,
+    /// Configured default branch.
+    #[schemars(with = "String")]
+    default_branch: coco::git_ext::OneLevel,
+}
+
+#[allow(clippy::panic)]
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use warp::http::StatusCode;
+
+    use crate::{
+        context, http,
+        http::test::request,
+        notification::{Import, Notification, Subscriptions},
+        project::working_copy,
+        session,
+    };
+
+    #[tokio::test]
+    async fn scan() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let repo_path = repos_dir.path().join("git-platinum");
+        coco::control::clone_platinum(&repo_path)?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.into(), Subscriptions::default());
+
+        let res = request()
+            .method("POST")
+            .path("/scan")
+            .json(&super::ScanInput {
+                path: repos_dir.path().to_path_buf(),
+                default_branch: coco::control::default_branch(),
+            })
+            .reply(&api)
+            .await;
+
+        http::test::assert_response(&res, StatusCode::OK, |have| {
+            assert_eq!(
+                have,
+                json!([{
+                    "path": repo_path,
+                    "name": "git-platinum",
+                    "head": "master",
+                    "branches": ["dev", "master"],
+                    "status": { "type": "ready" },
+                }])
+            );
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repos_dir = tempfile::tempdir_in(tmp_dir.path())?;
+        let repo_path = repos_dir.path().join("git-platinum");
+        coco::control::clone_platinum(&repo_path)?;
+        let missing_path = repos_dir.path().join("missing");
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let _session = session::initialize_test(&ctx, "cloudhead").await;
+        let subscriptions = Subscriptions::default();
+        let mut subscription = subscriptions.subscribe().await;
+        let api = super::filters(ctx.clone().into(), subscriptions);
+
+        let res = request()
+            .method("POST")
+            .path("/")
+            .json(&json!({
+                "projects": [
+                    {
+                        "path": repo_path,
+                        "description": "surfing",
+                        "defaultBranch": "master",
+                    },
+                    {
+                        "path": missing_path,
+                        "description": "nowhere",
+                        "defaultBranch": "master",
+                    },
+                ],
+            }))
+            .reply(&api)
+            .await;
+
+        http::test::assert_response(&res, StatusCode::ACCEPTED, |have| {
+            assert_eq!(have, json!([repo_path, missing_path]));
+        });
+
+        let mut imports = vec![];
+        while let Some(event) = subscription.recv().await {
+            if let Notification::Import(import) = event.notification {
+                let finished = matches!(import, Import::Finished { .. });
+                imports.push(import);
+                if finished {
+                    break;
+                }
+            }
+        }
+
+        assert!(matches!(imports[0], Import::Started { total: 2 }));
+        let urn = match &imports[1] {
+            Import::ProjectCreated { path, urn } if *path == repo_path => urn.clone(),
+            other => panic!("unexpected notification: {:?}", other),
+        };
+        assert!(matches!(
+            &imports[2],
+            Import::ProjectFailed { path, .. } if *path == missing_path
+        ));
+        assert!(matches!(
+            imports[3],
+            Import::Finished {
+                created: 1,
+                failed: 1
+            }
+        ));
+        assert_eq!(
+            working_copy::entries(&ctx.store, &urn)?
+                .into_iter()
+                .map(|entry| entry.working_copy.path)
+                .collect::<Vec<_>>(),
+            vec![repo_path]
+        );
+
+        Ok(())
+    }
+}
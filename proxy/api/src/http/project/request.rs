@@ -1,8 +1,11 @@
 //! Endpoints for project search requests.
 
+use std::time::SystemTime;
+
+use schemars::gen::SchemaGenerator;
 use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
 
-use crate::{context, http};
+use crate::{context, http, http::doc};
 
 /// Combination of all routes.
 pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
@@ -12,6 +15,21 @@ pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
         .boxed()
 }
 
+/// Documentation of all routes.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new("DELETE", "/{urn}", "Cancel the request for a project")
+            .empty(204)
+            .errors(doc::STATE),
+        doc::Route::new("PUT", "/{urn}", "Request a project from the network")
+            .response::<coco::request::SomeRequest<SystemTime>>(200, gen)
+            .errors(doc::AUTH),
+        doc::Route::new("GET", "/", "List all project requests")
+            .response::<Vec<coco::request::SomeRequest<SystemTime>>>(200, gen)
+            .errors(doc::AUTH),
+    ]
+}
+
 /// `DELETE /<urn>`
 fn cancel_filter(
     ctx: context::Context,
@@ -95,9 +113,9 @@ mod test {
 
     use pretty_assertions::assert_eq;
     use serde_json::json;
-    use warp::{http::StatusCode, test::request};
+    use warp::http::StatusCode;
 
-    use crate::{context, http};
+    use crate::{context, http, http::test::request};
 
     #[tokio::test]
     async fn cancel() -> Result<(), Box<dyn std::error::Error>> {
@@ -1,8 +1,9 @@
 //! Endpoints and serialisation for [`crate::session::Session`] related types.
 
+use schemars::gen::SchemaGenerator;
 use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
 
-use crate::{context, http};
+use crate::{context, http, http::doc, session};
 
 /// Combination of all session filters.
 pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
@@ -11,6 +12,19 @@ pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
         .boxed()
 }
 
+/// Documentation of all session filters.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new("GET", "/", "Get the current session")
+            .response::<session::Session>(200, gen)
+            .errors(&[(403, "FORBIDDEN"), (404, "NOT_FOUND")]),
+        doc::Route::new("POST", "/settings", "Update the settings of the session")
+            .request::<session::settings::Settings>(gen)
+            .empty(204)
+            .errors(doc::AUTH),
+    ]
+}
+
 /// `GET /`
 fn get_filter(
     ctx: context::Context,
@@ -21,7 +35,7 @@ fn get_filter(
         .and_then(handler::get)
 }
 
-/// `Post /settings`
+/// `POST /settings`
 fn update_settings_filter(
     ctx: context::Context,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
@@ -37,7 +51,7 @@ fn update_settings_filter(
 mod handler {
     use warp::{http::StatusCode, reply, Rejection, Reply};
 
-    use crate::{context, error, http, session};
+    use crate::{context, error, http, session, webhook};
 
     /// Fetch the [`session::Session`].
     pub async fn get(ctx: context::Context) -> Result<impl Reply, Rejection> {
@@ -52,8 +66,10 @@ mod handler {
     /// Set the [`session::settings::Settings`] to the passed value.
     pub async fn update_settings(
         ctx: context::Unsealed,
-        settings: session::settings::Settings,
+        mut settings: session::settings::Settings,
     ) -> Result<impl Reply, Rejection> {
+        webhook::validate(&settings.webhooks)?;
+        webhook::store_secrets(&ctx.store, &mut settings.webhooks)?;
         session::set_settings(&ctx.store, settings)?;
 
         Ok(reply::with_status(reply(), StatusCode::NO_CONTENT))
@@ -63,9 +79,9 @@ mod handler {
 #[cfg(test)]
 mod test {
     use pretty_assertions::assert_eq;
-    use warp::{http::StatusCode, test::request};
+    use warp::{http::StatusCode, Filter as _};
 
-    use crate::{context, session};
+    use crate::{context, http, http::test::request, session};
 
     #[tokio::test]
     async fn get() -> Result<(), Box<dyn std::error::Error>> {
@@ -108,4 +124,81 @@ mod test {
         assert_eq!(session_res.settings, settings);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn hide_webhook_secret() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into()).recover(http::error::recover);
+        session::initialize_test(&ctx, "cloudhead").await;
+
+        let mut settings = session::settings::Settings::default();
+        settings.webhooks.push(session::settings::Webhook {
+            url: "http://127.0.0.1:8080/hook".to_string(),
+            events: vec![],
+            secret: Some("secret".to_string()),
+            has_secret: false,
+        });
+        let res = request()
+            .method("POST")
+            .path("/settings")
+            .json(&settings)
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        let res = request().method("GET").path("/").reply(&api).await;
+        let session: serde_json::Value = serde_json::from_slice(res.body())?;
+        let webhook = &session["settings"]["webhooks"][0];
+        assert_eq!(webhook.get("secret"), None);
+        assert_eq!(webhook["hasSecret"], true);
+
+        // Posting the settings back as read keeps the stored secret.
+        let res = request()
+            .method("POST")
+            .path("/settings")
+            .json(&session["settings"])
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        // A new webhook needs a secret.
+        settings.webhooks[0].url = "http://127.0.0.1:8081/hook".to_string();
+        settings.webhooks[0].secret = None;
+        let res = request()
+            .method("POST")
+            .path("/settings")
+            .json(&settings)
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_remote_webhook() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into()).recover(http::error::recover);
+        session::initialize_test(&ctx, "cloudhead").await;
+
+        let mut settings = session::settings::Settings::default();
+        settings.webhooks.push(session::settings::Webhook {
+            url: "http://example.com/hook".to_string(),
+            events: vec![],
+            secret: Some("secret".to_string()),
+            has_secret: false,
+        });
+
+        let res = request()
+            .method("POST")
+            .path("/settings")
+            .json(&settings)
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
 }
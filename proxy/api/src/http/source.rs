@@ -1,9 +1,10 @@
 //! Endpoints and serialisation for source code browsing.
 
+use schemars::gen::SchemaGenerator;
 use serde::{Deserialize, Serialize};
 use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
 
-use crate::{context, http};
+use crate::{context, http, http::doc};
 
 /// Combination of all source filters.
 pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
@@ -17,6 +18,50 @@ pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
         .boxed()
 }
 
+/// Documentation of all source filters. Endpoints serving content addressed by a revision answer
+/// with `304` if the `If-None-Match` header matches.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new("GET", "/blob/{project_urn}", "Get a blob at a revision")
+            .response::<coco::Blob>(200, gen)
+            .empty(304)
+            .errors(doc::QUERY),
+        doc::Route::new(
+            "GET",
+            "/branches/{project_urn}",
+            "List the branches of a peer",
+        )
+        .response::<Vec<coco::Branch>>(200, gen)
+        .errors(doc::QUERY),
+        doc::Route::new("GET", "/commit/{project_urn}/{sha1}", "Get a commit")
+            .response::<coco::Commit>(200, gen)
+            .empty(304)
+            .errors(doc::STATE),
+        doc::Route::new(
+            "GET",
+            "/commits/{project_urn}",
+            "List the history of a revision",
+        )
+        .response::<coco::source::Commits>(200, gen)
+        .empty(304)
+        .errors(doc::QUERY),
+        doc::Route::new(
+            "GET",
+            "/local-state/{path}",
+            "Get the branches and the remote of a local repository",
+        )
+        .response::<coco::source::LocalState>(200, gen)
+        .errors(&[(404, "NOT_FOUND")]),
+        doc::Route::new("GET", "/tags/{project_urn}", "List the tags of a peer")
+            .response::<Vec<coco::Tag>>(200, gen)
+            .errors(doc::QUERY),
+        doc::Route::new("GET", "/tree/{project_urn}", "Get a tree at a revision")
+            .response::<coco::Tree>(200, gen)
+            .empty(304)
+            .errors(doc::QUERY),
+    ]
+}
+
 /// `GET /blob/<project_urn>?revision=<revision>&path=<path>`
 fn blob_filter(
     ctx: context::Context,
@@ -25,6 +70,7 @@ fn blob_filter(
         .and(path::param::<coco::Urn>())
         .and(path::end())
         .and(http::with_qs::<BlobQuery>())
+        .and(warp::header::optional::<String>("if-none-match"))
         .and(http::with_context_unsealed(ctx))
         .and_then(handler::blob)
 }
@@ -51,6 +97,7 @@ fn commit_filter(
         .and(path::param::<coco::oid::Oid>())
         .and(path::end())
         .and(warp::get())
+        .and(warp::header::optional::<String>("if-none-match"))
         .and(http::with_context_unsealed(ctx))
         .and_then(handler::commit)
 }
@@ -64,6 +111,7 @@ fn commits_filter(
         .and(http::with_context_unsealed(ctx))
         .and(path::param::<coco::Urn>())
         .and(http::with_qs::<CommitsQuery>())
+        .and(warp::header::optional::<String>("if-none-match"))
         .and_then(handler::commits)
 }
 
@@ -97,6 +145,7 @@ fn tree_filter(
         .and(path::end())
         .and(warp::get())
         .and(http::with_qs::<TreeQuery>())
+        .and(warp::header::optional::<String>("if-none-match"))
         .and(http::with_context_unsealed(ctx))
         .and_then(handler::tree)
 }
@@ -109,6 +158,8 @@ mod handler {
 
     use crate::{context, error, session, session::settings};
 
+    use super::cache;
+
     /// Fetch a [`coco::Blob`].
     pub async fn blob(
         project_urn: coco::Urn,
@@ -118,6 +169,7 @@ mod handler {
             revision,
             highlight,
         }: super::BlobQuery,
+        if_none_match: Option<String>,
         ctx: context::Unsealed,
     ) -> Result<impl Reply, Rejection> {
         let settings = session::get_current(&ctx.store)?
@@ -140,6 +192,19 @@ mod handler {
             .get_branch(project_urn, peer_id, None)
             .await
             .map_err(error::Error::from)?;
+        let policy = cache::Policy::from(&revision);
+        let oid = ctx
+            .state
+            .with_browser(branch.clone(), |browser| {
+                coco::revision_oid(browser, revision.clone())
+            })
+            .await
+            .map_err(error::Error::from)?;
+        let etag = cache::etag(oid, &(&path, theme));
+        if cache::matches(if_none_match.as_deref(), &etag) {
+            return Ok(cache::not_modified(&etag, policy));
+        }
+
         let blob = ctx
             .state
             .with_browser(branch, |mut browser| {
@@ -148,7 +213,7 @@ mod handler {
             .await
             .map_err(error::Error::from)?;
 
-        Ok(reply::json(&blob))
+        Ok(cache::with_headers(reply::json(&blob), &etag, policy))
     }
 
     /// Fetch the list [`coco::Branch`].
@@ -178,13 +243,38 @@ mod handler {
     pub async fn commit(
         project_urn: coco::Urn,
         sha1: oid::Oid,
+        if_none_match: Option<String>,
         ctx: context::Unsealed,
     ) -> Result<impl Reply, Rejection> {
+        if !ctx
+            .state
+            .has_commit(project_urn.clone(), sha1)
+            .await
+            .map_err(error::Error::from)?
+        {
+            return Err(Rejection::from(error::Error::from(
+                coco::state::Error::from(coco::source::Error::CommitNotFound(sha1)),
+            )));
+        }
+
         let default_branch = ctx
             .state
-            .find_default_branch(project_urn)
+            .find_default_branch(project_urn.clone())
+            .await
+            .map_err(error::Error::from)?;
+        // The commit itself never changes, but the branch it is shown on can.
+        let branch = ctx
+            .state
+            .with_browser(default_branch.clone(), |browser| {
+                coco::commit_branch(browser, sha1)
+            })
             .await
             .map_err(error::Error::from)?;
+        let etag = cache::etag(sha1, &(&project_urn, &branch));
+        if cache::matches(if_none_match.as_deref(), &etag) {
+            return Ok(cache::not_modified(&etag, cache::Policy::Revalidate));
+        }
+
         let commit = ctx
             .state
             .with_browser(default_branch, |mut browser| {
@@ -193,7 +283,11 @@ mod handler {
             .await
             .map_err(error::Error::from)?;
 
-        Ok(reply::json(&commit))
+        Ok(cache::with_headers(
+            reply::json(&commit),
+            &etag,
+            cache::Policy::Revalidate,
+        ))
     }
 
     /// Fetch the list of [`coco::Commit`] from a branch.
@@ -201,6 +295,7 @@ mod handler {
         ctx: context::Unsealed,
         project_urn: coco::Urn,
         super::CommitsQuery { revision }: super::CommitsQuery,
+        if_none_match: Option<String>,
     ) -> Result<impl Reply, Rejection> {
         let revision = super::http::guard_self_revision(&ctx.state, revision);
 
@@ -209,6 +304,22 @@ mod handler {
             .find_default_branch(project_urn)
             .await
             .map_err(error::Error::from)?;
+        // The commits of a revision never change, but the stats of the repository which are part
+        // of the response do.
+        let (oid, stats) = ctx
+            .state
+            .with_browser(default_branch.clone(), |browser| {
+                let oid = coco::revision_oid(browser, revision.clone())?;
+                let stats = browser.get_stats()?;
+                Ok((oid, stats))
+            })
+            .await
+            .map_err(error::Error::from)?;
+        let etag = cache::etag(oid, &stats);
+        if cache::matches(if_none_match.as_deref(), &etag) {
+            return Ok(cache::not_modified(&etag, cache::Policy::Revalidate));
+        }
+
         let commits = ctx
             .state
             .with_browser(default_branch, |mut browser| {
@@ -217,7 +328,11 @@ mod handler {
             .await
             .map_err(error::Error::from)?;
 
-        Ok(reply::json(&commits))
+        Ok(cache::with_headers(
+            reply::json(&commits),
+            &etag,
+            cache::Policy::Revalidate,
+        ))
     }
 
     /// Fetch the list [`coco::Branch`] for a local repository.
@@ -257,6 +372,7 @@ mod handler {
             peer_id,
             revision,
         }: super::TreeQuery,
+        if_none_match: Option<String>,
         ctx: context::Unsealed,
     ) -> Result<impl Reply, Rejection> {
         let peer_id = super::http::guard_self_peer_id(&ctx.state, peer_id);
@@ -266,6 +382,19 @@ mod handler {
             .get_branch(project_urn, peer_id, None)
             .await
             .map_err(error::Error::from)?;
+        let policy = cache::Policy::from(&revision);
+        let oid = ctx
+            .state
+            .with_browser(branch.clone(), |browser| {
+                coco::revision_oid(browser, revision.clone())
+            })
+            .await
+            .map_err(error::Error::from)?;
+        let etag = cache::etag(oid, &prefix);
+        if cache::matches(if_none_match.as_deref(), &etag) {
+            return Ok(cache::not_modified(&etag, policy));
+        }
+
         let tree = ctx
             .state
             .with_browser(branch, |mut browser| {
@@ -274,7 +403,83 @@ mod handler {
             .await
             .map_err(error::Error::from)?;
 
-        Ok(reply::json(&tree))
+        Ok(cache::with_headers(reply::json(&tree), &etag, policy))
+    }
+}
+
+/// Conditional requests for responses computed from a single commit, which only change when the
+/// commit does.
+mod cache {
+    use serde::Serialize;
+    use warp::{
+        http::{header, HeaderValue, StatusCode},
+        reply::Response,
+        Reply,
+    };
+
+    /// How long clients may cache responses for commits addressed by SHA, in seconds.
+    const MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+    /// How clients may cache a response.
+    #[derive(Clone, Copy)]
+    pub enum Policy {
+        /// The commit is addressed by SHA, so the response never changes.
+        Immutable,
+        /// The commit is addressed by a ref, so the response has to be revalidated against the
+        /// current target of the ref.
+        Revalidate,
+    }
+
+    impl<P> From<&Option<coco::Revision<P>>> for Policy {
+        fn from(revision: &Option<coco::Revision<P>>) -> Self {
+            match revision {
+                Some(coco::Revision::Sha { .. }) => Self::Immutable,
+                _ => Self::Revalidate,
+            }
+        }
+    }
+
+    /// Entity tag for a response computed from the commit `oid` with the request parameters
+    /// `params`.
+    pub fn etag<T>(oid: coco::oid::Oid, params: &T) -> String
+    where
+        T: Serialize,
+    {
+        let params = serde_json::to_vec(params).unwrap_or_default();
+        format!("\"{}-{}\"", oid, coco::Hash::hash(&params))
+    }
+
+    /// Returns `true` if the `If-None-Match` header of the request contains `etag`.
+    pub fn matches(if_none_match: Option<&str>, etag: &str) -> bool {
+        if_none_match.map_or(false, |if_none_match| {
+            if_none_match
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*")
+        })
+    }
+
+    /// Empty `304 Not Modified` response.
+    pub fn not_modified(etag: &str, policy: Policy) -> Response {
+        with_headers(StatusCode::NOT_MODIFIED, etag, policy)
+    }
+
+    /// Add the `ETag` and `Cache-Control` headers to `reply`.
+    pub fn with_headers(reply: impl Reply, etag: &str, policy: Policy) -> Response {
+        let mut response = reply.into_response();
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        let cache_control = match policy {
+            Policy::Immutable => format!("private, max-age={}, immutable", MAX_AGE),
+            Policy::Revalidate => "private, no-cache".to_string(),
+        };
+        if let Ok(cache_control) = HeaderValue::from_str(&cache_control) {
+            headers.insert(header::CACHE_CONTROL, cache_control);
+        }
+
+        response
     }
 }
 
@@ -335,9 +540,9 @@ mod test {
 
     use pretty_assertions::assert_eq;
     use serde_json::{json, Value};
-    use warp::{http::StatusCode, test::request};
+    use warp::http::StatusCode;
 
-    use crate::{context, error, http};
+    use crate::{context, error, http, http::test::request};
 
     #[tokio::test]
     async fn blob() -> Result<(), Box<dyn std::error::Error>> {
@@ -644,15 +849,7 @@ mod test {
 
         http::test::assert_response(&res, StatusCode::OK, |have| {
             assert_eq!(have, json!(want));
-            assert_eq!(
-                have,
-                json!({
-                    "branches": [
-                        "dev",
-                        "master",
-                    ],
-                }),
-            );
+            assert_eq!(have["branches"], json!(["dev", "master"]));
         });
 
         Ok(())
@@ -751,6 +948,227 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn tree_not_modified() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into());
+        let urn = replicate_platinum(&ctx).await?;
+
+        let tree_path = |revision| {
+            let query = super::TreeQuery {
+                prefix: Some("src".to_string()),
+                peer_id: None,
+                revision: Some(revision),
+            };
+            format!("/tree/{}?{}", urn, serde_qs::to_string(&query).unwrap())
+        };
+        let sha = coco::oid::Oid::try_from("3873745c8f6ffb45c990eb23b491d4b4b6182f95")?;
+        let by_sha = tree_path(coco::Revision::Sha { sha });
+        let by_branch = tree_path(coco::Revision::Branch {
+            name: "master".to_string(),
+            peer_id: None,
+        });
+
+        let res = request().method("GET").path(&by_sha).reply(&api).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers()["cache-control"]
+            .to_str()?
+            .contains("immutable"));
+        let etag = res.headers()["etag"].clone();
+
+        let res = request()
+            .method("GET")
+            .path(&by_sha)
+            .header("if-none-match", etag.clone())
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert!(res.body().is_empty());
+
+        let res = request().method("GET").path(&by_branch).reply(&api).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["cache-control"], "private, no-cache");
+        let etag = res.headers()["etag"].clone();
+
+        let res = request()
+            .method("GET")
+            .path(&by_branch)
+            .header("if-none-match", etag)
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let res = request()
+            .method("GET")
+            .path(&by_branch)
+            .header("if-none-match", "\"outdated\"")
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn blob_not_modified() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into());
+        let urn = replicate_platinum(&ctx).await?;
+
+        let blob_path = |path: &str, highlight| {
+            let query = super::BlobQuery {
+                path: path.to_string(),
+                peer_id: None,
+                revision: Some(coco::Revision::Sha {
+                    sha: coco::oid::Oid::try_from("3873745c8f6ffb45c990eb23b491d4b4b6182f95")
+                        .unwrap(),
+                }),
+                highlight: Some(highlight),
+            };
+            format!("/blob/{}?{}", urn, serde_qs::to_string(&query).unwrap())
+        };
+
+        let res = request()
+            .method("GET")
+            .path(&blob_path("text/arrows.txt", false))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers()["cache-control"]
+            .to_str()?
+            .contains("immutable"));
+        let etag = res.headers()["etag"].clone();
+
+        let res = request()
+            .method("GET")
+            .path(&blob_path("text/arrows.txt", false))
+            .header("if-none-match", etag.clone())
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert!(res.body().is_empty());
+
+        // The same commit with other parameters is a different response.
+        for path in &[
+            blob_path("README.md", false),
+            blob_path("text/arrows.txt", true),
+        ] {
+            let res = request()
+                .method("GET")
+                .path(path)
+                .header("if-none-match", etag.clone())
+                .reply(&api)
+                .await;
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn commit_not_modified() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into());
+        let urn = replicate_platinum(&ctx).await?;
+        let path = format!("/commit/{}/3873745c8f6ffb45c990eb23b491d4b4b6182f95", urn);
+
+        let res = request().method("GET").path(&path).reply(&api).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["cache-control"], "private, no-cache");
+        let etag = res.headers()["etag"].clone();
+
+        let res = request()
+            .method("GET")
+            .path(&path)
+            .header("if-none-match", etag)
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert!(res.body().is_empty());
+
+        // Commits which are not part of the project are never reported as not modified.
+        let res = request()
+            .method("GET")
+            .path(&format!(
+                "/commit/{}/0000000000000000000000000000000000000001",
+                urn
+            ))
+            .header("if-none-match", "*")
+            .reply(&api)
+            .await;
+        http::test::assert_response(&res, StatusCode::NOT_FOUND, |have| {
+            assert_eq!(have["variant"], "NOT_FOUND");
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn commits_not_modified() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let api = super::filters(ctx.clone().into());
+        let urn = replicate_platinum(&ctx).await?;
+
+        let commits_path = |name: &str| {
+            let query = super::CommitsQuery {
+                revision: Some(coco::Revision::Branch {
+                    name: name.to_string(),
+                    peer_id: None,
+                }),
+            };
+            format!("/commits/{}?{}", urn, serde_qs::to_string(&query).unwrap())
+        };
+
+        let res = request()
+            .method("GET")
+            .path(&commits_path("master"))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["cache-control"], "private, no-cache");
+        let etag = res.headers()["etag"].clone();
+
+        let res = request()
+            .method("GET")
+            .path(&commits_path("master"))
+            .header("if-none-match", etag.clone())
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let res = request()
+            .method("GET")
+            .path(&commits_path("dev"))
+            .header("if-none-match", etag)
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // The history of a commit never changes, but the stats in the response do.
+        let query = super::CommitsQuery {
+            revision: Some(coco::Revision::Sha {
+                sha: coco::oid::Oid::try_from("3873745c8f6ffb45c990eb23b491d4b4b6182f95")?,
+            }),
+        };
+        let res = request()
+            .method("GET")
+            .path(&format!(
+                "/commits/{}?{}",
+                urn,
+                serde_qs::to_string(&query).unwrap()
+            ))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["cache-control"], "private, no-cache");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn tree_dev_branch() -> Result<(), Box<dyn std::error::Error>> {
         // Testing that the endpoint works with URL encoding
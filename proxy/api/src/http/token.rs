@@ -0,0 +1,185 @@
+//! Endpoints for managing API tokens.
+
+use schemars::{gen::SchemaGenerator, JsonSchema};
+use serde::{Deserialize, Serialize};
+use warp::{filters::BoxedFilter, path, Filter, Rejection, Reply};
+
+use crate::{context, http, http::doc, token};
+
+/// Combination of all token filters.
+pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
+    list_filter(ctx.clone())
+        .or(create_filter(ctx.clone()))
+        .or(revoke_filter(ctx))
+        .boxed()
+}
+
+/// Documentation of all token filters.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![
+        doc::Route::new("GET", "/", "List API tokens, without their secrets")
+            .response::<Vec<token::Token>>(200, gen)
+            .errors(doc::AUTH),
+        doc::Route::new(
+            "POST",
+            "/",
+            "Create an API token, the only time its secret is revealed",
+        )
+        .request::<CreateInput>(gen)
+        .response::<token::Created>(201, gen)
+        .errors(&[(403, "FORBIDDEN"), (409, "TOKEN_EXISTS")]),
+        doc::Route::new("DELETE", "/{name}", "Revoke an API token")
+            .empty(204)
+            .errors(&[(403, "FORBIDDEN"), (404, "TOKEN_NOT_FOUND")]),
+    ]
+}
+
+/// `GET /`
+fn list_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::get()
+        .and(path::end())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::list)
+}
+
+/// `POST /`
+fn create_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::post()
+        .and(path::end())
+        .and(http::with_context_unsealed(ctx))
+        .and(warp::body::json())
+        .and_then(handler::create)
+}
+
+/// `DELETE /<name>`
+fn revoke_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::delete()
+        .and(path::param::<String>())
+        .and(path::end())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::revoke)
+}
+
+/// Token handlers for conversion between core domain and HTTP request fulfilment.
+mod handler {
+    use warp::{http::StatusCode, reply, Rejection, Reply};
+
+    use crate::{context, error, token};
+
+    /// List all API tokens, without their secrets.
+    pub async fn list(ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
+        let tokens = token::list(&ctx.store)?;
+        Ok(reply::json(&tokens))
+    }
+
+    /// Create an API token. The response carries the secret, which can't be obtained again.
+    pub async fn create(
+        ctx: context::Unsealed,
+        input: super::CreateInput,
+    ) -> Result<impl Reply, Rejection> {
+        let created = token::create(&ctx.store, input.name, input.scopes)?;
+        Ok(reply::with_status(
+            reply::json(&created),
+            StatusCode::CREATED,
+        ))
+    }
+
+    /// Revoke the API token called `name`.
+    pub async fn revoke(name: String, ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
+        if token::revoke(&ctx.store, &name)? {
+            Ok(reply::with_status(reply(), StatusCode::NO_CONTENT))
+        } else {
+            Err(Rejection::from(error::Error::TokenNotFound(name)))
+        }
+    }
+}
+
+/// Bundled input data for token creation.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(rename = "CreateTokenInput")]
+#[serde(rename_all = "camelCase")]
+pub struct CreateInput {
+    /// Unique name of the token.
+    name: String,
+    /// The endpoints the token grants access to.
+    scopes: Vec<token::Scope>,
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use warp::{http::StatusCode, path, Filter as _};
+
+    use crate::{context, http, http::test::request, token};
+
+    #[tokio::test]
+    async fn create_revoke() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        let store = ctx.store.clone();
+        let api = path("v1")
+            .and(path("tokens"))
+            .and(super::filters(ctx.into()))
+            .recover(http::error::recover);
+
+        let res = request()
+            .method("POST")
+            .path("/v1/tokens")
+            .json(&json!({ "name": "editor", "scopes": ["browse"] }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let created: serde_json::Value = serde_json::from_slice(res.body())?;
+        let secret = created["secret"].as_str().unwrap_or_default();
+        assert!(token::authorize(&store, secret)?.is_some());
+
+        let res = request()
+            .method("POST")
+            .path("/v1/tokens")
+            .json(&json!({ "name": "editor", "scopes": [] }))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        // Tokens can't be used to manage tokens.
+        let res = request()
+            .method("GET")
+            .path("/v1/tokens")
+            .header("Authorization", format!("Bearer {}", secret))
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = request()
+            .method("GET")
+            .path("/v1/tokens")
+            .header("Authorization", "Bearer guess")
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = request()
+            .method("DELETE")
+            .path("/v1/tokens/editor")
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(token::authorize(&store, secret)?, None);
+
+        let res = request()
+            .method("DELETE")
+            .path("/v1/tokens/editor")
+            .reply(&api)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+}
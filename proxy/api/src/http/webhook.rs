@@ -0,0 +1,39 @@
+//! Endpoints to inspect the deliveries of [`crate::webhook`]s.
+
+use schemars::gen::SchemaGenerator;
+use warp::{filters::BoxedFilter, path, Filter, Reply};
+
+use crate::{context, http, http::doc, webhook};
+
+/// `GET /deliveries`
+pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
+    path("deliveries")
+        .and(path::end())
+        .and(warp::get())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::deliveries)
+        .boxed()
+}
+
+/// Documentation of the webhook route.
+pub fn docs(gen: &mut SchemaGenerator) -> Vec<doc::Route> {
+    vec![doc::Route::new(
+        "GET",
+        "/deliveries",
+        "List the recent deliveries of notifications to webhooks, newest first",
+    )
+    .response::<Vec<webhook::Delivery>>(200, gen)
+    .errors(doc::AUTH)]
+}
+
+/// Webhook handlers for conversion between core domain and HTTP request fulfilment.
+mod handler {
+    use warp::{reply, Rejection, Reply};
+
+    use crate::{context, webhook};
+
+    /// List the logged deliveries, newest first.
+    pub async fn deliveries(ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
+        Ok(reply::json(&webhook::deliveries(&ctx.store)?))
+    }
+}
@@ -1,5 +1,6 @@
 //! Container to bundle and associate information around an identity.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use radicle_avatar as avatar;
@@ -7,45 +8,91 @@ use radicle_avatar as avatar;
 use crate::error;
 
 /// The users personal identifying metadata and keys.
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Identity {
     /// The Peer Id for the user.
+    #[schemars(with = "String")]
     pub peer_id: coco::PeerId,
     /// The coco URN.
+    #[schemars(with = "String")]
     pub urn: coco::Urn,
     /// Unambiguous identifier pointing at this identity.
     pub shareable_entity_identifier: coco::Identifier,
     /// Bundle of user provided data.
     pub metadata: Metadata,
     /// Generated fallback avatar to be used if actual avatar url is missing or can't be loaded.
+    /// See [`coco::user::Profile::avatar_url`].
+    #[schemars(with = "AvatarSchema")]
     pub avatar_fallback: avatar::Avatar,
 }
 
-impl<S> From<(coco::PeerId, coco::MetaUser<S>)> for Identity {
+impl<S> From<(coco::PeerId, coco::MetaUser<S>)> for Identity
+where
+    S: Clone,
+{
     fn from((peer_id, user): (coco::PeerId, coco::MetaUser<S>)) -> Self {
         let urn = user.urn();
+        let profile = coco::user::Profile::of(&user).unwrap_or_default();
         Self {
             peer_id,
-            urn: urn.clone(),
+            avatar_fallback: avatar_fallback(&urn, &profile),
+            urn,
             shareable_entity_identifier: coco::Identifier {
                 handle: user.name().to_string(),
                 peer_id,
             },
             metadata: Metadata {
                 handle: user.name().to_string(),
+                profile,
             },
-            avatar_fallback: avatar::Avatar::from(&urn.to_string(), avatar::Usage::Identity),
         }
     }
 }
 
+/// Generate the fallback avatar of the identity `urn`, seeded by the display name of its
+/// `profile` if one is set, so it changes along with the name shown next to it.
+#[must_use]
+pub fn avatar_fallback(urn: &coco::Urn, profile: &coco::user::Profile) -> avatar::Avatar {
+    let seed = profile
+        .display_name
+        .clone()
+        .unwrap_or_else(|| urn.to_string());
+    avatar::Avatar::from(&seed, avatar::Usage::Identity)
+}
+
+/// Schema of [`avatar::Avatar`], which is defined by `radicle_avatar`.
+#[derive(JsonSchema)]
+#[schemars(rename = "Avatar")]
+pub struct AvatarSchema {
+    /// Color filling the background.
+    pub background: ColorSchema,
+    /// Emoji in front of the background.
+    pub emoji: String,
+}
+
+/// Schema of the colors of an [`AvatarSchema`].
+#[derive(JsonSchema)]
+#[schemars(rename = "Color")]
+pub struct ColorSchema {
+    /// Red component.
+    pub r: u8,
+    /// Green component.
+    pub g: u8,
+    /// Blue component.
+    pub b: u8,
+}
+
 /// User maintained information for an identity, which can evolve over time.
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[schemars(rename = "IdentityMetadata")]
 #[serde(rename_all = "camelCase")]
 pub struct Metadata {
     /// Similar to a nickname, the users chosen short identifier.
     pub handle: String,
+    /// Optional information beyond the handle.
+    #[serde(flatten)]
+    pub profile: coco::user::Profile,
 }
 
 /// Creates a new identity.
@@ -56,6 +103,21 @@ pub async fn create(state: &coco::State, handle: &str) -> Result<Identity, error
     Ok((state.peer_id(), user).into())
 }
 
+/// Replace the profile of the identity owned by this peer with a new signed revision.
+///
+/// # Errors
+///
+/// * Errors if the profile contains links or an avatar URL which are not valid URLs.
+/// * Errors if the new revision of the user can't be created or stored.
+pub async fn update(
+    state: &coco::State,
+    owner: &coco::user::User,
+    profile: coco::user::Profile,
+) -> Result<Identity, error::Error> {
+    let user = state.update_owner_profile(owner, profile).await?;
+    Ok((state.peer_id(), user).into())
+}
+
 /// Retrieve an identity by id. We assume the `Identity` is owned by this peer.
 ///
 /// # Errors
@@ -68,27 +130,63 @@ pub async fn get(state: &coco::State, id: coco::Urn) -> Result<Identity, error::
 
 // TODO(finto): Check if this is used and if so, express more elegantly after
 // radicle-dev/radicle-link#374.
-/// Retrieve the list of identities known to the session user.
+/// Retrieve the list of identities known to the session user. A user with several devices is
+/// listed once, with the first device found as its `peer_id`.
 ///
 /// # Errors
 ///
 ///  * If we cannot get the list of projects
 ///  * If we cannot get the tracked peers for a given project
 pub async fn list(state: &coco::State) -> Result<Vec<Identity>, error::Error> {
-    let mut users = vec![];
+    let mut users: Vec<Identity> = vec![];
     for project in state.list_projects().await? {
         let project_urn = project.urn();
-        for peer in state
+        for devices in state
             .tracked(project_urn)
             .await?
             .into_iter()
-            .filter_map(coco::project::Peer::replicated_remote)
+            .filter_map(coco::project::peer::Devices::replicated)
         {
-            let user = peer.into();
-            if !users.contains(&user) {
-                users.push(user)
+            if users.iter().any(|user| user.urn == devices.user.urn()) {
+                continue;
+            }
+            if let Some(peer_id) = devices.peer_ids.first() {
+                users.push((*peer_id, devices.user).into());
             }
         }
     }
     Ok(users)
 }
+
+/// Add the key of the `device` peer to the identity owned by this peer, so the device can adopt
+/// the identity with [`adopt`].
+///
+/// # Errors
+///
+/// Errors if the new revision of the user can't be created or stored.
+pub async fn link_device(
+    state: &coco::State,
+    owner: &coco::user::User,
+    device: coco::PeerId,
+) -> Result<Identity, error::Error> {
+    let user = state.link_device(owner, device).await?;
+    Ok((state.peer_id(), user).into())
+}
+
+/// Adopt the identity `urn`, which the device `peer_id` linked this peer to with [`link_device`],
+/// as the identity of this peer.
+///
+/// # Errors
+///
+/// Errors if the identity can't be fetched from the device or this peer was not linked to it.
+pub async fn adopt(
+    state: &coco::State,
+    urn: coco::Urn,
+    peer_id: coco::PeerId,
+    addr_hints: Vec<std::net::SocketAddr>,
+) -> Result<Identity, error::Error> {
+    let user = state
+        .adopt_owner(urn.into_rad_url(peer_id), addr_hints)
+        .await?;
+    Ok((state.peer_id(), user).into())
+}
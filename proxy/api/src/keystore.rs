@@ -0,0 +1,274 @@
+//! Tracking of failed attempts to unseal the keystore, change its passphrase or open a migration
+//! bundle. After [`FREE_ATTEMPTS`] wrong passphrases,
+//! every further attempt has to wait for an exponentially growing delay. The attempts are
+//! persisted, so restarting the proxy doesn't reset them.
+//!
+//! Every attempt is counted as a failure before the passphrase is verified and settled once the
+//! outcome is known, see [`attempt`] and [`settle`]. Concurrent attempts therefore can't slip
+//! through before the failures of the others are recorded.
+
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+
+/// Name for the storage bucket used for keystore state.
+const BUCKET_NAME: &str = "keystore";
+/// Name of the item used for the failed unseal attempts.
+const KEY_ATTEMPTS: &str = "unseal_attempts";
+
+/// Number of wrong passphrases accepted before attempts are delayed.
+pub const FREE_ATTEMPTS: u32 = 3;
+/// Delay after the first attempt exceeding [`FREE_ATTEMPTS`], in seconds. Doubles with every
+/// further failure.
+const BASE_DELAY: u64 = 1;
+/// Upper bound of the delay between attempts, in seconds.
+const MAX_DELAY: u64 = 60 * 60;
+
+lazy_static::lazy_static! {
+    /// Serializes checking and recording attempts, which read and write the same item.
+    static ref ATTEMPTS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Failed unseal attempts since the last successful one.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Attempts {
+    /// Number of consecutive failures.
+    failures: u32,
+    /// Time of the last failure, in seconds since the UNIX epoch.
+    last_failure: u64,
+}
+
+impl Attempts {
+    /// Time in seconds since the UNIX epoch before which no further attempt is allowed, `None`
+    /// if attempts are not delayed.
+    fn retry_at(&self) -> Option<u64> {
+        let exceeded = self.failures.checked_sub(FREE_ATTEMPTS)?;
+        let delay = BASE_DELAY
+            .checked_shl(exceeded)
+            .map_or(MAX_DELAY, |delay| delay.min(MAX_DELAY));
+        Some(self.last_failure + delay)
+    }
+}
+
+/// Start an attempt to verify a passphrase of the key, e.g. to unseal the keystore. The attempt is
+/// recorded as a failure right away and has to be followed by [`settle`] with its outcome.
+///
+/// # Errors
+///
+/// * Errors with [`error::Error::TooManyAttempts`] if the attempt has to wait.
+/// * Errors if access to the store fails.
+pub fn attempt(store: &kv::Store) -> Result<(), error::Error> {
+    let _guard = lock();
+    check(store)?;
+    record_failure(store)
+}
+
+/// Settle an attempt started with [`attempt`] with its `result`. A right passphrase forgets all
+/// failed attempts and a wrong one keeps the failure recorded. Any other error, e.g. if the
+/// signing agent can't be reached or there is no key yet, says nothing about the passphrase, so
+/// the failure is rolled back.
+///
+/// # Errors
+///
+/// * Errors with the error of `result`.
+/// * Errors if access to the store fails.
+pub fn settle<T>(store: &kv::Store, result: Result<T, error::Error>) -> Result<T, error::Error> {
+    match result {
+        Ok(value) => {
+            reset(store)?;
+            Ok(value)
+        },
+        Err(err) if is_wrong_passphrase(&err) => Err(err),
+        Err(err) => {
+            roll_back(store)?;
+            Err(err)
+        },
+    }
+}
+
+/// Returns `true` if `err` is caused by a wrong passphrase, either rejected by the keystore or
+/// refused by the signing agent.
+#[allow(clippy::wildcard_enum_match_arm)]
+fn is_wrong_passphrase(err: &error::Error) -> bool {
+    match err {
+        error::Error::WrongPassphrase => true,
+        error::Error::Keystore(err) => err.is_invalid_passphrase(),
+        _ => false,
+    }
+}
+
+/// Check if another unseal attempt is allowed now.
+///
+/// # Errors
+///
+/// * Errors with [`error::Error::TooManyAttempts`] if the attempt has to wait.
+/// * Errors if access to the store fails.
+fn check(store: &kv::Store) -> Result<(), error::Error> {
+    let now = now();
+    match get(store)?.retry_at() {
+        Some(retry_at) if retry_at > now => Err(error::Error::TooManyAttempts {
+            retry_after: retry_at - now,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Record an attempt with a wrong passphrase.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+fn record_failure(store: &kv::Store) -> Result<(), error::Error> {
+    let mut attempts = get(store)?;
+    attempts.failures = attempts.failures.saturating_add(1);
+    attempts.last_failure = now();
+    set(store, attempts)
+}
+
+/// Undo the failure recorded by [`attempt`] for an attempt which failed for another reason than
+/// a wrong passphrase.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+fn roll_back(store: &kv::Store) -> Result<(), error::Error> {
+    let _guard = lock();
+    let mut attempts = get(store)?;
+    attempts.failures = attempts.failures.saturating_sub(1);
+    set(store, attempts)
+}
+
+/// Forget all failed attempts after a successful unseal.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+fn reset(store: &kv::Store) -> Result<(), error::Error> {
+    let _guard = lock();
+    Ok(store
+        .bucket::<&str, kv::Json<Attempts>>(Some(BUCKET_NAME))?
+        .remove(KEY_ATTEMPTS)?)
+}
+
+/// Acquire the [`ATTEMPTS_LOCK`].
+fn lock() -> MutexGuard<'static, ()> {
+    ATTEMPTS_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Get the recorded [`Attempts`].
+fn get(store: &kv::Store) -> Result<Attempts, error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<Attempts>>(Some(BUCKET_NAME))?
+        .get(KEY_ATTEMPTS)?
+        .map(kv::Codec::to_inner)
+        .unwrap_or_default())
+}
+
+/// Store the recorded [`Attempts`].
+fn set(store: &kv::Store, attempts: Attempts) -> Result<(), error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<Attempts>>(Some(BUCKET_NAME))?
+        .set(KEY_ATTEMPTS, kv::Json(attempts))?)
+}
+
+/// Current time in seconds since the UNIX epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::error;
+
+    use super::{
+        attempt, check, get, record_failure, reset, settle, Attempts, FREE_ATTEMPTS, MAX_DELAY,
+    };
+
+    #[test]
+    fn retry_at_grows_exponentially() {
+        let retry_at = |failures| {
+            Attempts {
+                failures,
+                last_failure: 100,
+            }
+            .retry_at()
+        };
+
+        assert_eq!(retry_at(FREE_ATTEMPTS - 1), None);
+        assert_eq!(retry_at(FREE_ATTEMPTS), Some(101));
+        assert_eq!(retry_at(FREE_ATTEMPTS + 3), Some(108));
+        assert_eq!(retry_at(FREE_ATTEMPTS + 100), Some(100 + MAX_DELAY));
+    }
+
+    #[test]
+    fn persist_failures() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        for _ in 0..FREE_ATTEMPTS + 10 {
+            record_failure(&store)?;
+        }
+        assert!(matches!(
+            check(&store),
+            Err(error::Error::TooManyAttempts { .. })
+        ));
+
+        reset(&store)?;
+        check(&store)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn settle_attempts() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        attempt(&store)?;
+        assert!(settle::<()>(&store, Err(error::Error::WrongPassphrase)).is_err());
+        assert_eq!(get(&store)?.failures, 1);
+
+        attempt(&store)?;
+        assert!(settle::<()>(&store, Err(error::Error::KeystoreSealed)).is_err());
+        assert_eq!(get(&store)?.failures, 1);
+
+        attempt(&store)?;
+        settle(&store, Ok(()))?;
+        assert_eq!(get(&store)?.failures, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_attempts() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        let attempts = (0..FREE_ATTEMPTS * 2)
+            .map(|_| {
+                let store = store.clone();
+                std::thread::spawn(move || attempt(&store))
+            })
+            .collect::<Vec<_>>();
+        let allowed = attempts
+            .into_iter()
+            .filter_map(|attempt| attempt.join().ok())
+            .filter(Result::is_ok)
+            .count();
+        assert_eq!(allowed, FREE_ATTEMPTS as usize);
+
+        Ok(())
+    }
+}
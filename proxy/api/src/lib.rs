@@ -30,12 +30,19 @@ mod config;
 mod context;
 pub mod env;
 mod error;
+mod health;
 mod http;
 mod identity;
+mod keystore;
+mod metrics;
+mod migration;
 mod notification;
+mod peer_config;
 mod process;
 mod project;
 mod service;
 mod session;
+mod token;
+mod webhook;
 
 pub use process::{run, Args};
@@ -0,0 +1,461 @@
+//! Counters and gauges about the peer and the HTTP API, rendered in the Prometheus text format.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use coco::{net::gossip::PutResult, request::RequestState};
+
+/// Prefix of all metric names.
+const PREFIX: &str = "radicle_proxy";
+
+/// Upper bounds of the buckets for the durations of HTTP requests, in seconds.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// All [`RequestState`]s with their labels, to report states without requests as well.
+const REQUEST_STATES: &[(RequestState, &str)] = &[
+    (RequestState::Created, "created"),
+    (RequestState::Requested, "requested"),
+    (RequestState::Found, "found"),
+    (RequestState::Cloning, "cloning"),
+    (RequestState::Cloned, "cloned"),
+    (RequestState::Cancelled, "cancelled"),
+    (RequestState::TimedOut, "timed_out"),
+];
+
+/// Labels of all [`coco::PeerStatus`]es.
+const STATUSES: &[&str] = &["stopped", "started", "offline", "syncing", "online"];
+
+/// Metrics collected over the lifetime of the process, which survive restarts of the peer.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    /// The collected counters.
+    inner: Arc<Mutex<Counters>>,
+}
+
+/// Counters collected from peer events and HTTP requests.
+#[derive(Default)]
+struct Counters {
+    /// Transitions between [`coco::PeerStatus`]es, by old and new status.
+    status_transitions: BTreeMap<(&'static str, &'static str), u64>,
+    /// Number of announced updates.
+    announcements: u64,
+    /// Fetches triggered by gossip, by their result.
+    gossip_fetches: BTreeMap<&'static str, u64>,
+    /// Syncs with other peers, by their result.
+    syncs: BTreeMap<&'static str, u64>,
+    /// Durations of HTTP requests, by method, route and status code.
+    http_requests: BTreeMap<(String, String, u16), Histogram>,
+}
+
+/// Distribution of observed durations over [`DURATION_BUCKETS`].
+struct Histogram {
+    /// Number of observations per bucket, not cumulative.
+    buckets: Vec<u64>,
+    /// Sum of all observations, in seconds.
+    sum: f64,
+    /// Number of observations.
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; DURATION_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Record the observation of `duration`.
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// State of the running peer at the time the metrics are rendered.
+pub struct PeerState {
+    /// The current status of the peer.
+    pub status: coco::PeerStatus,
+    /// Number of peers the local peer is connected to.
+    pub connected_peers: usize,
+    /// All requests in the waiting room.
+    pub requests: Vec<coco::request::SomeRequest<SystemTime>>,
+}
+
+impl Metrics {
+    /// Count the occurrence of a peer `event`.
+    pub fn record_event(&self, event: &coco::PeerEvent) {
+        let mut counters = self.lock();
+        match event {
+            coco::PeerEvent::Announced(updates) => counters.announcements += updates.len() as u64,
+            coco::PeerEvent::GossipFetched { result, .. } => {
+                let result = match result {
+                    PutResult::Applied(_) => "applied",
+                    PutResult::Uninteresting => "uninteresting",
+                    PutResult::Stale => "stale",
+                    PutResult::Error => "error",
+                };
+                *counters.gossip_fetches.entry(result).or_default() += 1;
+            },
+            coco::PeerEvent::PeerSynced(_) => *counters.syncs.entry("succeeded").or_default() += 1,
+            coco::PeerEvent::PeerSyncFailed(_) => *counters.syncs.entry("failed").or_default() += 1,
+            coco::PeerEvent::StatusChanged(old, new) => {
+                *counters
+                    .status_transitions
+                    .entry((status_label(old), status_label(new)))
+                    .or_default() += 1;
+            },
+            _ => {},
+        }
+    }
+
+    /// Record the `duration` of an HTTP request to `route`, which answered with `status`.
+    pub fn record_request(&self, method: &str, route: &str, status: u16, duration: Duration) {
+        self.lock()
+            .http_requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default()
+            .observe(duration);
+    }
+
+    /// Render all metrics in the Prometheus text format. The gauges about the peer are left out if
+    /// it isn't running.
+    #[must_use]
+    pub fn render(&self, peer: Option<PeerState>) -> String {
+        let counters = self.lock();
+        let mut out = String::new();
+
+        if let Some(peer) = peer {
+            header(
+                &mut out,
+                "peer_status",
+                "gauge",
+                "Current status of the peer.",
+            );
+            for status in STATUSES {
+                let value = u64::from(*status == status_label(&peer.status));
+                sample(&mut out, "peer_status", &[("status", status)], value);
+            }
+
+            header(
+                &mut out,
+                "peer_connected_peers",
+                "gauge",
+                "Number of peers the local peer is connected to.",
+            );
+            sample(&mut out, "peer_connected_peers", &[], peer.connected_peers);
+
+            header(
+                &mut out,
+                "waiting_room_requests",
+                "gauge",
+                "Project requests in the waiting room by state.",
+            );
+            for (state, label) in REQUEST_STATES {
+                let count = peer
+                    .requests
+                    .iter()
+                    .filter(|request| RequestState::from(*request) == *state)
+                    .count();
+                sample(
+                    &mut out,
+                    "waiting_room_requests",
+                    &[("state", label)],
+                    count,
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "peer_status_transitions_total",
+            "counter",
+            "Transitions between peer statuses.",
+        );
+        for ((old, new), count) in &counters.status_transitions {
+            sample(
+                &mut out,
+                "peer_status_transitions_total",
+                &[("from", old), ("to", new)],
+                count,
+            );
+        }
+
+        header(
+            &mut out,
+            "announcements_total",
+            "counter",
+            "Updates announced to the network.",
+        );
+        sample(&mut out, "announcements_total", &[], counters.announcements);
+
+        header(
+            &mut out,
+            "gossip_fetches_total",
+            "counter",
+            "Fetches triggered by gossip by result.",
+        );
+        for (result, count) in &counters.gossip_fetches {
+            sample(
+                &mut out,
+                "gossip_fetches_total",
+                &[("result", result)],
+                count,
+            );
+        }
+
+        header(
+            &mut out,
+            "peer_syncs_total",
+            "counter",
+            "Syncs with other peers by result.",
+        );
+        for (result, count) in &counters.syncs {
+            sample(&mut out, "peer_syncs_total", &[("result", result)], count);
+        }
+
+        let name = "http_request_duration_seconds";
+        header(
+            &mut out,
+            name,
+            "histogram",
+            "Durations of HTTP requests by method, route and status.",
+        );
+        for ((method, route, status), histogram) in &counters.http_requests {
+            let status = status.to_string();
+            let labels = [
+                ("method", method.as_str()),
+                ("route", route.as_str()),
+                ("status", status.as_str()),
+            ];
+            let mut cumulative = 0;
+            for (bound, count) in DURATION_BUCKETS.iter().zip(&histogram.buckets) {
+                cumulative += count;
+                let bound = bound.to_string();
+                let labels = [&labels[..], &[("le", bound.as_str())]].concat();
+                sample(&mut out, &format!("{}_bucket", name), &labels, cumulative);
+            }
+            let labels_inf = [&labels[..], &[("le", "+Inf")]].concat();
+            sample(
+                &mut out,
+                &format!("{}_bucket", name),
+                &labels_inf,
+                histogram.count,
+            );
+            sample(&mut out, &format!("{}_sum", name), &labels, histogram.sum);
+            sample(
+                &mut out,
+                &format!("{}_count", name),
+                &labels,
+                histogram.count,
+            );
+        }
+
+        out
+    }
+
+    /// Lock the counters.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Counters> {
+        self.inner.lock().expect("metrics lock is poisoned")
+    }
+}
+
+/// Label for a [`coco::PeerStatus`].
+const fn status_label(status: &coco::PeerStatus) -> &'static str {
+    match status {
+        coco::PeerStatus::Stopped => "stopped",
+        coco::PeerStatus::Started => "started",
+        coco::PeerStatus::Offline => "offline",
+        coco::PeerStatus::Syncing { .. } => "syncing",
+        coco::PeerStatus::Online { .. } => "online",
+    }
+}
+
+/// Write the `HELP` and `TYPE` lines of the metric `name`.
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {}_{} {}", PREFIX, name, help).ok();
+    writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind).ok();
+}
+
+/// Write a sample of the metric `name` with `labels`.
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    let labels = labels
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "{}=\"{}\"",
+                key,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        })
+        .collect::<Vec<_>>();
+    if labels.is_empty() {
+        writeln!(out, "{}_{} {}", PREFIX, name, value).ok();
+    } else {
+        writeln!(out, "{}_{}{{{}}} {}", PREFIX, name, labels.join(","), value).ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{convert::TryFrom as _, time::Duration};
+
+    use pretty_assertions::assert_eq;
+
+    use coco::net::gossip::PutResult;
+
+    use super::Metrics;
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::default();
+        metrics.record_event(&coco::PeerEvent::StatusChanged(
+            coco::PeerStatus::Started,
+            coco::PeerStatus::Online { connected: 1 },
+        ));
+        metrics.record_request("GET", "/v1/session", 200, Duration::from_millis(20));
+        metrics.record_request("GET", "/v1/session", 200, Duration::from_secs(20));
+
+        let rendered = metrics.render(None);
+        assert!(rendered.contains(
+            "radicle_proxy_peer_status_transitions_total{from=\"started\",to=\"online\"} 1\n"
+        ));
+        assert!(rendered.contains(
+            "radicle_proxy_http_request_duration_seconds_bucket\
+             {method=\"GET\",route=\"/v1/session\",status=\"200\",le=\"0.025\"} 1\n"
+        ));
+        assert!(rendered.contains(
+            "radicle_proxy_http_request_duration_seconds_bucket\
+             {method=\"GET\",route=\"/v1/session\",status=\"200\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(!rendered.contains("radicle_proxy_peer_status{"));
+    }
+
+    #[test]
+    fn record_events() {
+        let metrics = Metrics::default();
+        let urn = coco::Urn::new(
+            coco::Hash::hash(b"project"),
+            coco::uri::Protocol::Git,
+            coco::uri::Path::empty(),
+        );
+        let peer = coco::PeerId::from(coco::keys::SecretKey::new());
+        let gossip = coco::net::peer::Gossip {
+            urn: urn.clone(),
+            rev: None,
+            origin: None,
+        };
+        let oid = |sha: &str| coco::oid::Oid::try_from(sha).expect("failed to parse oid");
+
+        metrics.record_event(&coco::PeerEvent::Announced(
+            vec![
+                (urn.clone(), oid("0000000000000000000000000000000000000001")),
+                (urn, oid("0000000000000000000000000000000000000002")),
+            ]
+            .into_iter()
+            .collect(),
+        ));
+        for result in vec![
+            PutResult::Uninteresting,
+            PutResult::Uninteresting,
+            PutResult::Stale,
+            PutResult::Error,
+        ] {
+            metrics.record_event(&coco::PeerEvent::GossipFetched {
+                provider: peer,
+                gossip: gossip.clone(),
+                result,
+            });
+        }
+        metrics.record_event(&coco::PeerEvent::PeerSynced(peer));
+        metrics.record_event(&coco::PeerEvent::PeerSyncFailed(peer));
+        metrics.record_event(&coco::PeerEvent::PeerSyncFailed(peer));
+        // Events without a metric are ignored.
+        metrics.record_event(&coco::PeerEvent::RequestTick);
+
+        let rendered = metrics.render(None);
+        for sample in &[
+            "radicle_proxy_announcements_total 2\n",
+            "radicle_proxy_gossip_fetches_total{result=\"error\"} 1\n",
+            "radicle_proxy_gossip_fetches_total{result=\"stale\"} 1\n",
+            "radicle_proxy_gossip_fetches_total{result=\"uninteresting\"} 2\n",
+            "radicle_proxy_peer_syncs_total{result=\"failed\"} 2\n",
+            "radicle_proxy_peer_syncs_total{result=\"succeeded\"} 1\n",
+        ] {
+            assert!(
+                rendered.contains(sample),
+                "{:?} is missing from:\n{}",
+                sample,
+                rendered
+            );
+        }
+        assert!(!rendered.contains("result=\"applied\""));
+    }
+
+    #[test]
+    fn request_histogram() {
+        let metrics = Metrics::default();
+        // On the bound of the first bucket, twice in the second, in the seventh and beyond the
+        // last one.
+        for millis in &[5, 7, 10, 300, 20_000] {
+            metrics.record_request("GET", "/v1/session", 200, Duration::from_millis(*millis));
+        }
+        metrics.record_request("POST", "/v1/session", 500, Duration::from_millis(1));
+
+        let rendered = metrics.render(None);
+        let series = "radicle_proxy_http_request_duration_seconds";
+        let labels = "method=\"GET\",route=\"/v1/session\",status=\"200\"";
+        let buckets = rendered
+            .lines()
+            .filter(|line| line.starts_with(&format!("{}_bucket{{{},", series, labels)))
+            .map(|line| {
+                let (bucket, count) = line.split_at(line.rfind(' ').unwrap_or_default());
+                let bound = bucket.rsplit("le=").next().unwrap_or_default();
+                format!("{}{}", bound.trim_end_matches('}'), count)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            buckets,
+            vec![
+                "\"0.005\" 1",
+                "\"0.01\" 3",
+                "\"0.025\" 3",
+                "\"0.05\" 3",
+                "\"0.1\" 3",
+                "\"0.25\" 3",
+                "\"0.5\" 4",
+                "\"1\" 4",
+                "\"2.5\" 4",
+                "\"5\" 4",
+                "\"10\" 4",
+                "\"+Inf\" 5",
+            ]
+        );
+        assert!(rendered.contains(&format!("{}_count{{{}}} 5\n", series, labels)));
+        let sum = rendered
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("{}_sum{{{}}} ", series, labels)))
+            .and_then(|sum| sum.parse::<f64>().ok())
+            .expect("the sum is missing");
+        assert!((sum - 20.322).abs() < 1e-9, "unexpected sum {}", sum);
+
+        // Other methods, routes and statuses get their own series.
+        assert!(rendered.contains(&format!(
+            "{}_count{{method=\"POST\",route=\"/v1/session\",status=\"500\"}} 1\n",
+            series
+        )));
+    }
+}
@@ -0,0 +1,177 @@
+//! Moving an identity to another machine.
+//!
+//! A [`Bundle`] carries the encrypted key of the peer, all revisions of the user entity and the
+//! session, sealed with a passphrase chosen on [`export`]. On the new machine [`import`] restores
+//! the key and the session while the keystore is still sealed. The user entity can only be stored
+//! once the peer runs with the restored key, which is done by [`restore_pending`] after unsealing.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use coco::keystore::SecUtf8;
+
+use crate::{context, error, session};
+
+/// Version of the [`Bundle`] format written by [`export`].
+pub const VERSION: u8 = 1;
+
+/// Name for the storage bucket used for migration state.
+const BUCKET_NAME: &str = "migration";
+/// Name of the item used for a user entity that is waiting to be restored.
+const KEY_PENDING_USER: &str = "pending_user";
+
+/// Errors that occur when creating or reading a [`Bundle`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The contents of the bundle could not be (de)serialised.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// The bundle was written in a format this version can't read.
+    #[error("unsupported bundle version {0}, expected {}", VERSION)]
+    UnsupportedVersion(u8),
+}
+
+/// User entity of an imported [`Bundle`], waiting to be restored by [`restore_pending`].
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PendingUser {
+    /// JSON of the latest revision of the user entity.
+    user: String,
+    /// JSON of the earlier revisions of the user entity, oldest first.
+    ancestors: Vec<String>,
+}
+
+/// Passphrase protected file to move an identity to another machine.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    /// Format version, see [`VERSION`].
+    pub version: u8,
+    /// The [`Contents`] sealed with the export passphrase.
+    #[schemars(with = "serde_json::Value")]
+    pub sealed: coco::keystore::SecretBox,
+}
+
+/// Everything carried over by a [`Bundle`].
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Contents {
+    /// The key file as it is stored, still encrypted with the keystore passphrase.
+    key: Vec<u8>,
+    /// JSON of the latest revision of the user entity.
+    user: String,
+    /// JSON of the earlier revisions of the user entity, oldest first.
+    #[serde(default)]
+    ancestors: Vec<String>,
+    /// The session including identity and settings, if one was created.
+    session: Option<session::Session>,
+}
+
+/// Create a [`Bundle`] of the key, `owner` and the current session, sealed with `passphrase`.
+///
+/// # Errors
+///
+/// * Errors if the keystore can't export its key.
+/// * Errors if the user entity or the session can't be read.
+pub async fn export(
+    ctx: &context::Unsealed,
+    owner: &coco::user::User,
+    passphrase: SecUtf8,
+) -> Result<Bundle, error::Error> {
+    let keystore = ctx.keystore.clone();
+    let key = tokio::task::spawn_blocking(move || keystore.export_key())
+        .await
+        .expect("Task to export key was aborted")?;
+    let (user, ancestors) = ctx.state.export_owner(owner).await?;
+    let contents = Contents {
+        key,
+        user,
+        ancestors,
+        session: session::get_current(&ctx.store)?,
+    };
+    let data = serde_json::to_vec(&contents).map_err(Error::from)?;
+    let sealed = tokio::task::spawn_blocking(move || coco::keystore::seal(passphrase, &data))
+        .await
+        .expect("Task to seal bundle was aborted")?;
+
+    Ok(Bundle {
+        version: VERSION,
+        sealed,
+    })
+}
+
+/// Open `bundle` with `passphrase` and restore the key and the session. The keystore has to be
+/// empty, i.e. this is meant to run in a fresh `RAD_HOME` before the keystore is unsealed with the
+/// passphrase of the original key.
+///
+/// # Errors
+///
+/// * Errors if a key already exists.
+/// * Errors if the bundle version is not supported or the passphrase is wrong.
+/// * Errors if too many wrong passphrases were tried recently, see [`crate::keystore`].
+/// * Errors if the session or the pending user can't be stored.
+pub async fn import(
+    ctx: &context::Sealed,
+    bundle: Bundle,
+    passphrase: SecUtf8,
+) -> Result<(), error::Error> {
+    let keystore = ctx.keystore.clone();
+    if tokio::task::spawn_blocking(move || keystore.has_key())
+        .await
+        .expect("Task to look up key was aborted")?
+    {
+        return Err(coco::keystore::Error::key_exists().into());
+    }
+    if bundle.version != VERSION {
+        return Err(Error::UnsupportedVersion(bundle.version).into());
+    }
+
+    crate::keystore::attempt(&ctx.store)?;
+    let data =
+        tokio::task::spawn_blocking(move || coco::keystore::unseal(passphrase, bundle.sealed))
+            .await
+            .expect("Task to unseal bundle was aborted")
+            .map_err(error::Error::from);
+    let data = crate::keystore::settle(&ctx.store, data)?;
+    let contents: Contents = serde_json::from_slice(&data).map_err(Error::from)?;
+
+    let keystore = ctx.keystore.clone();
+    tokio::task::spawn_blocking(move || keystore.import_key(&contents.key))
+        .await
+        .expect("Task to import key was aborted")?;
+
+    if let Some(session) = contents.session {
+        session::restore(&ctx.store, session)?;
+    }
+    ctx.store
+        .bucket::<&str, kv::Json<PendingUser>>(Some(BUCKET_NAME))?
+        .set(
+            KEY_PENDING_USER,
+            kv::Json(PendingUser {
+                user: contents.user,
+                ancestors: contents.ancestors,
+            }),
+        )?;
+
+    Ok(())
+}
+
+/// Store the user entity of an imported [`Bundle`] and make it the default owner, once the peer
+/// runs with the imported key. Does nothing if there is no pending user.
+///
+/// # Errors
+///
+/// Errors if the user can't be restored or the store can't be accessed.
+pub async fn restore_pending(store: &kv::Store, state: &coco::State) -> Result<(), error::Error> {
+    let bucket = store.bucket::<&str, kv::Json<PendingUser>>(Some(BUCKET_NAME))?;
+    if let Some(pending) = bucket.get(KEY_PENDING_USER)?.map(kv::Codec::to_inner) {
+        let owner = state
+            .restore_owner(&pending.user, &pending.ancestors)
+            .await?;
+        session::set_identity(store, (state.peer_id(), owner).into())?;
+        bucket.remove(KEY_PENDING_USER)?;
+    }
+
+    Ok(())
+}
@@ -1,57 +1,191 @@
 //! Machinery to signal significant events to clients.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
+    convert::TryFrom as _,
+    path::PathBuf,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tokio::sync::{mpsc, RwLock};
 
-use coco::{convert::MaybeFrom, PeerEvent, PeerStatus};
+use coco::{convert::MaybeFrom, net::protocol::ProtocolEvent, PeerEvent, PeerStatus};
 
-/// Significant events happening during proxy runtime.
-#[derive(Clone, Debug)]
+pub mod inbox;
+
+/// Significant events happening during proxy runtime, which are serialized as the wrapped event,
+/// see [`Notification::to_json`].
+#[derive(Clone, Debug, JsonSchema)]
+#[schemars(untagged)]
 pub enum Notification {
     /// Event observed about the local peer.
     LocalPeer(LocalPeer),
+    /// Progress of a bulk project import.
+    Import(Import),
+    /// Change of the keystore state.
+    Keystore(Keystore),
+}
+
+/// Change of the keystore state.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+#[schemars(rename = "KeystoreNotification")]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Keystore {
+    /// The keystore was sealed, it has to be unsealed with the passphrase to continue.
+    #[serde(rename = "keystoreSealed", rename_all = "camelCase")]
+    Sealed {
+        /// What caused the keystore to be sealed.
+        reason: SealReason,
+    },
+}
+
+/// What caused the keystore to be sealed.
+#[derive(Clone, Copy, Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum SealReason {
+    /// No API requests were made for longer than the configured idle timeout.
+    Idle,
+    /// Sealing was requested through the API.
+    Requested,
+}
+
+/// Progress of a bulk project import.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Import {
+    /// The import of the enclosed number of repositories started.
+    #[serde(rename = "importStarted", rename_all = "camelCase")]
+    Started {
+        /// Number of repositories that are going to be imported.
+        total: usize,
+    },
+    /// A project was created for the repository.
+    #[serde(rename = "importProjectCreated", rename_all = "camelCase")]
+    ProjectCreated {
+        /// Location of the imported working copy.
+        path: PathBuf,
+        /// Urn of the created project.
+        #[schemars(with = "String")]
+        urn: coco::Urn,
+    },
+    /// Creating a project for the repository failed.
+    #[serde(rename = "importProjectFailed", rename_all = "camelCase")]
+    ProjectFailed {
+        /// Location of the working copy that failed to be imported.
+        path: PathBuf,
+        /// Reason the project creation failed.
+        reason: String,
+    },
+    /// All repositories of the import were processed.
+    #[serde(rename = "importFinished", rename_all = "camelCase")]
+    Finished {
+        /// Number of projects created.
+        created: usize,
+        /// Number of repositories that failed to be imported.
+        failed: usize,
+    },
 }
 
 /// Event observed about the local peer.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum LocalPeer {
+    /// A connection to a peer was established. Only the peer is known, the protocol doesn't
+    /// report the address it is connected on.
+    #[serde(rename_all = "camelCase")]
+    PeerConnected {
+        /// The connected peer.
+        #[schemars(with = "String")]
+        peer: coco::PeerId,
+    },
+    /// The connection to a peer is being closed. Like for [`LocalPeer::PeerConnected`], the
+    /// address of the peer isn't known.
+    #[serde(rename_all = "camelCase")]
+    PeerDisconnected {
+        /// The disconnected peer.
+        #[schemars(with = "String")]
+        peer: coco::PeerId,
+    },
+    /// Updates of projects were announced to the network.
+    #[serde(rename_all = "camelCase")]
+    Announced {
+        /// The announced updates.
+        updates: Vec<Announcement>,
+    },
+    /// Syncing with a peer completed.
+    #[serde(rename_all = "camelCase")]
+    PeerSynced {
+        /// The peer synced with.
+        #[schemars(with = "String")]
+        peer: coco::PeerId,
+    },
+    /// A project was updated with changes fetched from a peer.
+    #[serde(rename_all = "camelCase")]
     ProjectUpdated {
+        /// The peer the changes were fetched from.
+        #[schemars(with = "String")]
         provider: coco::PeerId,
+        /// Urn of the updated project.
+        #[schemars(with = "String")]
         urn: coco::Urn,
     },
     /// A request for a project was created and is pending submission to the network
     #[serde(rename_all = "camelCase")]
     RequestCreated {
         /// Urn of the project.
+        #[schemars(with = "String")]
         urn: coco::Urn,
     },
     /// A request for a project was cloned successfully.
     #[serde(rename_all = "camelCase")]
     RequestCloned {
         /// Origin the project was cloned from.
+        #[schemars(with = "String")]
         peer: coco::PeerId,
         /// Urn of the cloned project.
+        #[schemars(with = "String")]
+        urn: coco::Urn,
+    },
+    /// A requested project is being cloned from a peer.
+    #[serde(rename_all = "camelCase")]
+    RequestCloning {
+        /// Origin the project is cloned from.
+        #[schemars(with = "String")]
+        peer: coco::PeerId,
+        /// Urn of the project.
+        #[schemars(with = "String")]
         urn: coco::Urn,
     },
+    /// Cloning a requested project from a peer failed.
+    #[serde(rename_all = "camelCase")]
+    RequestFailed {
+        /// Origin the clone was attempted from.
+        #[schemars(with = "String")]
+        peer: coco::PeerId,
+        /// Urn of the project.
+        #[schemars(with = "String")]
+        urn: coco::Urn,
+        /// The reason the clone failed.
+        reason: String,
+    },
     /// A request for a project was queried on the network.
     #[serde(rename_all = "camelCase")]
     RequestQueried {
         /// Urn of the queried project.
+        #[schemars(with = "String")]
         urn: coco::Urn,
     },
     /// A request for a project timed out.
     #[serde(rename_all = "camelCase")]
     RequestTimedOut {
         /// Urn of the timed out project.
+        #[schemars(with = "String")]
         urn: coco::Urn,
     },
     /// Transition between two statuses occurred.
@@ -64,10 +198,49 @@ pub enum LocalPeer {
     },
 }
 
+/// An update of a project announced to the network.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Announcement {
+    /// Urn of the updated project.
+    #[schemars(with = "String")]
+    pub urn: coco::Urn,
+    /// The announced head.
+    pub oid: coco::oid::Oid,
+}
+
+impl Notification {
+    /// Serialise the wrapped event, which is tagged with its type in the `type` field.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the event can't be serialised.
+    pub fn to_json(&self) -> Result<serde_json::Value, serde_json::Error> {
+        match self {
+            Self::LocalPeer(event) => serde_json::to_value(event),
+            Self::Import(event) => serde_json::to_value(event),
+            Self::Keystore(event) => serde_json::to_value(event),
+        }
+    }
+}
+
 #[allow(clippy::wildcard_enum_match_arm)]
 impl MaybeFrom<PeerEvent> for Notification {
     fn maybe_from(event: PeerEvent) -> Option<Self> {
         match event {
+            PeerEvent::Announced(updates) => Some(Self::LocalPeer(LocalPeer::Announced {
+                updates: updates
+                    .into_iter()
+                    .map(|(urn, oid)| Announcement { urn, oid })
+                    .collect(),
+            })),
+            PeerEvent::Protocol(ProtocolEvent::Connected(peer)) => {
+                Some(Self::LocalPeer(LocalPeer::PeerConnected { peer }))
+            },
+            PeerEvent::Protocol(ProtocolEvent::Disconnecting(peer)) => {
+                Some(Self::LocalPeer(LocalPeer::PeerDisconnected { peer }))
+            },
+            PeerEvent::PeerSynced(peer) => Some(Self::LocalPeer(LocalPeer::PeerSynced { peer })),
             PeerEvent::GossipFetched {
                 provider, gossip, ..
             } => Some(Self::LocalPeer(LocalPeer::ProjectUpdated {
@@ -78,6 +251,17 @@ impl MaybeFrom<PeerEvent> for Notification {
                 peer: url.authority,
                 urn: url.urn,
             })),
+            PeerEvent::RequestCloning(url) => Some(Self::LocalPeer(LocalPeer::RequestCloning {
+                peer: url.authority,
+                urn: url.urn,
+            })),
+            PeerEvent::RequestFailed { url, reason } => {
+                Some(Self::LocalPeer(LocalPeer::RequestFailed {
+                    peer: url.authority,
+                    urn: url.urn,
+                    reason,
+                }))
+            },
             PeerEvent::RequestCreated(urn) => {
                 Some(Self::LocalPeer(LocalPeer::RequestCreated { urn }))
             },
@@ -95,23 +279,77 @@ impl MaybeFrom<PeerEvent> for Notification {
     }
 }
 
+/// Number of broadcast [`Event`]s kept to replay them to subscribers which missed them.
+pub const BACKLOG_SIZE: usize = 256;
+
+/// A broadcast [`Notification`] with its id.
+#[derive(Clone, Debug)]
+pub struct Event {
+    /// Id of the event, increasing with every broadcast and across restarts of the proxy.
+    pub id: u64,
+    /// The broadcast notification.
+    pub notification: Notification,
+}
+
+/// The most recently broadcast [`Event`]s.
+#[derive(Debug)]
+struct Backlog {
+    /// Id of the last broadcast event.
+    last_id: u64,
+    /// Up to [`BACKLOG_SIZE`] events, oldest first.
+    events: VecDeque<Event>,
+}
+
+impl Default for Backlog {
+    /// The ids start at the current time in microseconds since the UNIX epoch. So a client
+    /// resuming with the id of an event from before a restart gets all events of the backlog,
+    /// instead of skipping those which reused ids it has seen.
+    fn default() -> Self {
+        let last_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| {
+                u64::try_from(duration.as_micros()).unwrap_or_default()
+            });
+
+        Self {
+            last_id,
+            events: VecDeque::new(),
+        }
+    }
+}
+
 /// Manage active subscriptions and broadcast [`Notification`]s.
 #[derive(Clone, Debug, Default)]
 pub struct Subscriptions {
     /// Generator of unqiue keys for subscriptions.
     next_id: Arc<AtomicUsize>,
     /// Active subscribers.
-    subs: Arc<RwLock<HashMap<usize, mpsc::UnboundedSender<Notification>>>>,
+    subs: Arc<RwLock<HashMap<usize, mpsc::UnboundedSender<Event>>>>,
+    /// Recent events to replay to subscribers.
+    backlog: Arc<RwLock<Backlog>>,
 }
 
 impl Subscriptions {
     /// Broadcast [`Notification`] to all active subscriptions.
     pub async fn broadcast(&self, notification: Notification) {
+        // The backlog stays locked until the event is sent, so subscribers which are set up
+        // concurrently get it either from the backlog or their channel, but not from both.
+        let mut backlog = self.backlog.write().await;
+        backlog.last_id += 1;
+        let event = Event {
+            id: backlog.last_id,
+            notification,
+        };
+        backlog.events.push_back(event.clone());
+        if backlog.events.len() > BACKLOG_SIZE {
+            backlog.events.pop_front();
+        }
+
         // We use retain to discard all closed subscriptions.
         self.subs
             .write()
             .await
-            .retain(|_id, sender| sender.send(notification.clone()).is_ok());
+            .retain(|_id, sender| sender.send(event.clone()).is_ok());
     }
 
     /// Drop all stored senders, which terminates associated receivers and their streams.
@@ -119,13 +357,111 @@ impl Subscriptions {
         self.subs.write().await.clear();
     }
 
-    /// Set up a new subscription, ready to receive [`Notification`].
-    pub async fn subscribe(&self) -> mpsc::UnboundedReceiver<Notification> {
+    /// Set up a new subscription, ready to receive [`Event`]s.
+    pub async fn subscribe(&self) -> mpsc::UnboundedReceiver<Event> {
+        self.subscribe_since(None).await.2
+    }
+
+    /// Set up a new subscription like [`Self::subscribe`], which also returns the id of the last
+    /// broadcast event and the events in the backlog broadcast after the one with `last_id`.
+    /// Events older than the backlog are lost.
+    pub async fn subscribe_since(
+        &self,
+        last_id: Option<u64>,
+    ) -> (u64, Vec<Event>, mpsc::UnboundedReceiver<Event>) {
+        let backlog = self.backlog.read().await;
+        let missed = last_id.map_or_else(Vec::new, |last_id| {
+            backlog
+                .events
+                .iter()
+                .filter(|event| event.id > last_id)
+                .cloned()
+                .collect()
+        });
+
         let id = self.next_id.fetch_add(1, Ordering::Relaxed);
         let (sender, receiver) = mpsc::unbounded_channel();
-
         self.subs.write().await.insert(id, sender);
 
-        receiver
+        (backlog.last_id, missed, receiver)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use coco::convert::MaybeFrom as _;
+
+    use super::{Keystore, LocalPeer, Notification, SealReason, Subscriptions, BACKLOG_SIZE};
+
+    fn sealed() -> Notification {
+        Notification::Keystore(Keystore::Sealed {
+            reason: SealReason::Idle,
+        })
+    }
+
+    #[tokio::test]
+    async fn replay_backlog() {
+        let subscriptions = Subscriptions::default();
+        let (first_id, _, _) = subscriptions.subscribe_since(None).await;
+        for _ in 0..BACKLOG_SIZE + 2 {
+            subscriptions.broadcast(sealed()).await;
+        }
+
+        let (last_id, missed, mut receiver) = subscriptions.subscribe_since(Some(0)).await;
+        assert_eq!(last_id, first_id + BACKLOG_SIZE as u64 + 2);
+        assert_eq!(missed.len(), BACKLOG_SIZE);
+        assert_eq!(missed.first().map(|event| event.id), Some(first_id + 3));
+
+        let (_, missed, _) = subscriptions.subscribe_since(Some(last_id - 1)).await;
+        assert_eq!(
+            missed.iter().map(|event| event.id).collect::<Vec<_>>(),
+            vec![last_id]
+        );
+
+        subscriptions.broadcast(sealed()).await;
+        assert_eq!(
+            receiver.recv().await.map(|event| event.id),
+            Some(last_id + 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn ids_increase_across_restarts() {
+        let before = Subscriptions::default();
+        before.broadcast(sealed()).await;
+        let (last_id, _, _) = before.subscribe_since(None).await;
+        tokio::time::delay_for(std::time::Duration::from_millis(1)).await;
+
+        // A client resuming from an event of the previous run gets everything since the restart.
+        let after = Subscriptions::default();
+        after.broadcast(sealed()).await;
+        let (_, missed, _) = after.subscribe_since(Some(last_id)).await;
+        assert_eq!(missed.len(), 1);
+        assert!(missed[0].id > last_id);
+    }
+
+    #[test]
+    fn request_failed() {
+        let url = coco::uri::RadUrl {
+            urn: coco::Urn::new(
+                coco::Hash::hash(b"project"),
+                coco::uri::Protocol::Git,
+                coco::uri::Path::empty(),
+            ),
+            authority: coco::PeerId::from(coco::keys::SecretKey::new()),
+        };
+        let notification = Notification::maybe_from(coco::PeerEvent::RequestFailed {
+            url: url.clone(),
+            reason: "no connection".to_string(),
+        });
+
+        assert!(matches!(
+            notification,
+            Some(Notification::LocalPeer(LocalPeer::RequestFailed { peer, urn, reason }))
+                if peer == url.authority && urn == url.urn && reason == "no connection"
+        ));
+        assert!(Notification::maybe_from(coco::PeerEvent::RequestTick).is_none());
     }
 }
@@ -0,0 +1,357 @@
+//! Persistent inbox of [`LocalPeer`] notifications, so events happening while no client is
+//! subscribed aren't lost. Notifications are kept until they are dismissed or exceed the
+//! retention limits [`MAX_ITEMS`] and [`MAX_AGE`].
+//!
+//! The inbox is stored as a single item, so every change reads, modifies and writes it under a
+//! lock. Otherwise events pushed by the peer could be lost when a client marks or
+//! dismisses notifications at the same time.
+
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+
+use super::LocalPeer;
+
+/// Name for the storage bucket used for the inbox.
+const BUCKET_NAME: &str = "notifications";
+/// Name of the item used for the inbox.
+const KEY_INBOX: &str = "inbox";
+
+/// Maximum number of notifications kept, the oldest are dropped first.
+pub const MAX_ITEMS: usize = 1000;
+/// Maximum age of kept notifications, in seconds.
+pub const MAX_AGE: u64 = 30 * 24 * 60 * 60;
+/// Number of notifications returned by [`list`] if no limit is given.
+pub const DEFAULT_LIMIT: usize = 50;
+
+lazy_static::lazy_static! {
+    /// Serializes changes to the inbox, which read and write the same item.
+    static ref INBOX_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// A stored notification.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[schemars(rename = "InboxItem")]
+#[serde(rename_all = "camelCase")]
+pub struct Item {
+    /// Unique id of the notification, increasing with every notification.
+    pub id: u64,
+    /// Time the event occurred, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Whether the notification was marked as read.
+    pub read: bool,
+    /// The observed event.
+    pub event: LocalPeer,
+}
+
+/// A page of notifications, newest first.
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(rename = "InboxPage")]
+#[serde(rename_all = "camelCase")]
+pub struct Page {
+    /// Notifications on this page.
+    pub items: Vec<Item>,
+    /// Number of unread notifications in the inbox.
+    pub unread: usize,
+    /// Cursor to pass as `before` to get the next page, `None` if this is the last page.
+    pub next: Option<u64>,
+}
+
+/// All stored notifications.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Inbox {
+    /// Id of the next notification.
+    next_id: u64,
+    /// Notifications ordered by id, oldest first.
+    items: Vec<Item>,
+}
+
+/// Returns `true` if `event` is worth keeping in the inbox. Network activity like connections,
+/// syncs and status changes is only of interest while it happens.
+#[must_use]
+pub const fn is_persisted(event: &LocalPeer) -> bool {
+    match event {
+        LocalPeer::ProjectUpdated { .. }
+        | LocalPeer::RequestCreated { .. }
+        | LocalPeer::RequestCloned { .. }
+        | LocalPeer::RequestFailed { .. }
+        | LocalPeer::RequestTimedOut { .. } => true,
+        LocalPeer::PeerConnected { .. }
+        | LocalPeer::PeerDisconnected { .. }
+        | LocalPeer::Announced { .. }
+        | LocalPeer::PeerSynced { .. }
+        | LocalPeer::RequestCloning { .. }
+        | LocalPeer::RequestQueried { .. }
+        | LocalPeer::StatusChanged { .. } => false,
+    }
+}
+
+/// Add `event` to the inbox and drop notifications exceeding the retention limits.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn push(store: &kv::Store, event: LocalPeer) -> Result<Item, error::Error> {
+    let _guard = lock();
+    let mut inbox = get(store)?;
+    let now = now();
+
+    let item = Item {
+        id: inbox.next_id,
+        timestamp: now,
+        read: false,
+        event,
+    };
+    inbox.next_id += 1;
+    inbox.items.push(item.clone());
+
+    inbox
+        .items
+        .retain(|item| now.saturating_sub(item.timestamp) <= MAX_AGE);
+    let excess = inbox.items.len().saturating_sub(MAX_ITEMS);
+    inbox.items.drain(..excess);
+
+    set(store, inbox)?;
+
+    Ok(item)
+}
+
+/// List up to `limit` notifications older than the one with id `before`, newest first. With
+/// `unread_only` notifications marked as read are skipped.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn list(
+    store: &kv::Store,
+    before: Option<u64>,
+    limit: usize,
+    unread_only: bool,
+) -> Result<Page, error::Error> {
+    let inbox = get(store)?;
+    let unread = inbox.items.iter().filter(|item| !item.read).count();
+
+    let mut matching = inbox
+        .items
+        .into_iter()
+        .rev()
+        .filter(|item| before.map_or(true, |before| item.id < before))
+        .filter(|item| !unread_only || !item.read);
+    let items = matching.by_ref().take(limit).collect::<Vec<_>>();
+    let next = if matching.next().is_some() {
+        items.last().map(|item| item.id)
+    } else {
+        None
+    };
+
+    Ok(Page {
+        items,
+        unread,
+        next,
+    })
+}
+
+/// Mark the notification with `id` as read.
+///
+/// # Errors
+///
+/// * Errors with [`error::Error::NotificationNotFound`] if there is no such notification.
+/// * Errors if access to the store fails.
+pub fn mark_read(store: &kv::Store, id: u64) -> Result<(), error::Error> {
+    let _guard = lock();
+    let mut inbox = get(store)?;
+    inbox
+        .items
+        .iter_mut()
+        .find(|item| item.id == id)
+        .ok_or(error::Error::NotificationNotFound(id))?
+        .read = true;
+
+    set(store, inbox)
+}
+
+/// Mark all notifications as read.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn mark_all_read(store: &kv::Store) -> Result<(), error::Error> {
+    let _guard = lock();
+    let mut inbox = get(store)?;
+    for item in &mut inbox.items {
+        item.read = true;
+    }
+
+    set(store, inbox)
+}
+
+/// Remove the notification with `id` from the inbox.
+///
+/// # Errors
+///
+/// * Errors with [`error::Error::NotificationNotFound`] if there is no such notification.
+/// * Errors if access to the store fails.
+pub fn dismiss(store: &kv::Store, id: u64) -> Result<(), error::Error> {
+    let _guard = lock();
+    let mut inbox = get(store)?;
+    let len = inbox.items.len();
+    inbox.items.retain(|item| item.id != id);
+    if inbox.items.len() == len {
+        return Err(error::Error::NotificationNotFound(id));
+    }
+
+    set(store, inbox)
+}
+
+/// Acquire the [`INBOX_LOCK`].
+fn lock() -> MutexGuard<'static, ()> {
+    INBOX_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Get the stored [`Inbox`].
+fn get(store: &kv::Store) -> Result<Inbox, error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<Inbox>>(Some(BUCKET_NAME))?
+        .get(KEY_INBOX)?
+        .map(kv::Codec::to_inner)
+        .unwrap_or_default())
+}
+
+/// Store `inbox`.
+fn set(store: &kv::Store, inbox: Inbox) -> Result<(), error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<Inbox>>(Some(BUCKET_NAME))?
+        .set(KEY_INBOX, kv::Json(inbox))?)
+}
+
+/// Current time in seconds since the UNIX epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::{error, notification::LocalPeer};
+
+    use super::{dismiss, list, mark_all_read, mark_read, push, MAX_ITEMS};
+
+    fn event() -> LocalPeer {
+        LocalPeer::RequestTimedOut {
+            urn: coco::Urn::new(
+                coco::Hash::hash(b"project"),
+                coco::uri::Protocol::Git,
+                coco::uri::Path::empty(),
+            ),
+        }
+    }
+
+    #[test]
+    fn paginate() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        for _ in 0..5 {
+            push(&store, event())?;
+        }
+
+        let page = list(&store, None, 2, false)?;
+        assert_eq!(
+            page.items.iter().map(|item| item.id).collect::<Vec<_>>(),
+            vec![4, 3]
+        );
+        assert_eq!(page.next, Some(3));
+
+        let page = list(&store, page.next, 10, false)?;
+        assert_eq!(
+            page.items.iter().map(|item| item.id).collect::<Vec<_>>(),
+            vec![2, 1, 0]
+        );
+        assert_eq!(page.next, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_and_dismiss() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        let first = push(&store, event())?;
+        let second = push(&store, event())?;
+
+        mark_read(&store, first.id)?;
+        let page = list(&store, None, 10, true)?;
+        assert_eq!(page.unread, 1);
+        assert_eq!(page.items, vec![second.clone()]);
+
+        dismiss(&store, second.id)?;
+        assert!(matches!(
+            dismiss(&store, second.id),
+            Err(error::Error::NotificationNotFound(_))
+        ));
+        assert_eq!(list(&store, None, 10, false)?.unread, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        let threads = (0..8)
+            .map(|_| {
+                let store = store.clone();
+                std::thread::spawn(move || -> Result<(), error::Error> {
+                    for _ in 0..10 {
+                        push(&store, event())?;
+                        mark_all_read(&store)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().expect("thread panicked")?;
+        }
+
+        let page = list(&store, None, MAX_ITEMS, false)?;
+        assert_eq!(page.items.len(), 80);
+        assert_eq!(
+            page.items.first().map(|item| item.id),
+            Some(79),
+            "ids were handed out twice"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn retention() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        for _ in 0..=MAX_ITEMS {
+            push(&store, event())?;
+        }
+
+        let page = list(&store, None, MAX_ITEMS + 1, false)?;
+        assert_eq!(page.items.len(), MAX_ITEMS);
+        assert_eq!(page.items.last().map(|item| item.id), Some(1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+//! Configuration of the [`RunConfig`] of the peer, read from a TOML file and overridden by CLI
+//! flags. Durations are given in milliseconds, unset values fall back to the defaults of the
+//! proxy.
+//!
+//! ```toml
+//! [announce]
+//! interval_ms = 1000
+//!
+//! [sync]
+//! max_peers = 1
+//! on_startup = true
+//! period_ms = 5000
+//!
+//! [waiting_room]
+//! interval_ms = 500
+//! timeout_ms = 10000
+//! max_queries = 5
+//! max_clones = 5
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use coco::{
+    peer::run_config,
+    request::{Clones, Queries},
+    RunConfig,
+};
+
+/// Number of peers a sync is attempted with when coming online.
+const DEFAULT_SYNC_MAX_PEERS: usize = 1;
+
+/// Duration until the peer goes online regardless of how many syncs have succeeded.
+const DEFAULT_SYNC_PERIOD: Duration = Duration::from_secs(5);
+
+/// Errors when loading the peer configuration.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The configuration file couldn't be read.
+    #[error("failed to read peer config {path}: {source}")]
+    Read {
+        /// Location of the configuration file.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+
+    /// The configuration file isn't valid TOML or has unknown or mistyped fields.
+    #[error("failed to parse peer config {path}: {source}")]
+    Parse {
+        /// Location of the configuration file.
+        path: PathBuf,
+        /// The underlying error.
+        source: toml::de::Error,
+    },
+
+    /// A value is out of its allowed range.
+    #[error("invalid peer config value for `{field}`: {reason}")]
+    Invalid {
+        /// Name of the field in the configuration file.
+        field: &'static str,
+        /// What is wrong with the value.
+        reason: &'static str,
+    },
+}
+
+/// Peer configuration as given in the configuration file or by CLI flags.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Knobs for announcements of local updates.
+    pub announce: Announce,
+    /// Knobs for syncing with other peers when coming online.
+    pub sync: Sync,
+    /// Knobs for the waiting room tracking project requests.
+    pub waiting_room: WaitingRoom,
+}
+
+/// Knobs for announcements of local updates.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Announce {
+    /// Time between announcements, `0` disables them.
+    pub interval_ms: Option<u64>,
+}
+
+/// Knobs for syncing with other peers when coming online.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sync {
+    /// Number of peers a sync is attempted with.
+    pub max_peers: Option<usize>,
+    /// Whether to sync at all when coming online.
+    pub on_startup: Option<bool>,
+    /// Time until the peer goes online regardless of how many syncs have succeeded.
+    pub period_ms: Option<u64>,
+}
+
+/// Knobs for the waiting room tracking project requests.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaitingRoom {
+    /// Time between checks for requests ready to be queried or cloned.
+    pub interval_ms: Option<u64>,
+    /// Time a request waits before its next attempt.
+    pub timeout_ms: Option<u64>,
+    /// Query attempts after which a request times out, unlimited if unset.
+    pub max_queries: Option<usize>,
+    /// Clone attempts after which a request times out, unlimited if unset.
+    pub max_clones: Option<usize>,
+}
+
+impl Config {
+    /// Read the configuration from the TOML file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the file can't be read or parsed.
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        toml::from_str(&content).map_err(|source| Error::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Take the values set in `overrides` over the ones of `self`.
+    #[must_use]
+    pub fn merge(self, overrides: Self) -> Self {
+        Self {
+            announce: Announce {
+                interval_ms: overrides.announce.interval_ms.or(self.announce.interval_ms),
+            },
+            sync: Sync {
+                max_peers: overrides.sync.max_peers.or(self.sync.max_peers),
+                on_startup: overrides.sync.on_startup.or(self.sync.on_startup),
+                period_ms: overrides.sync.period_ms.or(self.sync.period_ms),
+            },
+            waiting_room: WaitingRoom {
+                interval_ms: overrides
+                    .waiting_room
+                    .interval_ms
+                    .or(self.waiting_room.interval_ms),
+                timeout_ms: overrides
+                    .waiting_room
+                    .timeout_ms
+                    .or(self.waiting_room.timeout_ms),
+                max_queries: overrides
+                    .waiting_room
+                    .max_queries
+                    .or(self.waiting_room.max_queries),
+                max_clones: overrides
+                    .waiting_room
+                    .max_clones
+                    .or(self.waiting_room.max_clones),
+            },
+        }
+    }
+
+    /// Check that all set values are in their allowed range.
+    ///
+    /// # Errors
+    ///
+    /// Errors with [`Error::Invalid`] for the first value out of range.
+    pub fn validate(&self) -> Result<(), Error> {
+        let zero = [
+            ("sync.max_peers", self.sync.max_peers == Some(0)),
+            ("sync.period_ms", self.sync.period_ms == Some(0)),
+            (
+                "waiting_room.interval_ms",
+                self.waiting_room.interval_ms == Some(0),
+            ),
+            (
+                "waiting_room.timeout_ms",
+                self.waiting_room.timeout_ms == Some(0),
+            ),
+            (
+                "waiting_room.max_queries",
+                self.waiting_room.max_queries == Some(0),
+            ),
+            (
+                "waiting_room.max_clones",
+                self.waiting_room.max_clones == Some(0),
+            ),
+        ];
+        if let Some((field, _)) = zero.iter().find(|(_, zero)| *zero) {
+            return Err(Error::Invalid {
+                field: *field,
+                reason: "must be greater than zero",
+            });
+        }
+
+        if let Some(timeout_ms) = self.waiting_room.timeout_ms {
+            // The waiting room multiplies the timeout with the number of attempts.
+            if timeout_ms > u64::from(u32::MAX) {
+                return Err(Error::Invalid {
+                    field: "waiting_room.timeout_ms",
+                    reason: "must be at most 4294967295",
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build the [`RunConfig`] of the peer, falling back to the defaults for unset values.
+    #[must_use]
+    pub fn run_config(&self) -> RunConfig {
+        let defaults = RunConfig::default();
+
+        RunConfig {
+            announce: run_config::Announce {
+                interval: self
+                    .announce
+                    .interval_ms
+                    .map_or(defaults.announce.interval, Duration::from_millis),
+            },
+            sync: run_config::Sync {
+                max_peers: self.sync.max_peers.unwrap_or(DEFAULT_SYNC_MAX_PEERS),
+                on_startup: self.sync.on_startup.unwrap_or(true),
+                period: self
+                    .sync
+                    .period_ms
+                    .map_or(DEFAULT_SYNC_PERIOD, Duration::from_millis),
+            },
+            waiting_room: run_config::WaitingRoom {
+                interval: self
+                    .waiting_room
+                    .interval_ms
+                    .map_or(defaults.waiting_room.interval, Duration::from_millis),
+                timeout: self
+                    .waiting_room
+                    .timeout_ms
+                    .map_or(defaults.waiting_room.timeout, Duration::from_millis),
+                max_queries: self
+                    .waiting_room
+                    .max_queries
+                    .map_or(defaults.waiting_room.max_queries, Queries::new),
+                max_clones: self
+                    .waiting_room
+                    .max_clones
+                    .map_or(defaults.waiting_room.max_clones, Clones::new),
+            },
+        }
+    }
+}
+
+/// Load the configuration from the file at `path`, if any, with `overrides` taking precedence.
+///
+/// # Errors
+///
+/// Errors if the file can't be read or parsed, or a value is out of its allowed range.
+pub fn load(path: Option<&Path>, overrides: Config) -> Result<Config, Error> {
+    let config = match path {
+        Some(path) => Config::read(path)?,
+        None => Config::default(),
+    }
+    .merge(overrides);
+    config.validate()?;
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use coco::request::{Clones, Queries};
+
+    use super::{load, Config, Error, Sync, WaitingRoom};
+
+    #[test]
+    fn file_with_overrides() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("peer.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [sync]
+            max_peers = 3
+            period_ms = 2000
+
+            [waiting_room]
+            timeout_ms = 30000
+            max_clones = 2
+            "#,
+        )?;
+
+        let overrides = Config {
+            sync: Sync {
+                max_peers: Some(7),
+                ..Sync::default()
+            },
+            ..Config::default()
+        };
+        let run_config = load(Some(path.as_path()), overrides)?.run_config();
+
+        assert_eq!(run_config.sync.max_peers, 7);
+        assert_eq!(run_config.sync.period, Duration::from_secs(2));
+        assert!(run_config.sync.on_startup);
+        assert_eq!(run_config.waiting_room.timeout, Duration::from_secs(30));
+        assert_eq!(run_config.waiting_room.max_clones, Clones::Max(2));
+        assert_eq!(run_config.waiting_room.max_queries, Queries::Infinite);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("peer.toml");
+
+        std::fs::write(&path, "[waiting_room]\ninterval_ms = 0\n")?;
+        let err = load(Some(path.as_path()), Config::default())
+            .err()
+            .map(|err| err.to_string());
+        assert_eq!(
+            err.as_deref(),
+            Some(
+                "invalid peer config value for `waiting_room.interval_ms`: must be greater than \
+                 zero"
+            )
+        );
+
+        std::fs::write(&path, "[sync]\nmax_peer = 2\n")?;
+        assert!(matches!(
+            load(Some(path.as_path()), Config::default()),
+            Err(Error::Parse { .. })
+        ));
+
+        let overrides = Config {
+            waiting_room: WaitingRoom {
+                max_queries: Some(0),
+                ..WaitingRoom::default()
+            },
+            ..Config::default()
+        };
+        assert!(matches!(
+            load(None, overrides),
+            Err(Error::Invalid {
+                field: "waiting_room.max_queries",
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+}
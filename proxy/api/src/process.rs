@@ -3,16 +3,24 @@
 #![allow(clippy::default_trait_access)]
 use argh::FromArgs;
 use futures::prelude::*;
-use std::{future::Future, net, sync::Arc, time::Duration};
+use std::{
+    future::Future,
+    net,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 use tokio::{
     signal::unix::{signal, SignalKind},
     sync::{watch, RwLock},
 };
 
-use coco::{convert::MaybeFrom as _, peer::run_config, seed, signer, Peer, RunConfig};
+use coco::{convert::MaybeFrom as _, seed, signer, Peer};
 
-use crate::{config, context, http, notification, service, session};
+use crate::{
+    config, context, http, metrics, migration, notification, peer_config, service, session, webhook,
+};
 
 /// Flags accepted by the proxy binary.
 #[derive(Clone, FromArgs)]
@@ -26,14 +34,87 @@ pub struct Args {
         default = "std::net::SocketAddr::from(([127, 0, 0, 1], 17246))"
     )]
     pub http_listen: net::SocketAddr,
+    /// also run HTTP API on a Unix domain socket at the given path, only accessible by the
+    /// current user (default: none)
+    #[argh(option)]
+    pub http_socket: Option<PathBuf>,
+    /// don't run HTTP API on the address passed with `--http-listen`, requires `--http-socket`
+    #[argh(switch)]
+    pub no_http_listen: bool,
     /// run the peer on a specified address:port (default: 0.0.0.0:0)
     #[argh(option, default = "std::net::SocketAddr::from(([0, 0, 0, 0], 0))")]
     pub peer_listen: net::SocketAddr,
     /// add one or more default seed addresses to initialise the settings store (default: none)
     #[argh(option, long = "default-seed")]
     pub default_seeds: Vec<String>,
+    /// delegate signing to the agent listening on the given Unix domain socket, which is unlocked
+    /// when the keystore is unsealed (default: none)
+    #[argh(option)]
+    pub signer_socket: Option<PathBuf>,
+    /// seal the keystore after the given number of seconds without API requests (default: never)
+    #[argh(option)]
+    pub idle_timeout: Option<u64>,
+    /// allow requests to the HTTP API from one or more origins (default: file://, the origin of
+    /// the app)
+    #[argh(option, long = "allowed-origin")]
+    pub allowed_origins: Vec<String>,
+    /// read the peer configuration from the given TOML file, the flags below take precedence
+    /// (default: none)
+    #[argh(option)]
+    pub peer_config: Option<PathBuf>,
+    /// milliseconds between announcements of local updates, 0 disables them (default: 1000)
+    #[argh(option)]
+    pub announce_interval_ms: Option<u64>,
+    /// number of peers to sync with when coming online (default: 1)
+    #[argh(option)]
+    pub sync_max_peers: Option<usize>,
+    /// whether to sync with peers when coming online (default: true)
+    #[argh(option)]
+    pub sync_on_startup: Option<bool>,
+    /// milliseconds until the peer goes online regardless of how many syncs have succeeded
+    /// (default: 5000)
+    #[argh(option)]
+    pub sync_period_ms: Option<u64>,
+    /// milliseconds between checks of the waiting room for requests to query or clone
+    /// (default: 500)
+    #[argh(option)]
+    pub waiting_room_interval_ms: Option<u64>,
+    /// milliseconds a request in the waiting room waits before its next attempt (default: 10000)
+    #[argh(option)]
+    pub waiting_room_timeout_ms: Option<u64>,
+    /// number of query attempts after which a request times out (default: unlimited)
+    #[argh(option)]
+    pub waiting_room_max_queries: Option<usize>,
+    /// number of clone attempts after which a request times out (default: unlimited)
+    #[argh(option)]
+    pub waiting_room_max_clones: Option<usize>,
 }
 
+impl Args {
+    /// The [`peer_config::Config`] given by flags, overriding the one of the `--peer-config` file.
+    fn peer_config_overrides(&self) -> peer_config::Config {
+        peer_config::Config {
+            announce: peer_config::Announce {
+                interval_ms: self.announce_interval_ms,
+            },
+            sync: peer_config::Sync {
+                max_peers: self.sync_max_peers,
+                on_startup: self.sync_on_startup,
+                period_ms: self.sync_period_ms,
+            },
+            waiting_room: peer_config::WaitingRoom {
+                interval_ms: self.waiting_room_interval_ms,
+                timeout_ms: self.waiting_room_timeout_ms,
+                max_queries: self.waiting_room_max_queries,
+                max_clones: self.waiting_room_max_clones,
+            },
+        }
+    }
+}
+
+/// Origins allowed to make requests to the HTTP API if none are passed: the app loaded from disk.
+const DEFAULT_ALLOWED_ORIGINS: &[&str] = &["file://"];
+
 /// Data required to run the peer and the API
 struct Rigging {
     /// The context provided to the API
@@ -42,6 +123,12 @@ struct Rigging {
     peer: Option<Peer>,
     /// Channel to receive updates to the seed nodes from the API
     seeds_sender: Option<watch::Sender<Vec<seed::Seed>>>,
+    /// Duration without API requests after which the keystore is sealed again
+    idle_timeout: Option<Duration>,
+    /// Address to serve the API on over TCP, if enabled
+    http_listen: Option<net::SocketAddr>,
+    /// Location of the Unix domain socket to serve the API on, if enabled
+    http_socket: Option<PathBuf>,
 }
 
 /// Run the proxy process
@@ -53,6 +140,11 @@ pub async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     // Required for `tokio::select`. We can’t put it on the element directly, though.
     #![allow(clippy::unreachable)]
 
+    if args.no_http_listen && args.http_socket.is_none() {
+        return Err("`--no-http-listen` requires `--http-socket`".into());
+    }
+    let peer_config = peer_config::load(args.peer_config.as_deref(), args.peer_config_overrides())?;
+
     let proxy_path = config::proxy_path()?;
     let bin_dir = config::bin_dir()?;
     coco::git_helper::setup(&proxy_path, &bin_dir)?;
@@ -73,6 +165,8 @@ pub async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     });
 
     let auth_token = Arc::new(RwLock::new(None));
+    let metrics = metrics::Metrics::default();
+    let subscriptions = notification::Subscriptions::default();
     loop {
         let notified_restart = service_manager.notified_restart();
         let service_handle = service_manager.handle();
@@ -81,10 +175,13 @@ pub async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
             service_handle,
             environment,
             auth_token.clone(),
+            metrics.clone(),
+            &peer_config,
+            bin_dir.clone(),
             args.clone(),
         )
         .await?;
-        let result = run_rigging(rigging, notified_restart).await;
+        let result = run_rigging(rigging, subscriptions.clone(), notified_restart).await;
         match result {
             // We've been shut down, ignore
             Err(RunError::Peer(coco::peer::Error::Spawn(_))) | Ok(()) => log::debug!("aborted"),
@@ -109,6 +206,10 @@ enum RunError {
     #[error(transparent)]
     Warp(#[from] warp::Error),
 
+    /// Binding the Unix domain socket for the API failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
     /// Event task aborted
     #[error(transparent)]
     SpawnAbortable(#[from] coco::SpawnAbortableError),
@@ -116,13 +217,15 @@ enum RunError {
 
 /// Run the API and peer.
 ///
-/// Returns when either the peer or the API stops.
+/// Returns when either the peer or the API stops. Notifications are broadcast to
+/// `subscriptions`, which outlive restarts so clients can resume their event streams.
 ///
 /// # Errors
 ///
 /// Errors when either the peer or the API error.
 async fn run_rigging(
     rigging: Rigging,
+    subscriptions: notification::Subscriptions,
     restart_signal: impl Future<Output = ()> + Send + 'static,
 ) -> Result<(), RunError> {
     // Required for `tokio::select`. We can’t put it on the element directly, though.
@@ -131,24 +234,49 @@ async fn run_rigging(
         ctx,
         peer,
         seeds_sender,
+        idle_timeout,
+        http_listen,
+        http_socket,
     } = rigging;
 
-    let subscriptions = notification::Subscriptions::default();
     let peer_subscriptions = subscriptions.clone();
+    let idle_subscriptions = subscriptions.clone();
     let server_ctx = ctx.clone();
+    let idle_ctx = ctx.clone();
+    let metrics = ctx.metrics().clone();
+    let inbox_store = ctx.store().clone();
+    let webhook_store = ctx.store().clone();
+    let webhook_subscriptions = subscriptions.clone();
 
     let server = async move {
         log::info!("starting API");
-        let api = http::api(server_ctx.clone(), subscriptions.clone());
-        let (_, server) = warp::serve(api).try_bind_with_graceful_shutdown(
-            server_ctx.http_listen(),
-            async move {
-                restart_signal.await;
-                subscriptions.clear().await;
-            },
-        )?;
+        let api = http::api(server_ctx, subscriptions.clone());
+        let shutdown = async move {
+            restart_signal.await;
+            subscriptions.clear().await;
+        }
+        .shared();
+        let mut servers = Vec::new();
+
+        if let Some(http_listen) = http_listen {
+            let (_, server) = warp::serve(api.clone())
+                .try_bind_with_graceful_shutdown(http_listen, shutdown.clone())?;
+            servers.push(server.boxed());
+        }
+        if let Some(http_socket) = http_socket {
+            let mut listener = http::bind_socket(&http_socket)?;
+            log::info!("serving API @ '{}'", http_socket.display());
+            servers.push(
+                async move {
+                    warp::serve(api)
+                        .serve_incoming_with_graceful_shutdown(listener.incoming(), shutdown)
+                        .await
+                }
+                .boxed(),
+            );
+        }
 
-        server.await;
+        future::join_all(servers).await;
         Ok(())
     };
 
@@ -186,17 +314,34 @@ async fn run_rigging(
             });
             tasks.push(seeds_event_task.map_err(RunError::from).boxed());
         }
+        if let (Some(idle_timeout), context::Context::Unsealed(unsealed)) = (idle_timeout, idle_ctx)
+        {
+            let idle_task = coco::SpawnAbortable::new(async move {
+                seal_when_idle(unsealed, idle_timeout, idle_subscriptions).await
+            });
+            tasks.push(idle_task.map_err(RunError::from).boxed());
+        }
+
         let peer_event_task = coco::SpawnAbortable::new({
             let mut peer_events = peer.subscribe();
 
             async move {
                 loop {
-                    if let Some(notification) = notification::Notification::maybe_from(
-                        peer_events
-                            .recv()
-                            .await
-                            .expect("Failed to receive peer event"),
-                    ) {
+                    let event = peer_events
+                        .recv()
+                        .await
+                        .expect("Failed to receive peer event");
+                    metrics.record_event(&event);
+                    if let Some(notification) = notification::Notification::maybe_from(event) {
+                        if let notification::Notification::LocalPeer(event) = &notification {
+                            if notification::inbox::is_persisted(event) {
+                                if let Err(err) =
+                                    notification::inbox::push(&inbox_store, event.clone())
+                                {
+                                    log::error!("Failed to persist notification: {}", err);
+                                }
+                            }
+                        }
                         peer_subscriptions.broadcast(notification).await
                     }
                 }
@@ -204,6 +349,10 @@ async fn run_rigging(
         });
         tasks.push(peer_event_task.map_err(RunError::from).boxed());
 
+        let webhook_task =
+            coco::SpawnAbortable::new(webhook::run(webhook_store, webhook_subscriptions));
+        tasks.push(webhook_task.map_err(RunError::from).boxed());
+
         let peer = async move {
             log::info!("starting peer");
             peer.into_running().await
@@ -222,6 +371,9 @@ async fn rig(
     service_handle: service::Handle,
     environment: &service::Environment,
     auth_token: Arc<RwLock<Option<String>>>,
+    metrics: metrics::Metrics,
+    peer_config: &peer_config::Config,
+    bin_dir: PathBuf,
     args: Args,
 ) -> Result<Rigging, Box<dyn std::error::Error>> {
     let store_path = if let Some(temp_dir) = &environment.temp_dir {
@@ -232,22 +384,54 @@ async fn rig(
     };
 
     let store = kv::Store::new(kv::Config::new(store_path).flush_every_ms(100))?;
+    let http_listen = if args.no_http_listen {
+        None
+    } else {
+        Some(args.http_listen)
+    };
+    let allowed_origins = if args.allowed_origins.is_empty() {
+        DEFAULT_ALLOWED_ORIGINS
+            .iter()
+            .map(|origin| (*origin).to_string())
+            .collect()
+    } else {
+        args.allowed_origins.clone()
+    };
 
-    if let Some(key) = environment.key {
-        let signer = signer::BoxedSigner::new(signer::SomeSigner { signer: key });
+    // With an agent the peer only starts once the agent was unlocked by unsealing the keystore.
+    let signer = if let Some(key) = environment.key {
+        Some(signer::BoxedSigner::new(signer::SomeSigner { signer: key }))
+    } else if let Some(agent) = environment.agent.clone() {
+        log::info!("signing with agent @ '{}'", agent.socket().display());
+        Some(signer::BoxedSigner::new(signer::SomeSigner {
+            signer: agent,
+        }))
+    } else {
+        None
+    };
 
+    if let Some(signer) = signer {
         let seeds = session_seeds(&store, &args.default_seeds).await?;
         let (seeds_sender, seeds_receiver) = watch::channel(seeds);
 
         let config = coco::config::configure(
             environment.coco_paths.clone(),
-            key,
+            signer.clone(),
             args.peer_listen,
             coco::config::StreamDiscovery::new(seeds_receiver),
         );
 
-        let (peer, state) =
-            coco::into_peer_state(config, signer.clone(), store.clone(), coco_run_config()).await?;
+        let (peer, state) = coco::into_peer_state(
+            config,
+            signer.clone(),
+            store.clone(),
+            peer_config.run_config(),
+        )
+        .await?;
+
+        if let Err(err) = migration::restore_pending(&store, &state).await {
+            log::error!("Failed to restore imported identity: {}", err);
+        }
 
         let peer_control = peer.control();
         let ctx = context::Context::Unsealed(context::Unsealed {
@@ -257,15 +441,23 @@ async fn rig(
             test: environment.test_mode,
             http_listen: args.http_listen,
             default_seeds: args.default_seeds,
+            allowed_origins,
             service_handle: service_handle.clone(),
             auth_token,
             keystore: environment.keystore.clone(),
+            signer_socket: args.signer_socket,
+            bin_dir,
+            metrics,
+            last_activity: Arc::new(RwLock::new(Instant::now())),
         });
 
         Ok(Rigging {
             ctx,
             peer: Some(peer),
             seeds_sender: Some(seeds_sender),
+            idle_timeout: args.idle_timeout.map(Duration::from_secs),
+            http_listen,
+            http_socket: args.http_socket,
         })
     } else {
         let ctx = context::Context::Sealed(context::Sealed {
@@ -273,18 +465,51 @@ async fn rig(
             test: environment.test_mode,
             http_listen: args.http_listen,
             default_seeds: args.default_seeds,
+            allowed_origins,
             service_handle,
             auth_token,
             keystore: environment.keystore.clone(),
+            signer_socket: args.signer_socket,
+            bin_dir,
+            metrics,
         });
         Ok(Rigging {
             ctx,
             peer: None,
             seeds_sender: None,
+            idle_timeout: None,
+            http_listen,
+            http_socket: args.http_socket,
         })
     }
 }
 
+/// Wait until no authenticated API request was made for `idle_timeout`, then seal the keystore
+/// and notify subscribers about it.
+async fn seal_when_idle(
+    ctx: context::Unsealed,
+    idle_timeout: Duration,
+    subscriptions: notification::Subscriptions,
+) {
+    loop {
+        let deadline = *ctx.last_activity.read().await + idle_timeout;
+        if Instant::now() >= deadline {
+            break;
+        }
+        tokio::time::delay_until(deadline.into()).await;
+    }
+
+    log::info!("sealing keystore after {:?} without activity", idle_timeout);
+    subscriptions
+        .broadcast(notification::Notification::Keystore(
+            notification::Keystore::Sealed {
+                reason: notification::SealReason::Idle,
+            },
+        ))
+        .await;
+    context::Context::from(ctx).seal().await;
+}
+
 /// Get and resolve seed settings from the session store.
 async fn session_seeds(
     store: &kv::Store,
@@ -297,14 +522,54 @@ async fn session_seeds(
     }))
 }
 
-/// [`RunConfig`] for the coco peer.
-fn coco_run_config() -> RunConfig {
-    RunConfig {
-        sync: run_config::Sync {
-            max_peers: 1,
-            on_startup: true,
-            period: Duration::from_secs(5),
-        },
-        ..RunConfig::default()
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use coco::{keystore::SecUtf8, signer::agent};
+
+    use crate::{context, notification, service};
+
+    #[tokio::test]
+    async fn seal_when_idle() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let socket = tmp_dir.path().join("agent.sock");
+        let key = coco::keys::SecretKey::new();
+        tokio::spawn(agent::serve(agent::bind(&socket)?, move |_passphrase| {
+            Ok::<_, String>(key)
+        }));
+        agent::Agent::unlock(&socket, SecUtf8::from("asdf")).await?;
+
+        let mut manager = service::Manager::new(true)?;
+        let mut ctx = context::Unsealed::tmp(&tmp_dir).await?;
+        ctx.service_handle = manager.handle();
+        ctx.signer_socket = Some(socket.clone());
+        ctx.service_handle
+            .set_secret_key(coco::keys::SecretKey::new());
+        assert!(manager.environment()?.key.is_some());
+
+        let auth_token = ctx.auth_token.clone();
+        let subscriptions = notification::Subscriptions::default();
+        let mut notifications = subscriptions.subscribe().await;
+
+        super::seal_when_idle(ctx, Duration::from_millis(10), subscriptions).await;
+
+        let notification = notifications.recv().await.map(|event| event.notification);
+        assert!(matches!(
+            notification,
+            Some(notification::Notification::Keystore(
+                notification::Keystore::Sealed {
+                    reason: notification::SealReason::Idle
+                }
+            ))
+        ));
+        assert_eq!(*auth_token.read().await, None);
+        // The restarted services come up sealed until the keystore is unsealed again.
+        let environment = manager.environment()?;
+        assert!(environment.key.is_none());
+        assert!(environment.agent.is_none());
+        assert!(agent::Agent::connect(&socket).await.is_err());
+
+        Ok(())
     }
 }
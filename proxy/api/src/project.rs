@@ -3,14 +3,18 @@
 
 use std::{collections::HashSet, ops::Deref};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use coco::project::peer;
 
 use crate::{error, identity};
 
+pub mod working_copy;
+
 /// Object encapsulating project metadata.
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[schemars(rename = "ProjectMetadata")]
 #[serde(rename_all = "camelCase")]
 pub struct Metadata {
     /// Project name.
@@ -20,6 +24,7 @@ pub struct Metadata {
     /// Default branch for checkouts, often used as mainline as well.
     pub default_branch: String,
     /// List of maintainers.
+    #[schemars(with = "HashSet<String>")]
     pub maintainers: HashSet<coco::Urn>,
 }
 
@@ -43,10 +48,11 @@ where
 /// Radicle project for sharing and collaborating.
 ///
 /// See [`Projects`] for a detailed breakdown of both kinds of projects.
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Project<S> {
     /// Unique identifier of the project in the network.
+    #[schemars(with = "String")]
     pub urn: coco::Urn,
     /// Unambiguous identifier pointing at this identity.
     pub shareable_entity_identifier: String,
@@ -114,7 +120,7 @@ where
 }
 
 /// Codified relation in form of roles and availability of project views.
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 pub struct Peer(peer::Peer<peer::Status<identity::Identity>>);
 
 impl Deref for Peer {
@@ -148,7 +154,7 @@ impl Deref for Tracked {
 }
 
 /// Partial failures that occur when getting the list of projects.
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum Failure {
     /// We couldn't get a default branch for the project.
@@ -157,7 +163,7 @@ pub enum Failure {
     Stats(Partial),
     /// We couldn't get the signed refs of the project, and so we can't determine if it's tracked
     /// or contributed.
-    SignedRefs(Full),
+    SignedRefs(#[schemars(with = "Project<coco::source::StatsSchema>")] Full),
 }
 
 /// All projects contained in a user's monorepo.
@@ -345,9 +351,10 @@ pub async fn list_for_user(
             .tracked(project.urn())
             .await?
             .into_iter()
-            .filter_map(coco::project::Peer::replicated_remote)
-            .find(|(_, project_user)| project_user.urn() == *user);
-        if let Some((peer, _)) = tracked {
+            .filter_map(coco::project::peer::Devices::replicated)
+            .find(|devices| devices.user.urn() == *user)
+            .and_then(|devices| devices.peer_ids.first().copied());
+        if let Some(peer) = tracked {
             let branch = state
                 .get_branch(project.urn(), peer, project.default_branch().to_owned())
                 .await?;
@@ -0,0 +1,161 @@
+//! Registry of the working copies created for projects, either by checking them out or by
+//! creating a project from a repository on disk.
+//!
+//! The working copies of a project are stored as a single item, so registering one reads,
+//! modifies and writes it under a lock. Otherwise a working copy could be lost when two are
+//! registered for the same project at the same time.
+
+use std::{
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error;
+
+/// Name for the storage bucket used for the registry.
+const BUCKET_NAME: &str = "working_copies";
+
+lazy_static::lazy_static! {
+    /// Serializes registrations, which read and write the item of the project.
+    static ref REGISTRY_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// A working copy of a project on the local filesystem.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkingCopy {
+    /// Location of the working copy.
+    pub path: PathBuf,
+    /// The peer the working copy was based on, `None` for our own.
+    #[schemars(with = "Option<String>")]
+    pub peer_id: Option<coco::PeerId>,
+    /// Time the working copy was created or last updated, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+}
+
+impl WorkingCopy {
+    /// Create a registry entry for the working copy at `path`, timestamped now.
+    #[must_use]
+    pub fn new(path: PathBuf, peer_id: Option<coco::PeerId>) -> Self {
+        Self {
+            path,
+            peer_id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+        }
+    }
+}
+
+/// A [`WorkingCopy`] together with its live state on disk.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[schemars(rename = "WorkingCopyEntry")]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    /// The registered working copy.
+    #[serde(flatten)]
+    pub working_copy: WorkingCopy,
+    /// Uncommitted changes and divergence from the `rad` remote, `None` if the working copy
+    /// can't be read anymore, e.g. because it was removed.
+    pub status: Option<coco::WorkingCopyStatus>,
+}
+
+/// Record `working_copy` for the project identified by `urn`. An existing entry for the same path
+/// is replaced.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn register(
+    store: &kv::Store,
+    urn: &coco::Urn,
+    working_copy: WorkingCopy,
+) -> Result<(), error::Error> {
+    let _guard = lock();
+    let mut working_copies = list(store, urn)?;
+    working_copies.retain(|existing| existing.path != working_copy.path);
+    working_copies.push(working_copy);
+
+    Ok(store
+        .bucket::<&str, kv::Json<Vec<WorkingCopy>>>(Some(BUCKET_NAME))?
+        .set(urn.to_string().as_str(), kv::Json(working_copies))?)
+}
+
+/// Acquire the [`REGISTRY_LOCK`].
+fn lock() -> MutexGuard<'static, ()> {
+    REGISTRY_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// List the working copies registered for the project identified by `urn`.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn list(store: &kv::Store, urn: &coco::Urn) -> Result<Vec<WorkingCopy>, error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<Vec<WorkingCopy>>>(Some(BUCKET_NAME))?
+        .get(urn.to_string().as_str())?
+        .map(kv::Codec::to_inner)
+        .unwrap_or_default())
+}
+
+/// List the working copies registered for the project identified by `urn` with their current
+/// [`coco::WorkingCopyStatus`].
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn entries(store: &kv::Store, urn: &coco::Urn) -> Result<Vec<Entry>, error::Error> {
+    Ok(list(store, urn)?
+        .into_iter()
+        .map(|working_copy| {
+            let status = coco::working_copy_status(&working_copy.path)
+                .map_err(|err| {
+                    log::warn!(
+                        "Failed to read working copy @ '{}': {}",
+                        working_copy.path.display(),
+                        err
+                    );
+                })
+                .ok();
+            Entry {
+                working_copy,
+                status,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::{list, register, WorkingCopy};
+
+    #[test]
+    fn register_replaces_same_path() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+        let urn = coco::Urn::new(
+            coco::Hash::hash(b"geez"),
+            coco::uri::Protocol::Git,
+            coco::uri::Path::empty(),
+        );
+
+        let first = WorkingCopy::new(tmp_dir.path().join("first"), None);
+        let second = WorkingCopy::new(tmp_dir.path().join("second"), None);
+        register(&store, &urn, first.clone())?;
+        register(&store, &urn, second.clone())?;
+        register(&store, &urn, first.clone())?;
+
+        assert_eq!(list(&store, &urn)?, vec![second, first]);
+
+        Ok(())
+    }
+}
@@ -10,6 +10,10 @@ pub struct Environment {
     ///
     /// If this is `None` coco is not started.
     pub key: Option<coco::keys::SecretKey>,
+    /// Agent signing for the coco peer, unlocked when the keystore was unsealed.
+    ///
+    /// Takes the place of [`Environment::key`] when signing is delegated to an agent.
+    pub agent: Option<coco::signer::agent::Agent>,
     /// If set, we use a temporary directory for on-disk persistence.
     pub temp_dir: Option<tempfile::TempDir>,
     /// Paths for on-disk persistence.
@@ -44,6 +48,7 @@ impl Environment {
             let keystore = Arc::new(coco::keystore::memory());
             Ok(Self {
                 key: None,
+                agent: None,
                 temp_dir: Some(temp_dir),
                 coco_paths,
                 keystore,
@@ -54,6 +59,7 @@ impl Environment {
             let keystore = Arc::new(coco::keystore::file(coco_paths.clone()));
             Ok(Self {
                 key: None,
+                agent: None,
                 temp_dir: None,
                 coco_paths,
                 keystore,
@@ -108,7 +114,11 @@ impl Manager {
                     self.environment = Environment::new(test_mode)?
                 },
                 Message::SetSecretKey(key) => self.environment.key = Some(key),
-                Message::Seal => self.environment.key = None,
+                Message::SetAgent(agent) => self.environment.agent = Some(agent),
+                Message::Seal => {
+                    self.environment.key = None;
+                    self.environment.agent = None;
+                },
             }
         }
 
@@ -131,6 +141,8 @@ enum Message {
     Reset,
     /// Unseal the key store with the given secret key
     SetSecretKey(coco::keys::SecretKey),
+    /// Unseal the key store by signing with the given unlocked agent
+    SetAgent(coco::signer::agent::Agent),
     /// Seal the key store and reload the services
     Seal,
 }
@@ -155,6 +167,11 @@ impl Handle {
         self.send_message(Message::SetSecretKey(key))
     }
 
+    /// Unseal the key store by signing with the given unlocked agent
+    pub fn set_agent(&mut self, agent: coco::signer::agent::Agent) {
+        self.send_message(Message::SetAgent(agent))
+    }
+
     /// Seal the key store and reload the services
     pub fn seal(&mut self) {
         self.send_message(Message::Seal)
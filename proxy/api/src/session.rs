@@ -1,6 +1,7 @@
 //! Management of local session state like the currently used identity, wallet related data and
 //! configuration of all sorts.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{error, identity};
@@ -13,7 +14,7 @@ const BUCKET_NAME: &str = "session";
 const KEY_CURRENT: &str = "current";
 
 /// Container for all local state.
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Session {
     /// The currently used [`identity::Identity`].
@@ -88,6 +89,30 @@ pub fn set_settings(store: &kv::Store, settings: settings::Settings) -> Result<(
     Ok(())
 }
 
+/// Update the identity of the session, e.g. after its profile was edited. Does nothing if there is
+/// no session yet.
+///
+/// # Errors
+///
+/// Errors when we cannot access the store.
+pub fn set_identity(store: &kv::Store, identity: identity::Identity) -> Result<(), error::Error> {
+    if let Some(mut session) = get_current(store)? {
+        session.identity = identity;
+
+        set_current(store, session)?
+    }
+    Ok(())
+}
+
+/// Replace the current session with `session`, e.g. one carried over from another machine.
+///
+/// # Errors
+///
+/// Errors when we cannot write to the store.
+pub fn restore(store: &kv::Store, session: Session) -> Result<(), error::Error> {
+    set_current(store, session)
+}
+
 /// Initialize a session for tests.
 ///
 /// Creates an owner identity for the session using `owner_handle` and stores the current session.
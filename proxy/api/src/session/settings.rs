@@ -1,8 +1,9 @@
 //! User controlled parameters for application appearance, behaviour and state.
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// User controlled parameters for application appearance, behaviour and state.
-#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
     /// Currently set appearance parameters.
@@ -11,10 +12,13 @@ pub struct Settings {
     pub coco: CoCo,
     #[serde(default)]
     pub feature_flags: FeatureFlags,
+    /// Local endpoints notified about proxy events.
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
 }
 
 /// Knobs for the look and feel.
-#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Appearance {
     /// Currently active color scheme.
@@ -24,7 +28,7 @@ pub struct Appearance {
 }
 
 /// Color schemes available.
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum Theme {
     /// A dark theme.
@@ -40,7 +44,7 @@ impl Default for Theme {
 }
 
 /// User dismissable textual hints.
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Hints {
     /// Whether to show hints about how to set up the remote helper.
@@ -56,7 +60,7 @@ impl Default for Hints {
 }
 
 /// `CoCo` config parameters subject to user preferences
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, JsonSchema)]
 pub struct CoCo {
     /// Peers to connect to at startup.
     pub seeds: Vec<String>,
@@ -70,7 +74,7 @@ impl Default for CoCo {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, JsonSchema)]
 pub struct FeatureFlags {
     /// Whether the funding feature is enabled or disabled.
     pub funding: bool,
@@ -81,3 +85,21 @@ impl Default for FeatureFlags {
         Self { funding: false }
     }
 }
+
+/// A local HTTP endpoint which gets notifications POSTed to, see [`crate::webhook`].
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    /// URL of the endpoint, which has to be served over plain HTTP on a loopback address.
+    pub url: String,
+    /// Types of the notifications to deliver, e.g. `projectUpdated`. All are delivered if empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Secret the payloads are signed with. It is only accepted when the settings are updated and
+    /// kept apart from the session, so it never shows up when the session is read.
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    /// Whether a secret is stored for the webhook.
+    #[serde(default)]
+    pub has_secret: bool,
+}
@@ -0,0 +1,231 @@
+//! Named, long-lived API tokens for local clients like editor integrations and scripts. Other than
+//! the auth cookie, which grants full control, a token only grants access to the endpoints
+//! covered by its [`Scope`]s. Only a hash of every token is stored.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::HEXLOWER;
+use rand::Rng as _;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use warp::http::Method;
+
+use crate::error;
+
+/// Name for the storage bucket used for API tokens.
+const BUCKET_NAME: &str = "api_tokens";
+/// Name of the item used for the list of tokens.
+const KEY_TOKENS: &str = "tokens";
+
+/// Set of endpoints a [`Token`] grants access to. Paths are relative to `/v1`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Scope {
+    /// Read-only access to projects, identities and their source: `GET` requests to `source`,
+    /// `projects`, `identities` and `avatars`.
+    Browse,
+    /// Creating, checking out and importing projects: all requests to `projects`, except those
+    /// covered by [`Scope::Network`].
+    Projects,
+    /// Control of network activity: tracking, project requests and the peer event stream.
+    Network,
+}
+
+impl Scope {
+    /// Returns `true` if the scope grants access to the endpoint `path` below `/v1` with
+    /// `method`.
+    #[must_use]
+    pub fn allows(self, method: &Method, path: &[&str]) -> bool {
+        let network = matches!(path, ["projects", "requests", ..] | ["notifications", ..])
+            || matches!(
+                path,
+                ["projects", _, "track", ..] | ["projects", _, "untrack", ..]
+            );
+
+        match self {
+            Self::Browse => {
+                *method == Method::GET
+                    && matches!(
+                        path,
+                        ["source", ..] | ["projects", ..] | ["identities", ..] | ["avatars", ..]
+                    )
+            },
+            Self::Projects => !network && matches!(path, ["projects", ..]),
+            Self::Network => network,
+        }
+    }
+}
+
+/// A named API token, without the secret.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Token {
+    /// Unique name to tell tokens apart, e.g. the client using it.
+    pub name: String,
+    /// The endpoints the token grants access to.
+    pub scopes: Vec<Scope>,
+    /// Time the token was created, in seconds since the UNIX epoch.
+    pub created_at: u64,
+}
+
+impl Token {
+    /// Returns `true` if any of the scopes of the token grants access to the endpoint `path` with
+    /// `method`, see [`Scope::allows`].
+    #[must_use]
+    pub fn allows(&self, method: &Method, path: &[&str]) -> bool {
+        self.scopes.iter().any(|scope| scope.allows(method, path))
+    }
+}
+
+/// A [`Token`] as it is stored.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    /// The stored token.
+    #[serde(flatten)]
+    token: Token,
+    /// Hash of the secret.
+    hash: String,
+}
+
+/// Token returned on [`create`], the only time the secret is revealed.
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(rename = "CreatedToken")]
+#[serde(rename_all = "camelCase")]
+pub struct Created {
+    /// The created token.
+    #[serde(flatten)]
+    pub token: Token,
+    /// Secret to send as `Authorization: Bearer <secret>`.
+    pub secret: String,
+}
+
+/// Create a token called `name` which grants access to `scopes`.
+///
+/// # Errors
+///
+/// * Errors if a token with the same name exists.
+/// * Errors if access to the store fails.
+pub fn create(
+    store: &kv::Store,
+    name: String,
+    scopes: Vec<Scope>,
+) -> Result<Created, error::Error> {
+    let mut entries = entries(store)?;
+    if entries.iter().any(|entry| entry.token.name == name) {
+        return Err(error::Error::TokenExists(name));
+    }
+
+    let secret = HEXLOWER.encode(&rand::thread_rng().gen::<[u8; 32]>());
+    let token = Token {
+        name,
+        scopes,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs()),
+    };
+    entries.push(Entry {
+        token: token.clone(),
+        hash: hash(&secret),
+    });
+    set(store, entries)?;
+
+    Ok(Created { token, secret })
+}
+
+/// List all tokens.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn list(store: &kv::Store) -> Result<Vec<Token>, error::Error> {
+    Ok(entries(store)?
+        .into_iter()
+        .map(|entry| entry.token)
+        .collect())
+}
+
+/// Revoke the token called `name`. Returns `false` if there was no such token.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn revoke(store: &kv::Store, name: &str) -> Result<bool, error::Error> {
+    let mut entries = entries(store)?;
+    let len = entries.len();
+    entries.retain(|entry| entry.token.name != name);
+    let revoked = entries.len() != len;
+    set(store, entries)?;
+
+    Ok(revoked)
+}
+
+/// Find the token for `secret`.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn authorize(store: &kv::Store, secret: &str) -> Result<Option<Token>, error::Error> {
+    let hash = hash(secret);
+    Ok(entries(store)?
+        .into_iter()
+        .find(|entry| entry.hash == hash)
+        .map(|entry| entry.token))
+}
+
+/// Get all stored [`Entry`]s.
+fn entries(store: &kv::Store) -> Result<Vec<Entry>, error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<Vec<Entry>>>(Some(BUCKET_NAME))?
+        .get(KEY_TOKENS)?
+        .map(kv::Codec::to_inner)
+        .unwrap_or_default())
+}
+
+/// Store `entries` as the list of all tokens.
+fn set(store: &kv::Store, entries: Vec<Entry>) -> Result<(), error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<Vec<Entry>>>(Some(BUCKET_NAME))?
+        .set(KEY_TOKENS, kv::Json(entries))?)
+}
+
+/// Hash of a token secret. The secrets are random, so a plain hash suffices.
+fn hash(secret: &str) -> String {
+    coco::Hash::hash(secret.as_bytes()).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use warp::http::Method;
+
+    use super::{authorize, create, list, revoke, Scope};
+
+    #[test]
+    fn scopes() {
+        assert!(Scope::Browse.allows(&Method::GET, &["source", "tree", "urn"]));
+        assert!(!Scope::Browse.allows(&Method::POST, &["projects"]));
+        assert!(Scope::Projects.allows(&Method::POST, &["projects"]));
+        assert!(!Scope::Projects.allows(&Method::PUT, &["projects", "urn", "track", "peer"]));
+        assert!(Scope::Network.allows(&Method::PUT, &["projects", "urn", "track", "peer"]));
+        assert!(Scope::Network.allows(&Method::PUT, &["projects", "requests", "urn"]));
+        assert!(!Scope::Network.allows(&Method::GET, &["tokens"]));
+    }
+
+    #[test]
+    fn create_authorize_revoke() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
+
+        let created = create(&store, "editor".to_string(), vec![Scope::Browse])?;
+        assert!(create(&store, "editor".to_string(), vec![]).is_err());
+        assert_eq!(list(&store)?, vec![created.token.clone()]);
+        assert_eq!(authorize(&store, &created.secret)?, Some(created.token));
+        assert_eq!(authorize(&store, "guess")?, None);
+
+        assert!(revoke(&store, "editor")?);
+        assert_eq!(authorize(&store, &created.secret)?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,486 @@
+//! Delivery of [`crate::notification::Notification`]s to the local HTTP endpoints registered as
+//! [`settings::Webhook`]s in the session settings. Every payload is signed with the secret of the
+//! webhook, failed deliveries are retried and the outcome of every delivery is kept in a log.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use data_encoding::HEXLOWER;
+use hmac::{Mac as _, NewMac as _};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use warp::http::{header, StatusCode, Uri};
+
+use crate::{
+    error,
+    notification::{Event, Subscriptions},
+    session::{self, settings},
+};
+
+/// Name for the storage bucket used for webhooks.
+const BUCKET_NAME: &str = "webhooks";
+/// Name of the item used for the delivery log.
+const KEY_DELIVERIES: &str = "deliveries";
+/// Name of the item used for the secrets of the webhooks, keyed by their URL. They are kept apart
+/// from the session settings, so reading the session never reveals them.
+const KEY_SECRETS: &str = "secrets";
+
+/// Maximum number of deliveries kept in the log, the oldest are dropped first.
+pub const MAX_DELIVERIES: usize = 200;
+/// Number of attempts to deliver a payload before giving up.
+const MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubles with every further retry.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Time an endpoint has to respond before the attempt is considered failed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Header carrying the type of the delivered notification.
+pub const EVENT_HEADER: &str = "x-radicle-event";
+/// Header carrying the signature of the payload as `sha256=<hex encoded HMAC-SHA256>`, keyed
+/// with the secret of the webhook.
+pub const SIGNATURE_HEADER: &str = "x-radicle-signature";
+
+/// Errors when delivering a payload.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The request couldn't be built.
+    #[error(transparent)]
+    Request(#[from] warp::http::Error),
+
+    /// The request failed.
+    #[error(transparent)]
+    Http(#[from] hyper::Error),
+
+    /// The endpoint didn't respond in time.
+    #[error("the endpoint did not respond within {0:?}")]
+    Timeout(Duration),
+
+    /// The endpoint responded with an error.
+    #[error("the endpoint responded with {0}")]
+    Status(StatusCode),
+}
+
+/// Outcome of the delivery of a notification to a webhook.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    /// Id of the delivered [`Event`].
+    pub event_id: u64,
+    /// Type of the delivered notification.
+    pub event_type: String,
+    /// URL of the webhook.
+    pub url: String,
+    /// Time of the last attempt, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Number of attempts made.
+    pub attempts: u32,
+    /// Status code of the last response, if there was one.
+    pub status: Option<u16>,
+    /// Reason the last attempt failed, `None` if the delivery succeeded.
+    pub error: Option<String>,
+}
+
+/// Payload POSTed to webhooks.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Payload {
+    /// Id of the event, the same as in the notification stream.
+    id: u64,
+    /// Type of the notification.
+    #[serde(rename = "type")]
+    kind: String,
+    /// Time the payload was created, in seconds since the UNIX epoch.
+    timestamp: u64,
+    /// The notification.
+    data: serde_json::Value,
+}
+
+/// Check that all `webhooks` point at local endpoints.
+///
+/// # Errors
+///
+/// Errors with [`error::Error::InvalidWebhook`] for the first webhook whose URL isn't served over
+/// plain HTTP on a loopback address.
+pub fn validate(webhooks: &[settings::Webhook]) -> Result<(), error::Error> {
+    for webhook in webhooks {
+        let is_local = webhook.url.parse::<Uri>().ok().map_or(false, |uri| {
+            uri.scheme_str() == Some("http")
+                && matches!(
+                    uri.host(),
+                    Some("localhost") | Some("127.0.0.1") | Some("[::1]") | Some("::1")
+                )
+        });
+        if !is_local {
+            return Err(error::Error::InvalidWebhook(webhook.url.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Move the secrets passed with `webhooks` out of the settings into their own store, keep the
+/// stored secret of webhooks passed without one and forget the secrets of removed webhooks.
+///
+/// # Errors
+///
+/// * Errors with [`error::Error::MissingWebhookSecret`] for the first webhook which has neither a
+///   new nor a stored secret.
+/// * Errors if access to the store fails.
+pub fn store_secrets(
+    store: &kv::Store,
+    webhooks: &mut [settings::Webhook],
+) -> Result<(), error::Error> {
+    let stored = get_secrets(store)?;
+    let mut secrets = HashMap::new();
+
+    for webhook in webhooks.iter_mut() {
+        let secret = webhook
+            .secret
+            .take()
+            .or_else(|| stored.get(&webhook.url).cloned())
+            .ok_or_else(|| error::Error::MissingWebhookSecret(webhook.url.clone()))?;
+        secrets.insert(webhook.url.clone(), secret);
+        webhook.has_secret = true;
+    }
+
+    Ok(store
+        .bucket::<&str, kv::Json<HashMap<String, String>>>(Some(BUCKET_NAME))?
+        .set(KEY_SECRETS, kv::Json(secrets))?)
+}
+
+/// Deliver all notifications broadcast to `subscriptions` to the webhooks of the current session
+/// and record the outcomes in the delivery log.
+pub async fn run(store: kv::Store, subscriptions: Subscriptions) {
+    let mut events = subscriptions.subscribe().await;
+    let (delivered, mut deliveries) = mpsc::unbounded_channel();
+
+    // Deliveries are retried concurrently, while the log is written from this task only.
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => dispatch(&store, event, &delivered),
+                None => break,
+            },
+            Some(delivery) = deliveries.recv() => {
+                if let Err(err) = record(&store, delivery) {
+                    log::error!("Failed to record webhook delivery: {}", err);
+                }
+            },
+        }
+    }
+}
+
+/// Start the delivery of `event` to all webhooks interested in it. The outcomes are sent to
+/// `delivered`.
+fn dispatch(store: &kv::Store, event: Event, delivered: &mpsc::UnboundedSender<Delivery>) {
+    let webhooks = match session::get_current(store) {
+        Ok(session) => session.map(|session| session.settings.webhooks),
+        Err(err) => {
+            log::error!("Failed to read webhooks: {}", err);
+            None
+        },
+    }
+    .unwrap_or_default();
+    if webhooks.is_empty() {
+        return;
+    }
+
+    let (kind, body) = match payload(&event) {
+        Ok(payload) => payload,
+        Err(err) => {
+            log::error!("Failed to serialise webhook payload: {}", err);
+            return;
+        },
+    };
+
+    let secrets = match get_secrets(store) {
+        Ok(secrets) => secrets,
+        Err(err) => {
+            log::error!("Failed to read webhook secrets: {}", err);
+            return;
+        },
+    };
+
+    for webhook in webhooks {
+        if !webhook.events.is_empty() && !webhook.events.contains(&kind) {
+            continue;
+        }
+        // Sessions stored before the secrets were moved out still carry them.
+        let secret = match secrets
+            .get(&webhook.url)
+            .or_else(|| webhook.secret.as_ref())
+        {
+            Some(secret) => secret.clone(),
+            None => {
+                log::warn!("Skipping webhook {} without a secret", webhook.url);
+                continue;
+            },
+        };
+
+        let delivered = delivered.clone();
+        let event_id = event.id;
+        let kind = kind.clone();
+        let body = body.clone();
+        tokio::spawn(async move {
+            let delivery = deliver(&webhook.url, &secret, event_id, kind, body).await;
+            delivered.send(delivery).ok();
+        });
+    }
+}
+
+/// Build the type and the serialised [`Payload`] for `event`.
+fn payload(event: &Event) -> Result<(String, Vec<u8>), serde_json::Error> {
+    let data = event.notification.to_json()?;
+    let kind = data["type"].as_str().unwrap_or_default().to_string();
+    let body = serde_json::to_vec(&Payload {
+        id: event.id,
+        kind: kind.clone(),
+        timestamp: now(),
+        data,
+    })?;
+
+    Ok((kind, body))
+}
+
+/// POST `body` signed with `secret` to `url` until it succeeds or [`MAX_ATTEMPTS`] are exceeded.
+async fn deliver(url: &str, secret: &str, event_id: u64, kind: String, body: Vec<u8>) -> Delivery {
+    let mut delivery = Delivery {
+        event_id,
+        event_type: kind,
+        url: url.to_string(),
+        timestamp: now(),
+        attempts: 0,
+        status: None,
+        error: None,
+    };
+    let mut delay = RETRY_DELAY;
+
+    loop {
+        delivery.attempts += 1;
+        delivery.timestamp = now();
+
+        let result = post(url, secret, &delivery.event_type, body.clone()).await;
+        delivery.status = match &result {
+            Ok(status) | Err(Error::Status(status)) => Some(status.as_u16()),
+            Err(_) => None,
+        };
+        match result {
+            Ok(_) => {
+                delivery.error = None;
+                break;
+            },
+            Err(err) => delivery.error = Some(err.to_string()),
+        }
+
+        if delivery.attempts >= MAX_ATTEMPTS {
+            log::warn!(
+                "Failed to deliver event {} to webhook {}: {:?}",
+                delivery.event_id,
+                delivery.url,
+                delivery.error
+            );
+            break;
+        }
+        tokio::time::delay_for(delay).await;
+        delay *= 2;
+    }
+
+    delivery
+}
+
+/// POST `body` signed with `secret` to `url`.
+async fn post(url: &str, secret: &str, kind: &str, body: Vec<u8>) -> Result<StatusCode, Error> {
+    let request = hyper::Request::post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, kind)
+        .header(SIGNATURE_HEADER, sign(secret, &body))
+        .body(hyper::Body::from(body))?;
+
+    let response = tokio::time::timeout(TIMEOUT, hyper::Client::new().request(request))
+        .await
+        .map_err(|_| Error::Timeout(TIMEOUT))??;
+
+    if response.status().is_success() {
+        Ok(response.status())
+    } else {
+        Err(Error::Status(response.status()))
+    }
+}
+
+/// Signature of `body` for the [`SIGNATURE_HEADER`].
+#[must_use]
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_varkey(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+
+    format!("sha256={}", HEXLOWER.encode(&mac.finalize().into_bytes()))
+}
+
+/// List the logged deliveries, newest first.
+///
+/// # Errors
+///
+/// Errors if access to the store fails.
+pub fn deliveries(store: &kv::Store) -> Result<Vec<Delivery>, error::Error> {
+    let mut deliveries = get(store)?;
+    deliveries.reverse();
+
+    Ok(deliveries)
+}
+
+/// Add `delivery` to the log and drop the oldest ones exceeding [`MAX_DELIVERIES`].
+fn record(store: &kv::Store, delivery: Delivery) -> Result<(), error::Error> {
+    let mut deliveries = get(store)?;
+    deliveries.push(delivery);
+    let excess = deliveries.len().saturating_sub(MAX_DELIVERIES);
+    deliveries.drain(..excess);
+
+    Ok(store
+        .bucket::<&str, kv::Json<Vec<Delivery>>>(Some(BUCKET_NAME))?
+        .set(KEY_DELIVERIES, kv::Json(deliveries))?)
+}
+
+/// Get the logged deliveries, oldest first.
+fn get(store: &kv::Store) -> Result<Vec<Delivery>, error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<Vec<Delivery>>>(Some(BUCKET_NAME))?
+        .get(KEY_DELIVERIES)?
+        .map(kv::Codec::to_inner)
+        .unwrap_or_default())
+}
+
+/// Get the stored secrets of the webhooks, keyed by their URL.
+fn get_secrets(store: &kv::Store) -> Result<HashMap<String, String>, error::Error> {
+    Ok(store
+        .bucket::<&str, kv::Json<HashMap<String, String>>>(Some(BUCKET_NAME))?
+        .get(KEY_SECRETS)?
+        .map(kv::Codec::to_inner)
+        .unwrap_or_default())
+}
+
+/// Current time in seconds since the UNIX epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use pretty_assertions::assert_eq;
+    use tokio::sync::mpsc;
+    use warp::{http::StatusCode, Filter as _};
+
+    use crate::{
+        notification::{Event, Keystore, Notification, SealReason},
+        session::settings,
+    };
+
+    #[test]
+    fn validate() {
+        let webhook = |url: &str| settings::Webhook {
+            url: url.to_string(),
+            events: vec![],
+            secret: Some("secret".to_string()),
+            has_secret: false,
+        };
+
+        assert!(super::validate(&[webhook("http://127.0.0.1:8080/hook")]).is_ok());
+        assert!(super::validate(&[webhook("http://localhost/hook")]).is_ok());
+        assert!(super::validate(&[webhook("https://example.com/hook")]).is_err());
+        assert!(super::validate(&[webhook("http://10.0.0.1/hook")]).is_err());
+    }
+
+    #[test]
+    fn sign() {
+        // Test case 2 of RFC 4231.
+        assert_eq!(
+            super::sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn deliver_signed_payload() -> Result<(), Box<dyn std::error::Error>> {
+        // A local test server standing in for the endpoint.
+        let (received, mut requests) = mpsc::unbounded_channel();
+        let endpoint = warp::post()
+            .and(warp::header::<String>(super::EVENT_HEADER))
+            .and(warp::header::<String>(super::SIGNATURE_HEADER))
+            .and(warp::body::bytes())
+            .map(move |kind: String, signature: String, body: bytes::Bytes| {
+                received.send((kind, signature, body)).ok();
+                warp::reply()
+            });
+        let (addr, server) = warp::serve(endpoint).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let url = format!("http://{}/hook", addr);
+        let event = Event {
+            id: 7,
+            notification: Notification::Keystore(Keystore::Sealed {
+                reason: SealReason::Idle,
+            }),
+        };
+        let (kind, body) = super::payload(&event)?;
+
+        let delivery = super::deliver(&url, "secret", event.id, kind, body).await;
+        assert_eq!(delivery.status, Some(200));
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.error, None);
+
+        let (kind, signature, body) = requests.recv().await.ok_or("no request received")?;
+        assert_eq!(kind, "keystoreSealed");
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        let payload: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(payload["id"], 7);
+        assert_eq!(payload["data"]["reason"], "idle");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retry_failed_delivery() -> Result<(), Box<dyn std::error::Error>> {
+        // An endpoint failing the first request.
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let endpoint = warp::post().map({
+            let attempts = attempts.clone();
+            move || {
+                let status = if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                } else {
+                    StatusCode::OK
+                };
+                warp::reply::with_status(warp::reply(), status)
+            }
+        });
+        let (addr, server) = warp::serve(endpoint).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let delivery = super::deliver(
+            &format!("http://{}/hook", addr),
+            "secret",
+            1,
+            "keystoreSealed".to_string(),
+            b"{}".to_vec(),
+        )
+        .await;
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.status, Some(200));
+        assert_eq!(delivery.error, None);
+
+        Ok(())
+    }
+}
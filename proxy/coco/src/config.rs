@@ -8,9 +8,9 @@ use std::{
 
 use tokio::sync::{mpsc, watch};
 
-use librad::{keys, net, net::discovery, paths, peer::PeerId};
+use librad::{net, net::discovery, paths, peer::PeerId};
 
-use crate::seed;
+use crate::{seed, signer};
 
 lazy_static::lazy_static! {
     /// Localhost binding to any available port, i.e. `127.0.0.1:0`.
@@ -65,13 +65,13 @@ pub type Disco = discovery::Static<
 ///
 /// Results in an error if the [`paths::Paths`] could not be created.
 pub fn default(
-    key: keys::SecretKey,
+    signer: signer::BoxedSigner,
     path: impl AsRef<std::path::Path>,
-) -> Result<net::peer::PeerConfig<Disco, keys::SecretKey>, io::Error> {
+) -> Result<net::peer::PeerConfig<Disco, signer::BoxedSigner>, io::Error> {
     let paths = paths::Paths::from_root(path)?;
     Ok(configure(
         paths,
-        key,
+        signer,
         *LOCALHOST_ANY,
         static_seed_discovery(vec![]),
     ))
@@ -82,15 +82,15 @@ pub fn default(
 #[must_use]
 pub fn configure<D>(
     paths: paths::Paths,
-    key: keys::SecretKey,
+    signer: signer::BoxedSigner,
     listen_addr: SocketAddr,
     disco: D,
-) -> net::peer::PeerConfig<D, keys::SecretKey> {
+) -> net::peer::PeerConfig<D, signer::BoxedSigner> {
     let gossip_params = net::gossip::MembershipParams::default();
     let storage_config = net::peer::StorageConfig::default();
 
     net::peer::PeerConfig {
-        signer: key,
+        signer,
         paths,
         listen_addr,
         gossip_params,
@@ -3,6 +3,7 @@
 
 use std::{fmt, str::FromStr};
 
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 
 use librad::{meta::user, peer};
@@ -78,6 +79,20 @@ impl Serialize for Identifier {
     }
 }
 
+impl JsonSchema for Identifier {
+    fn schema_name() -> String {
+        "Identifier".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+}
+
 impl<'de> Deserialize<'de> for Identifier {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
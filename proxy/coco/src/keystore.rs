@@ -3,15 +3,21 @@
 //! This module provides the [`Keystore`] trait and the [`file()`] and [`memory()`] functions to
 //! construct specific [`Keystore`] implementations.
 
-use std::convert::Infallible;
+use std::{
+    convert::Infallible,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use librad::{keys, paths};
 pub use radicle_keystore::pinentry::SecUtf8;
 use radicle_keystore::{
-    crypto::{self, Pwhash, SecretBoxError},
+    crypto::{self, Crypto as _, Pwhash, SecretBoxError},
     file, Keystore as _, SecretKeyExt,
 };
 
+pub use radicle_keystore::crypto::SecretBox;
+
 /// Storage for one secret key.
 pub trait Keystore {
     /// Create a key and store it encrypted with the given passphrase.
@@ -29,10 +35,70 @@ pub trait Keystore {
     /// * Errors if backend fails to retrieve the data.
     /// * Errors if there is no key in the storage yet.
     fn get(&self, passphrase: SecUtf8) -> Result<keys::SecretKey, Error>;
+
+    /// Re-encrypt the stored key with `new` after unlocking it with `old`. The key remains
+    /// encrypted with `old` if this fails.
+    ///
+    /// # Errors
+    ///
+    /// * Errors if the `old` passphrase is wrong.
+    /// * Errors if there is no key in the storage yet.
+    /// * Errors if the backend fails to persist the re-encrypted key.
+    fn change_passphrase(&self, old: SecUtf8, new: SecUtf8) -> Result<(), Error>;
+
+    /// Returns `true` if a key is stored.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the backend can't tell whether a key is stored.
+    fn has_key(&self) -> Result<bool, Error>;
+
+    /// Get the stored key as it is persisted, i.e. still encrypted with its passphrase, so it can
+    /// be moved to another machine and restored there with [`Keystore::import_key`].
+    ///
+    /// # Errors
+    ///
+    /// * Errors if there is no key in the storage yet.
+    /// * Errors if the backend can't hand out the encrypted key.
+    fn export_key(&self) -> Result<Vec<u8>, Error>;
+
+    /// Store a key obtained from [`Keystore::export_key`]. The key can then be retrieved with
+    /// [`Keystore::get`] using the passphrase it was originally created with.
+    ///
+    /// # Errors
+    ///
+    /// * Errors if a key already exists.
+    /// * Errors if the backend fails to persist the key.
+    fn import_key(&self, key: &[u8]) -> Result<(), Error>;
+}
+
+/// Encrypt `data` with `passphrase`, using the same scheme that protects the keys on disk.
+///
+/// # Errors
+///
+/// Errors if the encryption fails.
+pub fn seal(passphrase: SecUtf8, data: &[u8]) -> Result<SecretBox, Error> {
+    Ok(Pwhash::new(passphrase, *crypto::KDF_PARAMS_PROD)
+        .seal(data)
+        .map_err(FileError::Crypto)?)
+}
+
+/// Decrypt `sealed` data produced by [`seal`].
+///
+/// # Errors
+///
+/// Errors if the passphrase is wrong.
+pub fn unseal(passphrase: SecUtf8, sealed: SecretBox) -> Result<Vec<u8>, Error> {
+    let data = Pwhash::new(passphrase, *crypto::KDF_PARAMS_PROD)
+        .unseal(sealed)
+        .map_err(FileError::Crypto)?;
+    Ok(data.unsecure().to_vec())
 }
 
 /// File name component of the file path to the key.
 const KEY_PATH: &str = "librad.key";
+/// File name component of the file path the key is written to before it replaces [`KEY_PATH`].
+const KEY_PATH_TMP: &str = "librad.key.tmp";
 
 /// Create a [`Keystore`] that is backed by an encrypted file on disk.
 ///
@@ -57,11 +123,20 @@ type FileStorage = radicle_keystore::FileStorage<
 >;
 
 impl FileStore {
+    /// Location of the key file.
+    fn key_path(&self) -> PathBuf {
+        self.paths.keys_dir().join(KEY_PATH)
+    }
+
     /// Get the [`FileStorage`] backend for this key store.
     fn store(&self, passphrase: SecUtf8) -> FileStorage {
-        let key_path = self.paths.keys_dir().join(KEY_PATH);
+        Self::store_at(&self.key_path(), passphrase)
+    }
+
+    /// Get a [`FileStorage`] backend for the key file at `path`.
+    fn store_at(path: &Path, passphrase: SecUtf8) -> FileStorage {
         let crypto = Pwhash::new(passphrase, *crypto::KDF_PARAMS_PROD);
-        FileStorage::new(&key_path, crypto)
+        FileStorage::new(path, crypto)
     }
 }
 
@@ -83,6 +158,47 @@ impl Keystore for FileStore {
         let key_pair = self.store(passphrase).get_key()?;
         Ok(key_pair.secret_key)
     }
+
+    fn change_passphrase(&self, old: SecUtf8, new: SecUtf8) -> Result<(), Error> {
+        let key = self.get(old)?;
+
+        // Write the re-encrypted key next to the current one and swap them with a rename, so
+        // the key file is never left half written.
+        let tmp_path = self.paths.keys_dir().join(KEY_PATH_TMP);
+        match fs::remove_file(&tmp_path) {
+            Ok(()) => {},
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => return Err(FileError::Io(err).into()),
+        }
+        Self::store_at(&tmp_path, new).put_key(key)?;
+        fs::rename(&tmp_path, self.key_path()).map_err(FileError::Io)?;
+
+        Ok(())
+    }
+
+    fn has_key(&self) -> Result<bool, Error> {
+        Ok(self.key_path().exists())
+    }
+
+    fn export_key(&self) -> Result<Vec<u8>, Error> {
+        match fs::read(self.key_path()) {
+            Ok(key) => Ok(key),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(FileError::NoSuchKey.into()),
+            Err(err) => Err(FileError::Io(err).into()),
+        }
+    }
+
+    fn import_key(&self, key: &[u8]) -> Result<(), Error> {
+        let key_path = self.key_path();
+        if key_path.exists() {
+            return Err(FileError::KeyExists.into());
+        }
+
+        fs::create_dir_all(self.paths.keys_dir()).map_err(FileError::Io)?;
+        fs::write(key_path, key).map_err(FileError::Io)?;
+
+        Ok(())
+    }
 }
 
 /// Create an insecure in-memory [`Keystore`].
@@ -129,6 +245,37 @@ impl Keystore for MemoryStore {
             Err(FileError::NoSuchKey.into())
         }
     }
+
+    fn change_passphrase(&self, old: SecUtf8, new: SecUtf8) -> Result<(), Error> {
+        let mut key_and_passphrase = self
+            .key_and_passphrase
+            .lock()
+            .expect("Failed to access memory key");
+        match &mut *key_and_passphrase {
+            Some((_key, stored_passphrase)) if *stored_passphrase == old => {
+                *stored_passphrase = new;
+                Ok(())
+            },
+            Some(_) => Err(FileError::Crypto(SecretBoxError::InvalidKey).into()),
+            None => Err(FileError::NoSuchKey.into()),
+        }
+    }
+
+    fn has_key(&self) -> Result<bool, Error> {
+        Ok(self
+            .key_and_passphrase
+            .lock()
+            .expect("Failed to access memory key")
+            .is_some())
+    }
+
+    fn export_key(&self) -> Result<Vec<u8>, Error> {
+        Err(Error::unsupported())
+    }
+
+    fn import_key(&self, _key: &[u8]) -> Result<(), Error> {
+        Err(Error::unsupported())
+    }
 }
 
 /// Error type for the [`FileStorage`] backend.
@@ -138,24 +285,88 @@ type FileError = file::Error<SecretBoxError<Infallible>, keys::IntoSecretKeyErro
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 pub struct Error {
-    #[from]
     /// The inner error
-    inner: FileError,
+    inner: Inner,
+}
+
+/// Sources of [`Error`].
+#[derive(Debug, thiserror::Error)]
+enum Inner {
+    /// Error of the [`FileStorage`] backend, also used by the other backends.
+    #[error(transparent)]
+    File(#[from] FileError),
+
+    /// The backend can't export or import keys.
+    #[error("the keystore does not support exporting or importing keys")]
+    Unsupported,
+}
+
+impl From<FileError> for Error {
+    fn from(err: FileError) -> Self {
+        Self {
+            inner: Inner::File(err),
+        }
+    }
 }
 
 impl Error {
+    /// Error for backends which don't support exporting or importing keys.
+    const fn unsupported() -> Self {
+        Self {
+            inner: Inner::Unsupported,
+        }
+    }
+
+    /// Error for an attempt to store a key while one exists already.
+    #[must_use]
+    pub fn key_exists() -> Self {
+        FileError::KeyExists.into()
+    }
+
     /// Returns `true` if the error indicates that an invalid passphrase was used to decrypt the
     /// secret key.
     #[must_use]
     pub const fn is_invalid_passphrase(&self) -> bool {
         #[allow(clippy::wildcard_enum_match_arm)]
-        matches!(self.inner, FileError::Crypto(SecretBoxError::InvalidKey))
+        matches!(
+            self.inner,
+            Inner::File(FileError::Crypto(SecretBoxError::InvalidKey))
+        )
     }
 
     /// Returns `true` if the error indicates that a key already exists in the store.
     #[must_use]
     pub const fn is_key_exists(&self) -> bool {
         #[allow(clippy::wildcard_enum_match_arm)]
-        matches!(self.inner, FileError::KeyExists)
+        matches!(self.inner, Inner::File(FileError::KeyExists))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use librad::paths;
+
+    use super::{file, Keystore as _, SecUtf8};
+
+    #[test]
+    fn change_passphrase() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let store = file(paths::Paths::from_root(tmp_dir.path())?);
+        let key = store.create_key(SecUtf8::from("old"))?;
+
+        let err = store
+            .change_passphrase(SecUtf8::from("wrong"), SecUtf8::from("new"))
+            .expect_err("changed the passphrase with a wrong one");
+        assert!(err.is_invalid_passphrase());
+
+        store.change_passphrase(SecUtf8::from("old"), SecUtf8::from("new"))?;
+
+        assert_eq!(store.get(SecUtf8::from("new"))?.public(), key.public());
+        assert!(store
+            .get(SecUtf8::from("old"))
+            .expect_err("unsealed with the old passphrase")
+            .is_invalid_passphrase());
+
+        Ok(())
     }
 }
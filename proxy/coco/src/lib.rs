@@ -65,9 +65,10 @@ pub mod signer;
 
 pub mod source;
 pub use source::{
-    blob, branches, commit, commit_header, commits, into_branch_type, local_state, revisions, tags,
-    tree, Blob, BlobContent, Branch, Commit, CommitHeader, Info, ObjectType, Person, Revision,
-    Revisions, Tag, Tree, TreeEntry,
+    blob, branches, commit, commit_branch, commit_header, commits, into_branch_type, local_state,
+    revision_oid, revisions, tags, tree, working_copy_status, Blob, BlobContent, Branch, Commit,
+    CommitHeader, Info, ObjectType, Person, Revision, Revisions, Tag, Tree, TreeEntry,
+    WorkingCopyStatus,
 };
 
 mod spawn_abortable;
@@ -82,7 +83,7 @@ pub mod user;
 /// * peer construction from config fails.
 /// * accept on the peer fails.
 pub async fn into_peer_state<D>(
-    config: net::peer::PeerConfig<D, keys::SecretKey>,
+    config: net::peer::PeerConfig<D, librad::signer::BoxedSigner>,
     signer: librad::signer::BoxedSigner,
     store: kv::Store,
     run_config: RunConfig,
@@ -2,6 +2,7 @@
 
 use std::{convert::TryFrom, fmt, str};
 
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use radicle_surf::vcs::git::git2;
@@ -66,3 +67,17 @@ impl Serialize for Oid {
         serializer.serialize_str(&self.0.to_string())
     }
 }
+
+impl JsonSchema for Oid {
+    fn schema_name() -> String {
+        "Oid".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+}
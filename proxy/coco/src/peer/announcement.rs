@@ -154,7 +154,7 @@ mod test {
         let tmp_dir = tempfile::tempdir().expect("failed to create temdir");
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::new(signer::SomeSigner { signer: key });
-        let config = config::default(key, tmp_dir.path())?;
+        let config = config::default(signer.clone(), tmp_dir.path())?;
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
 
@@ -5,7 +5,7 @@ use std::time::SystemTime;
 use either::Either;
 use tokio::sync::{mpsc, oneshot};
 
-use librad::uri::RadUrn;
+use librad::{peer::PeerId, uri::RadUrn};
 
 use crate::{request, request::waiting_room};
 
@@ -17,6 +17,10 @@ use super::run_state::Status;
 pub enum Request {
     /// Request the current peer status.
     CurrentStatus(oneshot::Sender<Status>),
+    /// Request the time of the last status change.
+    StatusSince(oneshot::Sender<SystemTime>),
+    /// Request the peers the local peer is currently connected to.
+    ConnectedPeers(oneshot::Sender<Vec<PeerId>>),
 
     /// Cancel an ongoing project search.
     CancelSearch(
@@ -44,6 +48,10 @@ pub enum Request {
 pub enum Response {
     /// Response to a status request.
     CurrentStatus(oneshot::Sender<Status>, Status),
+    /// Response to a status since request.
+    StatusSince(oneshot::Sender<SystemTime>, SystemTime),
+    /// Response to a connected peers request.
+    ConnectedPeers(oneshot::Sender<Vec<PeerId>>, Vec<PeerId>),
 
     /// Response to a cancel project search request.
     CancelSearch(
@@ -83,11 +91,43 @@ impl Control {
 
     /// Initiate a new request for the status.
     pub async fn current_status(&mut self) -> Status {
+        self.try_current_status().await.expect("peer is gone")
+    }
+
+    /// Initiate a new request for the status, `None` if the peer is gone.
+    pub async fn try_current_status(&mut self) -> Option<Status> {
         let (sender, receiver) = oneshot::channel::<Status>();
 
         self.sender
             .send(Request::CurrentStatus(sender))
             .await
+            .ok()?;
+
+        receiver.await.ok()
+    }
+
+    /// Initiate a new request for the time the current status was entered.
+    pub async fn status_since(&mut self) -> SystemTime {
+        self.try_status_since().await.expect("peer is gone")
+    }
+
+    /// Initiate a new request for the time the current status was entered, `None` if the peer is
+    /// gone.
+    pub async fn try_status_since(&mut self) -> Option<SystemTime> {
+        let (sender, receiver) = oneshot::channel::<SystemTime>();
+
+        self.sender.send(Request::StatusSince(sender)).await.ok()?;
+
+        receiver.await.ok()
+    }
+
+    /// Initiate a new request for the peers the local peer is connected to.
+    pub async fn connected_peers(&mut self) -> Vec<PeerId> {
+        let (sender, receiver) = oneshot::channel::<Vec<PeerId>>();
+
+        self.sender
+            .send(Request::ConnectedPeers(sender))
+            .await
             .expect("peer is gone");
 
         receiver.await.expect("receiver is gone")
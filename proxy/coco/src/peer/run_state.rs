@@ -5,7 +5,8 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 use librad::{
     net::{
@@ -52,10 +53,19 @@ pub enum Event {
     Protocol(ProtocolEvent<Gossip>),
     /// Sync with a peer completed.
     PeerSynced(PeerId),
+    /// Sync with a peer failed.
+    PeerSyncFailed(PeerId),
     /// Request fullfilled with a successful clone.
     RequestCloned(RadUrl),
     /// Request is being cloned from a peer.
     RequestCloning(RadUrl),
+    /// Cloning the requested project from a peer failed.
+    RequestFailed {
+        /// The URL the clone was attempted from.
+        url: RadUrl,
+        /// The reason the clone failed.
+        reason: String,
+    },
     /// Request for the URN was created and is pending submission to the network.
     RequestCreated(RadUrn),
     /// Request for the URN was submitted to the network.
@@ -86,9 +96,14 @@ impl MaybeFrom<&Input> for Event {
                 }),
             },
             Input::PeerSync(input::Sync::Succeeded(peer_id)) => Some(Self::PeerSynced(*peer_id)),
+            Input::PeerSync(input::Sync::Failed(peer_id)) => Some(Self::PeerSyncFailed(*peer_id)),
             Input::Protocol(protocol_event) => Some(Self::Protocol(protocol_event.clone())),
             Input::Request(input::Request::Cloned(url)) => Some(Self::RequestCloned(url.clone())),
             Input::Request(input::Request::Cloning(url)) => Some(Self::RequestCloning(url.clone())),
+            Input::Request(input::Request::Failed { url, reason }) => Some(Self::RequestFailed {
+                url: url.clone(),
+                reason: reason.clone(),
+            }),
             Input::Request(input::Request::Queried(urn)) => Some(Self::RequestQueried(urn.clone())),
             Input::Request(input::Request::Tick) => Some(Self::RequestTick),
             Input::Request(input::Request::TimedOut(urn)) => {
@@ -100,7 +115,8 @@ impl MaybeFrom<&Input> for Event {
 }
 
 /// The current status of the local peer and its relation to the network.
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[schemars(rename = "PeerStatus")]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum Status {
     /// Nothing is setup, not even a socket to listen on.
@@ -251,6 +267,19 @@ impl RunState {
             input::Control::Status(sender) => vec![Command::Control(command::Control::Respond(
                 control::Response::CurrentStatus(sender, self.status.clone()),
             ))],
+            input::Control::StatusSince(sender) => {
+                vec![Command::Control(command::Control::Respond(
+                    control::Response::StatusSince(sender, self.status_since),
+                ))]
+            },
+            input::Control::ConnectedPeers(sender) => {
+                vec![Command::Control(command::Control::Respond(
+                    control::Response::ConnectedPeers(
+                        sender,
+                        self.connected_peers.keys().copied().collect(),
+                    ),
+                ))]
+            },
         }
     }
 
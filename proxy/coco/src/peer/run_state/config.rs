@@ -2,6 +2,8 @@
 
 use std::time::Duration;
 
+use crate::request::{self, Clones, Queries};
+
 /// Default time to wait between announcement subroutine runs.
 pub(super) const DEFAULT_ANNOUNCE_INTERVAL: Duration = std::time::Duration::from_secs(1);
 
@@ -18,7 +20,7 @@ pub(super) const DEFAULT_SYNC_PERIOD: Duration = Duration::from_secs(5);
 pub(super) const DEFAULT_WAITING_ROOM_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Default period to consider until a query has timed out.
-pub(crate) const DEFAULT_WAITING_ROOM_TIMEOUT: Duration = Duration::from_secs(10);
+pub(super) const DEFAULT_WAITING_ROOM_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Set of knobs to change the behaviour of the `RunState`.
 #[derive(Default)]
@@ -71,12 +73,35 @@ pub struct WaitingRoom {
     /// Interval at which to query the [`crate::request::waiting_room::WaitingRoom`] for ready
     /// requests.
     pub interval: Duration,
+    /// Duration a request has to wait before its next query or clone attempt, and the base of
+    /// the time until it times out.
+    pub timeout: Duration,
+    /// Number of query attempts after which a request times out.
+    pub max_queries: Queries,
+    /// Number of clone attempts after which a request times out.
+    pub max_clones: Clones,
+}
+
+impl WaitingRoom {
+    /// The [`request::waiting_room::Config`] for the waiting room.
+    #[must_use]
+    pub const fn request_config(&self) -> request::waiting_room::Config<Duration> {
+        request::waiting_room::Config {
+            max_queries: self.max_queries,
+            max_clones: self.max_clones,
+            delta: self.timeout,
+        }
+    }
 }
 
 impl Default for WaitingRoom {
     fn default() -> Self {
+        let config = request::waiting_room::Config::<Duration>::default();
         Self {
             interval: DEFAULT_WAITING_ROOM_INTERVAL,
+            timeout: DEFAULT_WAITING_ROOM_TIMEOUT,
+            max_queries: config.max_queries,
+            max_clones: config.max_clones,
         }
     }
 }
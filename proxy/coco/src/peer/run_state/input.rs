@@ -52,6 +52,10 @@ pub enum Announce {
 pub enum Control {
     /// New status.
     Status(oneshot::Sender<super::Status>),
+    /// Request the time of the last status change.
+    StatusSince(oneshot::Sender<SystemTime>),
+    /// Request the connected peers.
+    ConnectedPeers(oneshot::Sender<Vec<PeerId>>),
 
     /// Cancel an ongoing project search.
     CancelRequest(
@@ -25,14 +25,14 @@ use librad::{
 
 use crate::{
     convert::MaybeFrom as _,
-    request::{self, waiting_room::WaitingRoom},
+    request::waiting_room::WaitingRoom,
     spawn_abortable::{self, SpawnAbortable},
     state::State,
 };
 
 use super::{
     announcement, control, gossip, include,
-    run_state::{command, config, input, Command, Config as RunConfig, Event, Input, RunState},
+    run_state::{command, input, Command, Config as RunConfig, Event, Input, RunState},
     sync, waiting_room, RECEIVER_CAPACITY,
 };
 
@@ -74,19 +74,17 @@ impl Subroutines {
         } else {
             Some(interval(run_config.announce.interval))
         };
+        let waiting_room_config = run_config.waiting_room.request_config();
         let waiting_room = match waiting_room::load(&store) {
             Err(err) => {
                 log::warn!("Failed to load waiting room: {}", err);
-                WaitingRoom::new(request::waiting_room::Config {
-                    delta: config::DEFAULT_WAITING_ROOM_TIMEOUT,
-                    ..request::waiting_room::Config::default()
-                })
+                WaitingRoom::new(waiting_room_config)
+            },
+            Ok(None) => WaitingRoom::new(waiting_room_config),
+            Ok(Some(mut room)) => {
+                room.set_config(waiting_room_config);
+                room
             },
-            Ok(None) => WaitingRoom::new(request::waiting_room::Config {
-                delta: config::DEFAULT_WAITING_ROOM_TIMEOUT,
-                ..request::waiting_room::Config::default()
-            }),
-            Ok(Some(room)) => room,
         };
         let waiting_room_timer = interval(run_config.waiting_room.interval);
         let (input_sender, inputs) = mpsc::channel::<Input>(RECEIVER_CAPACITY);
@@ -115,6 +113,12 @@ impl Subroutines {
                         control::Request::CurrentStatus(sender) => {
                             Input::Control(input::Control::Status(sender))
                         },
+                        control::Request::StatusSince(sender) => {
+                            Input::Control(input::Control::StatusSince(sender))
+                        },
+                        control::Request::ConnectedPeers(sender) => {
+                            Input::Control(input::Control::ConnectedPeers(sender))
+                        },
                         control::Request::CancelSearch(urn, time, sender) => {
                             Input::Control(input::Control::CancelRequest(urn, time, sender))
                         },
@@ -291,6 +295,8 @@ async fn announce(state: State, store: kv::Store, mut sender: mpsc::Sender<Input
 async fn control_respond(cmd: control::Response) {
     match cmd {
         control::Response::CurrentStatus(sender, status) => sender.send(status).ok(),
+        control::Response::StatusSince(sender, since) => sender.send(since).ok(),
+        control::Response::ConnectedPeers(sender, peers) => sender.send(peers).ok(),
         control::Response::CancelSearch(sender, request) => sender.send(request).ok(),
         control::Response::GetSearch(sender, request) => sender.send(request).ok(),
         control::Response::ListSearches(sender, requests) => sender.send(requests).ok(),
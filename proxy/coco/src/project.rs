@@ -13,6 +13,9 @@ pub use create::{Create, Repo};
 pub mod checkout;
 pub use checkout::Checkout;
 
+/// Module concerned with discovering existing repositories to create projects from in bulk.
+pub mod import;
+
 pub mod peer;
 pub use peer::Peer;
 
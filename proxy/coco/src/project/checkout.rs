@@ -12,13 +12,15 @@ use librad::{
         local::url::LocalUrl,
         types::{remote::Remote, FlatRef, Force},
     },
-    git_ext::{OneLevel, RefLike, RefspecPattern},
+    git_ext::{self, OneLevel, RefLike, RefspecPattern},
     peer::PeerId,
     uri::RadUrn,
 };
 use radicle_surf::vcs::git::git2;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-use crate::config;
+use crate::{config, oid::Oid};
 
 /// When checking out a working copy, we can run into several I/O failures.
 #[derive(Debug, thiserror::Error)]
@@ -27,9 +29,113 @@ pub enum Error {
     #[error(transparent)]
     Git(#[from] git2::Error),
 
+    /// Fetching a remote of an existing working copy failed.
+    #[error("failed to fetch the remote '{remote}' of the working copy")]
+    Fetch {
+        /// Name of the remote.
+        remote: String,
+        /// The underlying git error.
+        #[source]
+        source: git2::Error,
+    },
+
     /// An error occured building include files.
     #[error(transparent)]
     Include(#[from] include::Error),
+
+    /// The requested tag or commit could not be found after cloning.
+    #[error("the revision '{revision}' could not be found in the working copy")]
+    MissingRevision {
+        /// Name of the tag or SHA of the commit.
+        revision: String,
+        /// The underlying git error.
+        #[source]
+        source: git2::Error,
+    },
+}
+
+/// What a working copy should have checked out after it was cloned.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Target {
+    /// The branch under the name provided, set up to track the same branch on the `rad` remote.
+    #[serde(rename_all = "camelCase")]
+    Branch {
+        /// Name of the branch.
+        #[schemars(with = "String")]
+        name: OneLevel,
+    },
+    /// A detached `HEAD` at the tag under the name provided.
+    #[serde(rename_all = "camelCase")]
+    Tag {
+        /// Name of the tag.
+        #[schemars(with = "String")]
+        name: OneLevel,
+    },
+    /// A detached `HEAD` at the commit provided.
+    #[serde(rename_all = "camelCase")]
+    Commit {
+        /// The SHA1 value.
+        sha: Oid,
+    },
+}
+
+/// The result of [`Checkout::run`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// A fresh working copy was cloned to the path.
+    Cloned(PathBuf),
+    /// A working copy of the same project already existed and was brought up to date.
+    Updated(Update),
+}
+
+impl Outcome {
+    /// The location of the working copy.
+    #[must_use]
+    pub fn into_path(self) -> PathBuf {
+        match self {
+            Self::Cloned(path) => path,
+            Self::Updated(update) => update.path,
+        }
+    }
+}
+
+/// Report of what changed when updating an existing working copy.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Update {
+    /// The location of the working copy.
+    pub path: PathBuf,
+    /// Names of the remotes that were fetched from the monorepo.
+    pub fetched: Vec<String>,
+    /// Names of the remotes that were missing and have been created.
+    pub created: Vec<String>,
+    /// Short name of the checked out branch, `None` if `HEAD` is detached.
+    pub branch: Option<String>,
+    /// What happened to the checked out branch.
+    pub fast_forward: FastForward,
+}
+
+/// The outcome of trying to fast-forward the checked out branch to its upstream.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum FastForward {
+    /// The branch already points to the same commit as its upstream.
+    UpToDate,
+    /// The branch and the working tree were moved forward.
+    #[serde(rename_all = "camelCase")]
+    Forwarded {
+        /// The commit the branch pointed to before.
+        from: Oid,
+        /// The commit the branch points to now.
+        to: Oid,
+    },
+    /// The branch has commits its upstream does not have, it has to be merged manually.
+    Diverged,
+    /// Local changes in the working tree would be overwritten.
+    Conflict,
+    /// `HEAD` is detached or the branch has no upstream.
+    Skipped,
 }
 
 /// The data necessary for checking out a project.
@@ -43,6 +149,8 @@ where
     pub name: String,
     /// The default branch of the project.
     pub default_branch: OneLevel,
+    /// What to check out, the `default_branch` if `None`.
+    pub target: Option<Target>,
     /// The path on the filesystem where we're going to checkout to.
     pub path: P,
     /// Absolute path of the include file that will be set in the working copy config.
@@ -71,17 +179,17 @@ impl Ownership {
     ///
     /// # Errors
     ///   * If the cloning of the working copy fails.
-    ///   * In the case of a remote clone, if the pushing of the default branch fails.
+    ///   * In the case of a remote clone, if the pushing of `branch` fails.
     pub fn clone(
-        self,
+        &self,
         urn: RadUrn,
-        default_branch: &OneLevel,
+        branch: &OneLevel,
         path: &path::Path,
         builder: &mut git2::build::RepoBuilder,
     ) -> Result<git2::Repository, git2::Error> {
         match self {
             Self::Local(peer_id) => {
-                let url = LocalUrl::from_urn(urn, peer_id);
+                let url = LocalUrl::from_urn(urn, *peer_id);
                 Self::local(&url, path, builder)
             },
             Self::Remote {
@@ -89,10 +197,113 @@ impl Ownership {
                 remote,
                 local,
             } => {
-                let url = LocalUrl::from_urn(urn, local);
-                Self::remote(&handle, remote, url, default_branch, path, builder)
+                let url = LocalUrl::from_urn(urn, *local);
+                Self::remote(handle, *remote, url, branch, path, builder)
+            },
+        }
+    }
+
+    /// Our own `PeerId`.
+    #[must_use]
+    pub const fn local_peer_id(&self) -> PeerId {
+        match self {
+            Self::Local(peer_id) => *peer_id,
+            Self::Remote { local, .. } => *local,
+        }
+    }
+
+    /// Create the remote for the peer we're checking out from if the working copy is missing it.
+    /// Returns the name of the remote if it was created.
+    ///
+    /// # Errors
+    ///   * If looking up or creating the remote fails.
+    pub fn ensure_remote(
+        &self,
+        repo: &git2::Repository,
+        urn: RadUrn,
+    ) -> Result<Option<String>, git2::Error> {
+        match self {
+            Self::Local(_) => Ok(None),
+            Self::Remote {
+                handle,
+                remote,
+                local,
+            } => {
+                let name = RefLike::try_from(format!("{}@{}", handle, remote))
+                    .expect("failed to parse remote name");
+                match repo.find_remote(name.as_str()) {
+                    Ok(_) => Ok(None),
+                    Err(err) if git_ext::is_not_found_err(&err) => {
+                        let url = LocalUrl::from_urn(urn, *local);
+                        peer_remote(url, &name, *remote).create(repo)?;
+                        Ok(Some(name.as_str().to_string()))
+                    },
+                    Err(err) => Err(err),
+                }
+            },
+        }
+    }
+
+    /// Fetch the tag `name` into `refs/tags/<name>` of the working copy. Tags are not part of the
+    /// refspecs set up when cloning, so they have to be requested explicitly.
+    ///
+    /// # Errors
+    ///   * If the fetch from the remote fails.
+    pub fn fetch_tag(&self, repo: &git2::Repository, name: &OneLevel) -> Result<(), git2::Error> {
+        let (remote_name, refspec) = match self {
+            Self::Local(_) => (
+                config::RAD_REMOTE.to_string(),
+                format!("+refs/tags/{}:refs/tags/{}", name.as_str(), name.as_str()),
+            ),
+            Self::Remote { handle, remote, .. } => (
+                format!("{}@{}", handle, remote),
+                format!(
+                    "+refs/remotes/{}/tags/{}:refs/tags/{}",
+                    remote,
+                    name.as_str(),
+                    name.as_str()
+                ),
+            ),
+        };
+        repo.find_remote(&remote_name)?
+            .fetch(&[&refspec], None, None)
+    }
+
+    /// Find the local branch `name` of the working copy. If it is missing, it is created from the
+    /// branch of the peer we're checking out from and set up to track the `rad` remote, like
+    /// [`Checkout::run`] does when cloning.
+    ///
+    /// # Errors
+    ///   * If the branch is missing locally and the peer doesn't have it either.
+    ///   * In the case of a remote checkout, if the pushing of the branch fails.
+    pub fn ensure_branch<'repo>(
+        &self,
+        repo: &'repo git2::Repository,
+        name: &OneLevel,
+    ) -> Result<git2::Branch<'repo>, git2::Error> {
+        match repo.find_branch(name.as_str(), git2::BranchType::Local) {
+            Err(err) if git_ext::is_not_found_err(&err) => {},
+            result => return result,
+        }
+
+        let tracking = match self {
+            Self::Local(_) => format!("{}/{}", config::RAD_REMOTE, name.as_str()),
+            Self::Remote { handle, remote, .. } => {
+                format!("{}@{}/{}", handle, remote, name.as_str())
             },
+        };
+        let commit = repo
+            .find_branch(&tracking, git2::BranchType::Remote)?
+            .get()
+            .peel_to_commit()?;
+        repo.branch(name.as_str(), &commit, false)?;
+        if let Self::Remote { .. } = self {
+            repo.find_remote(config::RAD_REMOTE)?
+                .push(&[&format!("refs/heads/{}", name.as_str())], None)?;
         }
+        super::set_rad_upstream(repo, name)?;
+
+        repo.find_branch(name.as_str(), git2::BranchType::Local)
     }
 
     /// See [`Checkout::run`].
@@ -110,7 +321,7 @@ impl Ownership {
         handle: &str,
         peer: PeerId,
         url: LocalUrl,
-        default_branch: &OneLevel,
+        branch: &OneLevel,
         path: &path::Path,
         builder: &mut git2::build::RepoBuilder,
     ) -> Result<git2::Repository, git2::Error> {
@@ -118,23 +329,17 @@ impl Ownership {
             RefLike::try_from(format!("{}@{}", handle, peer)).expect("failed to parse remote name");
         {
             builder.remote_create(move |repo, _remote_name, url| {
-                let mut remote = Remote::new(url, name.as_str().to_string());
-                let heads: FlatRef<PeerId, _> = FlatRef::heads(PhantomData, peer).with_name(
-                    RefspecPattern::try_from("heads/*").expect("'heads/*' failed to parse"),
-                );
-                let remotes: FlatRef<RefLike, _> = FlatRef::heads(PhantomData, name.clone());
-                remote.fetch_spec = Some(remotes.refspec(heads, Force::True).boxed());
-                remote.create(repo)
+                peer_remote(url, &name, peer).create(repo)
             });
         }
 
         let repo = git2::build::RepoBuilder::clone(builder, &url.to_string(), path)?;
 
-        // Create a rad remote and push the default branch so we can set it as the
+        // Create a rad remote and push the checked out branch so we can set it as the
         // upstream.
         {
             let mut remote = Remote::rad_remote(url, None).create(&repo)?;
-            remote.push(&[&format!("refs/heads/{}", default_branch.as_str())], None)?;
+            remote.push(&[&format!("refs/heads/{}", branch.as_str())], None)?;
         }
 
         Ok(repo)
@@ -186,11 +391,27 @@ where
     ///     path = /home/user/.config/radicle/git-includes/hwd1yrerzpjbmtshsqw6ajokqtqrwaswty6p7kfeer3yt1n76t46iqggzcr.inc
     /// ```
     ///
+    /// ## Update
+    ///
+    /// If the path already holds a working copy of the same project, i.e. its `rad` remote points
+    /// to the `urn` for the user's `PeerId`, it is updated instead of cloned. The include path is
+    /// refreshed, a missing remote for the peer is created, all remotes are fetched and the
+    /// requested `target` is checked out. See [`Checkout::update`].
+    ///
+    /// ## Target
+    ///
+    /// Instead of `default_branch`, the checked out branch can be any other branch of the peer by
+    /// passing a [`Target::Branch`], in which case that branch is the one pushed to and tracking
+    /// the `rad` remote. For a [`Target::Tag`] or [`Target::Commit`] the `default_branch` is
+    /// cloned as usual and `HEAD` is detached at the requested revision afterwards.
+    ///
     /// # Errors
     ///  * If the project cloning fails.
     ///  * If we cannot set the upstream branch for the `rad` remote.
     ///  * If we cannot set the include path for the working copy.
-    pub fn run(self, ownership: Ownership) -> Result<PathBuf, Error> {
+    ///  * If the requested tag or commit is not present after cloning or fetching.
+    ///  * If the update of an existing working copy fails.
+    pub fn run(self, ownership: Ownership) -> Result<Outcome, Error> {
         // Check if the path provided ends in the 'directory_name' provided. If not we create the
         // full path to that name.
         let path = &self.path.as_ref();
@@ -207,15 +428,252 @@ where
                     }
                 });
 
+        if let Some(repo) = self.existing(&project_path, &ownership)? {
+            return Ok(Outcome::Updated(self.update(&repo, &ownership)?));
+        }
+
+        let branch = match &self.target {
+            Some(Target::Branch { name }) => name,
+            _ => &self.default_branch,
+        };
+
         // Clone the repository
         let mut builder = git2::build::RepoBuilder::new();
-        builder.branch(self.default_branch.as_str());
-        let repo = ownership.clone(self.urn, &self.default_branch, &project_path, &mut builder)?;
+        builder.branch(branch.as_str());
+        let repo = ownership.clone(self.urn, branch, &project_path, &mut builder)?;
 
         // Set configurations
-        super::set_rad_upstream(&repo, &self.default_branch)?;
+        super::set_rad_upstream(&repo, branch)?;
         include::set_include_path(&repo, self.include_path)?;
 
-        Ok(project_path)
+        if let Some(commit) = revision(&repo, &ownership, self.target.as_ref())? {
+            detach(&repo, &commit)?;
+        }
+
+        Ok(Outcome::Cloned(project_path))
+    }
+
+    /// Bring an existing working copy of the project up to date.
+    ///
+    /// The include path is set again, the remote for the peer is created if it is missing and
+    /// every remote is fetched. Then the `target` is checked out, unless local changes conflict
+    /// with it. Without a `target` the branch checked out already is kept. If a branch with an
+    /// upstream is checked out, it is fast-forwarded when it has no commits of its own and local
+    /// changes don't conflict with the incoming ones.
+    ///
+    /// # Errors
+    ///  * If we cannot set the include path for the working copy.
+    ///  * If creating or fetching the remotes fails.
+    ///  * If the requested branch, tag or commit can't be found.
+    ///  * If checking out the `target` or the fast-forward fails for any other reason than
+    ///    conflicting local changes.
+    pub fn update(self, repo: &git2::Repository, ownership: &Ownership) -> Result<Update, Error> {
+        log::debug!("Updating working copy @ '{}'", repo.path().display());
+        include::set_include_path(repo, self.include_path)?;
+
+        let created = ownership
+            .ensure_remote(repo, self.urn)?
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let mut fetched = vec![];
+        for name in repo.remotes()?.iter().flatten() {
+            repo.find_remote(name)?
+                .fetch(&[] as &[&str], None, None)
+                .map_err(|source| Error::Fetch {
+                    remote: name.to_string(),
+                    source,
+                })?;
+            fetched.push(name.to_string());
+        }
+
+        let switched = match &self.target {
+            Some(Target::Branch { name }) => {
+                let branch = ownership.ensure_branch(repo, name)?;
+                let commit = branch.get().peel_to_commit()?;
+                let reference = branch.into_reference();
+                let refname = reference.name().unwrap_or_default();
+                switch(repo, &commit, |repo| repo.set_head(refname))?
+            },
+            target => match revision(repo, ownership, target.as_ref())? {
+                Some(commit) => switch(repo, &commit, |repo| repo.set_head_detached(commit.id()))?,
+                None => true,
+            },
+        };
+        let (branch, fast_forward) = if switched {
+            fast_forward(repo)?
+        } else {
+            (head_branch(repo)?, FastForward::Conflict)
+        };
+
+        Ok(Update {
+            // Collecting the components drops the trailing separator of the workdir.
+            path: repo
+                .workdir()
+                .unwrap_or_else(|| repo.path())
+                .components()
+                .collect(),
+            fetched,
+            created,
+            branch,
+            fast_forward,
+        })
     }
+
+    /// Open the working copy at `path` if it belongs to the project we're checking out, i.e. its
+    /// `rad` remote points to the `urn` for our `PeerId`.
+    fn existing(
+        &self,
+        path: &path::Path,
+        ownership: &Ownership,
+    ) -> Result<Option<git2::Repository>, git2::Error> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let repo = match git2::Repository::open(path) {
+            Ok(repo) => repo,
+            Err(err) if git_ext::is_not_found_err(&err) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let url = LocalUrl::from_urn(self.urn.clone(), ownership.local_peer_id()).to_string();
+        let same_project = match repo.find_remote(config::RAD_REMOTE) {
+            Ok(remote) => remote.url() == Some(url.as_str()),
+            Err(err) if git_ext::is_not_found_err(&err) => false,
+            Err(err) => return Err(err),
+        };
+
+        Ok(if same_project { Some(repo) } else { None })
+    }
+}
+
+/// Build the remote for `peer` named `name`, fetching the peer's heads from their namespace in
+/// the monorepo.
+fn peer_remote<Url>(url: Url, name: &RefLike, peer: PeerId) -> Remote<Url> {
+    let mut remote = Remote::new(url, name.as_str().to_string());
+    let heads: FlatRef<PeerId, _> = FlatRef::heads(PhantomData, peer)
+        .with_name(RefspecPattern::try_from("heads/*").expect("'heads/*' failed to parse"));
+    let remotes: FlatRef<RefLike, _> = FlatRef::heads(PhantomData, name.clone());
+    remote.fetch_spec = Some(remotes.refspec(heads, Force::True).boxed());
+    remote
+}
+
+/// The commit a [`Target::Tag`] or [`Target::Commit`] points to, fetching the tag first. `None`
+/// for any other target.
+fn revision<'repo>(
+    repo: &'repo git2::Repository,
+    ownership: &Ownership,
+    target: Option<&Target>,
+) -> Result<Option<git2::Commit<'repo>>, Error> {
+    match target {
+        Some(Target::Tag { name }) => {
+            ownership.fetch_tag(repo, name)?;
+            let tag = format!("refs/tags/{}", name.as_str());
+            let commit = repo
+                .find_reference(&tag)
+                .and_then(|reference| reference.peel_to_commit())
+                .map_err(|source| Error::MissingRevision {
+                    revision: name.as_str().to_string(),
+                    source,
+                })?;
+            Ok(Some(commit))
+        },
+        Some(Target::Commit { sha }) => {
+            let commit =
+                repo.find_commit((*sha).into())
+                    .map_err(|source| Error::MissingRevision {
+                        revision: sha.to_string(),
+                        source,
+                    })?;
+            Ok(Some(commit))
+        },
+        Some(Target::Branch { .. }) | None => Ok(None),
+    }
+}
+
+/// Check out the tree of `commit` and move `HEAD` with `set_head`. Returns `false` without
+/// touching `HEAD` if local changes in the working tree would be overwritten.
+fn switch<F>(repo: &git2::Repository, commit: &git2::Commit, set_head: F) -> Result<bool, Error>
+where
+    F: FnOnce(&git2::Repository) -> Result<(), git2::Error>,
+{
+    match repo.checkout_tree(
+        commit.as_object(),
+        Some(git2::build::CheckoutBuilder::new().safe()),
+    ) {
+        Ok(()) => {},
+        Err(err) if err.code() == git2::ErrorCode::Conflict => return Ok(false),
+        Err(err) => return Err(err.into()),
+    }
+    set_head(repo)?;
+
+    Ok(true)
+}
+
+/// Short name of the checked out branch, `None` if `HEAD` is detached or unborn.
+fn head_branch(repo: &git2::Repository) -> Result<Option<String>, git2::Error> {
+    match repo.head() {
+        Ok(head) if head.is_branch() => Ok(head.shorthand().map(ToString::to_string)),
+        Ok(_) => Ok(None),
+        Err(err) if git_ext::is_not_found_err(&err) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Fast-forward the checked out branch to its upstream. See [`Checkout::update`].
+fn fast_forward(repo: &git2::Repository) -> Result<(Option<String>, FastForward), git2::Error> {
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => head,
+        Ok(_) => return Ok((None, FastForward::Skipped)),
+        Err(err) if git_ext::is_not_found_err(&err) => return Ok((None, FastForward::Skipped)),
+        Err(err) => return Err(err),
+    };
+    let name = head.shorthand().map(ToString::to_string);
+    let branch = git2::Branch::wrap(head);
+    let upstream = match branch.upstream() {
+        Ok(upstream) => upstream.get().peel_to_commit()?,
+        Err(err) if git_ext::is_not_found_err(&err) => return Ok((name, FastForward::Skipped)),
+        Err(err) => return Err(err),
+    };
+    let local = branch.get().peel_to_commit()?;
+
+    let annotated = repo.find_annotated_commit(upstream.id())?;
+    let (analysis, _preference) = repo.merge_analysis(&[&annotated])?;
+    if analysis.is_up_to_date() {
+        return Ok((name, FastForward::UpToDate));
+    }
+    if !analysis.is_fast_forward() {
+        return Ok((name, FastForward::Diverged));
+    }
+
+    match repo.checkout_tree(
+        upstream.as_object(),
+        Some(git2::build::CheckoutBuilder::new().safe()),
+    ) {
+        Ok(()) => {},
+        Err(err) if err.code() == git2::ErrorCode::Conflict => {
+            return Ok((name, FastForward::Conflict))
+        },
+        Err(err) => return Err(err),
+    }
+    branch
+        .into_reference()
+        .set_target(upstream.id(), "rad: fast-forward")?;
+
+    Ok((
+        name,
+        FastForward::Forwarded {
+            from: local.id().into(),
+            to: upstream.id().into(),
+        },
+    ))
+}
+
+/// Check out `commit` in the working copy and point a detached `HEAD` at it.
+fn detach(repo: &git2::Repository, commit: &git2::Commit) -> Result<(), git2::Error> {
+    repo.checkout_tree(
+        commit.as_object(),
+        Some(git2::build::CheckoutBuilder::new().safe()),
+    )?;
+    repo.set_head_detached(commit.id())
 }
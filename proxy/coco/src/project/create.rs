@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use librad::{
@@ -31,7 +32,7 @@ pub enum Error {
 }
 
 /// The data required to either open an existing repository or create a new one.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum Repo {
     /// Open an existing repository.
@@ -67,7 +68,8 @@ impl Repo {
     }
 
     /// Get the full path of the `Repo` creation data.
-    fn full_path(&self) -> PathBuf {
+    #[must_use]
+    pub fn full_path(&self) -> PathBuf {
         match self {
             Self::Existing { path } => path.to_path_buf(),
             Self::New { name, path } => path.join(name),
@@ -76,12 +78,13 @@ impl Repo {
 }
 
 /// The data required for creating a new project.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Create {
     /// Description of the project we want to create.
     pub description: String,
     /// The default branch name for the project.
+    #[schemars(with = "String")]
     pub default_branch: OneLevel,
     /// What kind of working copy we're working with, i.e. new or existing.
     pub repo: Repo,
@@ -98,6 +101,14 @@ impl Create {
         validation::Repository::validate(self.repo, url, self.default_branch)
     }
 
+    /// Collect every problem [`Create::validate`] could run into, without side effects.
+    ///
+    /// See [`validation::Repository::check`]
+    #[must_use]
+    pub fn check(&self, url: &LocalUrl) -> Vec<validation::Error> {
+        validation::Repository::check(&self.repo, url, &self.default_branch)
+    }
+
     /// Build a [`project::Project`], where the provided [`User`] is the owner, and the set of
     /// keys starts with the provided [`keys::PublicKey`].
     ///
@@ -204,4 +215,30 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn check_collects_all_problems() -> Result<(), Box<dyn std::error::Error>> {
+        let peer_id = PeerId::from(SecretKey::new());
+        let url = LocalUrl::from_urn(
+            RadUrn::new(Hash::hash(b"geez"), uri::Protocol::Git, uri::Path::empty()),
+            peer_id,
+        );
+        let tmpdir = tempfile::tempdir().expect("failed to create tmp dir");
+        let path = tmpdir.path().join("existing");
+        let repo = git2::Repository::init(&path)?;
+        repo.remote(crate::config::RAD_REMOTE, "rad://elsewhere.git")?;
+
+        let create = Create {
+            description: "Radicle".to_string(),
+            default_branch: OneLevel::from(reflike!("radicle")),
+            repo: Repo::Existing { path },
+        };
+        let problems = create.check(&url);
+
+        assert_eq!(problems.len(), 2);
+        assert_matches!(problems[0], validation::Error::MissingDefaultBranch { .. });
+        assert_matches!(problems[1], validation::Error::UrlMismatch { .. });
+
+        Ok(())
+    }
 }
@@ -1,7 +1,11 @@
 //! Validation logic for safely checking that a [`super::Repo`] is valid before setting up the
 //! working copy.
 
-use std::{convert::TryFrom, io, path::PathBuf};
+use std::{
+    convert::TryFrom,
+    io,
+    path::{Path, PathBuf},
+};
 
 use librad::{
     git::{local::url::LocalUrl, types::remote::Remote},
@@ -74,6 +78,14 @@ pub enum Error {
     #[error("the path provided '{0}' does not exist when it was expected to")]
     PathDoesNotExist(PathBuf),
 
+    /// A `rad` remote was found in a repository no project was created from yet. It can't match
+    /// the URL of the project, which is only known once it is created.
+    #[error("the repository already has a `rad` remote")]
+    RadRemoteExists {
+        /// The URL of the existing `rad` remote, if it has one.
+        url: Option<String>,
+    },
+
     /// The `rad` remote was found, but the URL did not match the URL we were expecting.
     #[error("the `rad` remote was found but the url field does not match the provided url, found: '{found}' expected: '{expected}'")]
     UrlMismatch {
@@ -175,7 +187,7 @@ impl Repository {
 
                 {
                     let _default_branch_ref = Self::existing_branch(&repo, &default_branch)?;
-                    let _remote = Self::existing_remote(&repo, &url)?;
+                    let _remote = Self::existing_remote(&repo, Some(&url))?;
                 }
                 Ok(Self::Existing {
                     repo,
@@ -210,6 +222,92 @@ impl Repository {
         }
     }
 
+    /// Run every check of [`Repository::validate`] without short-circuiting and without touching
+    /// the working copy, collecting all problems found along the way.
+    ///
+    /// Checks that depend on an earlier one, e.g. looking for the default branch of a path that is
+    /// not a repository, are skipped when the earlier check fails. An empty result means
+    /// [`Repository::validate`] would succeed for the same input.
+    #[must_use]
+    pub fn check(repo: &super::Repo, url: &LocalUrl, default_branch: &OneLevel) -> Vec<Error> {
+        Self::check_repo(repo, Some(url), default_branch)
+    }
+
+    /// Run the checks of [`Repository::check`] for the existing repository at `path`, which no
+    /// project was created from yet. As the URL of the project isn't known, any `rad` remote is
+    /// reported as [`Error::RadRemoteExists`].
+    #[must_use]
+    pub fn check_import(path: &Path, default_branch: &OneLevel) -> Vec<Error> {
+        let repo = super::Repo::Existing {
+            path: path.to_path_buf(),
+        };
+        Self::check_repo(&repo, None, default_branch)
+    }
+
+    /// Implementation of [`Repository::check`], see [`Repository::existing_remote`] for the
+    /// meaning of `url`.
+    fn check_repo(
+        repo: &super::Repo,
+        url: Option<&LocalUrl>,
+        default_branch: &OneLevel,
+    ) -> Vec<Error> {
+        let mut problems = vec![];
+
+        match repo {
+            super::Repo::Existing { path } => {
+                if !path.exists() {
+                    problems.push(Error::PathDoesNotExist(path.to_path_buf()));
+                    return problems;
+                }
+
+                if let Err(err) = repo.project_name() {
+                    problems.push(err);
+                }
+
+                let repo = match git2::Repository::open(path) {
+                    Ok(repo) => repo,
+                    Err(err) if git_ext::is_not_found_err(&err) => {
+                        problems.push(Error::NotARepo(path.to_path_buf()));
+                        return problems;
+                    },
+                    Err(err) => {
+                        problems.push(err.into());
+                        return problems;
+                    },
+                };
+
+                if let Err(err) = Self::existing_branch(&repo, default_branch) {
+                    problems.push(err);
+                }
+                if let Err(err) = Self::existing_remote(&repo, url) {
+                    problems.push(err);
+                }
+            },
+            super::Repo::New { name, path } => {
+                let repo_path = path.join(name);
+
+                if repo_path.is_file() {
+                    problems.push(Error::AlreadExists(repo_path));
+                } else if repo_path.is_dir() {
+                    match repo_path.read_dir() {
+                        Ok(mut entries) => {
+                            if entries.next().is_some() {
+                                problems.push(Error::AlreadExists(repo_path));
+                            }
+                        },
+                        Err(err) => problems.push(err.into()),
+                    }
+                }
+
+                if let Err(err) = Self::existing_author() {
+                    problems.push(err);
+                }
+            },
+        }
+
+        problems
+    }
+
     /// Initialise the [`git2::Repository`].
     ///
     /// # Errors
@@ -305,7 +403,7 @@ impl Repository {
         let _default_branch_ref = Self::existing_branch(repo, default_branch)?;
 
         log::debug!("Creating rad remote");
-        let mut git_remote = Self::existing_remote(repo, &url)?
+        let mut git_remote = Self::existing_remote(repo, Some(&url))?
             .map_or_else(|| Remote::rad_remote(url, None).create(repo), Ok)?;
         Self::push_branches(repo, &mut git_remote)?;
         Ok(())
@@ -340,14 +438,19 @@ impl Repository {
             })
     }
 
+    /// Find the `rad` remote of `repo` and check that it points to `url`. Without a `url`, i.e.
+    /// for a project which is yet to be created, any `rad` remote is an error.
     fn existing_remote<'a>(
         repo: &'a git2::Repository,
-        url: &LocalUrl,
+        url: Option<&LocalUrl>,
     ) -> Result<Option<git2::Remote<'a>>, Error> {
-        match repo.find_remote(config::RAD_REMOTE) {
-            Err(err) if git_ext::is_not_found_err(&err) => Ok(None),
-            Err(err) => Err(err.into()),
-            Ok(remote) => match remote.url() {
+        match (repo.find_remote(config::RAD_REMOTE), url) {
+            (Err(err), _) if git_ext::is_not_found_err(&err) => Ok(None),
+            (Err(err), _) => Err(err.into()),
+            (Ok(remote), None) => Err(Error::RadRemoteExists {
+                url: remote.url().map(ToString::to_string),
+            }),
+            (Ok(remote), Some(url)) => match remote.url() {
                 None => Err(Error::MissingUrl),
                 Some(remote_url) if remote_url != url.to_string() => Err(Error::UrlMismatch {
                     expected: url.to_string(),
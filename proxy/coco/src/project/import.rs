@@ -0,0 +1,237 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use librad::git_ext::OneLevel;
+use radicle_surf::vcs::git::git2;
+
+use super::create::validation;
+
+/// Upper bound of directory levels descended into when scanning for repositories.
+pub const MAX_DEPTH: usize = 3;
+
+/// Errors that occur when scanning a directory for repositories.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// An error occurred in `git2` while inspecting a repository.
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    /// When trying to read a directory, an I/O error occurred.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The path to scan is not a directory.
+    #[error("the path provided '{0}' is not a directory")]
+    NotADirectory(PathBuf),
+
+    /// A repository failed a validation check which doesn't map to a [`Status`].
+    #[error(transparent)]
+    Validation(#[from] validation::Error),
+}
+
+/// What [`super::Create::validate`] would make of a repository found during a [`scan`].
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[schemars(rename = "CandidateStatus")]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Status {
+    /// The repository passes validation and a project can be created from it.
+    Ready,
+    /// The requested default branch does not exist in the repository.
+    #[serde(rename_all = "camelCase")]
+    MissingDefaultBranch {
+        /// The default branch that was expected to be found.
+        branch: String,
+    },
+    /// The repository already has a `rad` remote, which conflicts with the remote set up for a
+    /// newly created project.
+    #[serde(rename_all = "camelCase")]
+    RadRemoteExists {
+        /// The URL of the existing `rad` remote, if it has one.
+        url: Option<String>,
+    },
+}
+
+/// A repository found during a [`scan`] together with the outcome of its inspection.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    /// Location of the working copy.
+    pub path: PathBuf,
+    /// The name the project would be created with, i.e. the final component of `path`.
+    pub name: String,
+    /// Short name of the currently checked out branch, if `HEAD` points to one.
+    pub head: Option<String>,
+    /// Sorted list of local branches.
+    pub branches: Vec<String>,
+    /// Outcome of the validation checks.
+    pub status: Status,
+}
+
+/// Walk the directory tree under `root` and [`inspect`] every non-bare git repository found,
+/// checking for `default_branch`.
+///
+/// Hidden directories are skipped and the walk neither descends into repositories nor goes deeper
+/// than [`MAX_DEPTH`] levels below `root`. Directories below `root` and repositories which can't
+/// be read are logged and skipped. The result is sorted by path.
+///
+/// # Errors
+///
+///   * `root` is not a directory.
+///   * Reading `root` fails.
+pub fn scan(root: &Path, default_branch: &OneLevel) -> Result<Vec<Candidate>, Error> {
+    if !root.is_dir() {
+        return Err(Error::NotADirectory(root.to_path_buf()));
+    }
+
+    let mut candidates = vec![];
+    walk(root.read_dir()?, default_branch, 0, &mut candidates);
+    candidates.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(candidates)
+}
+
+/// Inspect the repository at `path` and determine if a project could be created from it, based on
+/// [`validation::Repository::check_import`].
+///
+/// # Errors
+///
+///   * `path` does not point to a git repository.
+///   * Reading the branches or remotes of the repository fails.
+pub fn inspect(path: &Path, default_branch: &OneLevel) -> Result<Candidate, Error> {
+    let mut status = Status::Ready;
+    for problem in validation::Repository::check_import(path, default_branch) {
+        match problem {
+            // A `rad` remote takes precedence, as the repository can't be imported even with the
+            // right default branch.
+            validation::Error::RadRemoteExists { url } => {
+                status = Status::RadRemoteExists { url };
+            },
+            validation::Error::MissingDefaultBranch { branch, .. } => {
+                if status == Status::Ready {
+                    status = Status::MissingDefaultBranch { branch };
+                }
+            },
+            err => return Err(err.into()),
+        }
+    }
+
+    let repo = git2::Repository::open(path)?;
+
+    let head = repo
+        .head()
+        .ok()
+        .filter(git2::Reference::is_branch)
+        .and_then(|head| head.shorthand().map(ToString::to_string));
+    let mut branches = repo
+        .branches(Some(git2::BranchType::Local))?
+        .filter_map(|branch_result| {
+            let (branch, _) = branch_result.ok()?;
+            let name = branch.name().ok()?;
+            name.map(String::from)
+        })
+        .collect::<Vec<_>>();
+    branches.sort();
+
+    Ok(Candidate {
+        path: path.to_path_buf(),
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        head,
+        branches,
+        status,
+    })
+}
+
+/// Recursive step of [`scan`] over the `entries` of a directory.
+fn walk(
+    entries: fs::ReadDir,
+    default_branch: &OneLevel,
+    depth: usize,
+    candidates: &mut Vec<Candidate>,
+) {
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => {
+                log::warn!("Skipping directory entry: {}", err);
+                continue;
+            },
+        };
+        let hidden = path
+            .file_name()
+            .map_or(true, |name| name.to_string_lossy().starts_with('.'));
+        if hidden || !path.is_dir() {
+            continue;
+        }
+
+        match git2::Repository::open(&path) {
+            Ok(repo) if repo.is_bare() => continue,
+            Ok(_repo) => match inspect(&path, default_branch) {
+                Ok(candidate) => candidates.push(candidate),
+                Err(err) => log::warn!("Skipping repository @ '{}': {}", path.display(), err),
+            },
+            Err(_not_a_repo) => {
+                if depth < MAX_DEPTH {
+                    match path.read_dir() {
+                        Ok(entries) => walk(entries, default_branch, depth + 1, candidates),
+                        Err(err) => {
+                            log::warn!("Skipping directory @ '{}': {}", path.display(), err)
+                        },
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use librad::{git_ext::OneLevel, reflike};
+    use radicle_surf::vcs::git::git2;
+
+    use crate::control;
+
+    use super::{scan, Status};
+
+    #[test]
+    fn scan_reports_nested_repositories() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let platinum = tmp_dir.path().join("work").join("git-platinum");
+        control::clone_platinum(platinum.clone())?;
+        std::fs::create_dir_all(tmp_dir.path().join("empty"))?;
+        std::fs::create_dir_all(tmp_dir.path().join(".hidden"))?;
+
+        let remote = tmp_dir.path().join("remote");
+        let repo = git2::Repository::init(&remote)?;
+        repo.remote(crate::config::RAD_REMOTE, "rad://existing.git")?;
+
+        let candidates = scan(tmp_dir.path(), &OneLevel::from(reflike!("master")))?;
+
+        assert_eq!(
+            candidates
+                .iter()
+                .map(|candidate| (candidate.name.as_str(), candidate.status.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "remote",
+                    Status::RadRemoteExists {
+                        url: Some("rad://existing.git".to_string())
+                    }
+                ),
+                ("git-platinum", Status::Ready),
+            ]
+        );
+
+        Ok(())
+    }
+}
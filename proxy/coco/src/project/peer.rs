@@ -2,12 +2,13 @@
 //!
 //! A [`Peer`] can be `Local` or `Remote`, it can be `NotReplicated` or `Replicated`, and it
 //! can be a `Tracker`, `Contributor`, or `Maintainer` of the project.
+use schemars::JsonSchema;
 use serde::Serialize;
 
 use librad::peer::PeerId;
 
 /// Relation of the peer to the project.
-#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum Role {
     /// Replicating, but not participating.
@@ -28,13 +29,14 @@ pub enum Role {
 /// [`Replicated`]. When `S` is `Status` it means that the `Peer` could be in one of two states:
 /// `NotReplicated` or `Replicated`. When `S` is `Replicated` it means the peer is definitely
 /// `Replicated` on the local peer's machine.
-#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum Peer<S> {
     /// Represents the local peer.
     #[serde(rename_all = "camelCase")]
     Local {
         /// [`PeerId`] of the peer.
+        #[schemars(with = "String")]
         peer_id: PeerId,
         /// State of replication.
         status: S,
@@ -43,6 +45,7 @@ pub enum Peer<S> {
     #[serde(rename_all = "camelCase")]
     Remote {
         /// [`PeerId`] of the peer.
+        #[schemars(with = "String")]
         peer_id: PeerId,
         /// State of replication.
         status: S,
@@ -134,7 +137,7 @@ impl<S> Peer<S> {
 
 /// If data has been replicated locally we should be able to determine the [`Role`] the peer had
 /// with this project as well as their user metadata.
-#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Replicated<U> {
     /// The role this peer has with the project.
@@ -168,7 +171,7 @@ impl<U> Replicated<U> {
 ///
 /// If it is `Replicated`, it means the data has been replicated and we have the associated role
 /// and user metadata for this peer.
-#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum Status<U> {
     /// No data has been replicated locally (yet).
@@ -198,3 +201,147 @@ impl<U> Status<U> {
         }
     }
 }
+
+/// A user together with the devices, i.e. peers, they use. One person running the app on several
+/// machines is represented by a single user whose keys include the ones of every device.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Devices<U> {
+    /// The user metadata shared by all devices.
+    pub user: U,
+    /// The [`PeerId`]s of the devices, in the order they were found.
+    #[schemars(with = "Vec<String>")]
+    pub peer_ids: Vec<PeerId>,
+}
+
+#[allow(clippy::use_self)]
+impl<U> Devices<Status<U>> {
+    /// Refine the `Devices` into the ones of a replicated user.
+    ///
+    /// This will return `Some` if the `Status` was `Replicated`, and `None` otherwise.
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn replicated(self) -> Option<Devices<U>> {
+        match self.user {
+            Status::Replicated(Replicated { user, .. }) => Some(Devices {
+                user,
+                peer_ids: self.peer_ids,
+            }),
+            Status::NotReplicated => None,
+        }
+    }
+
+    /// Turn the `Devices` back into one `Remote` [`Peer`] per device, each with the status of the
+    /// user.
+    pub fn remotes(self) -> impl Iterator<Item = Peer<Status<U>>>
+    where
+        U: Clone,
+    {
+        let status = self.user;
+        self.peer_ids.into_iter().map(move |peer_id| Peer::Remote {
+            peer_id,
+            status: status.clone(),
+        })
+    }
+}
+
+/// Group the remote `peers` by their user, where two users are the same if `key` returns the same
+/// value for them. The [`Role`] of a user is the highest one of their devices, e.g. a user who
+/// contributed from one device is a contributor on all of them. Peers that are not replicated yet
+/// can't be attributed to a user, so each of them is kept on its own.
+pub fn group_by_user<U, K, F, I>(peers: I, key: F) -> Vec<Devices<Status<U>>>
+where
+    I: IntoIterator<Item = Peer<Status<U>>>,
+    F: Fn(&U) -> K,
+    K: PartialEq,
+{
+    let mut grouped: Vec<Devices<Status<U>>> = vec![];
+    for peer in peers {
+        let (peer_id, status) = match peer {
+            Peer::Remote { peer_id, status } => (peer_id, status),
+            Peer::Local { .. } => continue,
+        };
+        let Replicated { role, user } = match status {
+            Status::Replicated(replicated) => replicated,
+            Status::NotReplicated => {
+                grouped.push(Devices {
+                    user: Status::NotReplicated,
+                    peer_ids: vec![peer_id],
+                });
+                continue;
+            },
+        };
+        let existing = grouped
+            .iter_mut()
+            .find_map(|devices| match &mut devices.user {
+                Status::Replicated(replicated) if key(&replicated.user) == key(&user) => {
+                    Some((replicated, &mut devices.peer_ids))
+                },
+                Status::Replicated(_) | Status::NotReplicated => None,
+            });
+        match existing {
+            Some((replicated, peer_ids)) => {
+                replicated.role = replicated.role.max(role);
+                peer_ids.push(peer_id);
+            },
+            None => grouped.push(Devices {
+                user: Status::replicated(role, user),
+                peer_ids: vec![peer_id],
+            }),
+        }
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use librad::{keys::SecretKey, peer::PeerId};
+
+    use super::{group_by_user, Devices, Peer, Role, Status};
+
+    #[test]
+    fn group_by_user_merges_devices() {
+        let laptop = PeerId::from(SecretKey::new());
+        let desktop = PeerId::from(SecretKey::new());
+        let other = PeerId::from(SecretKey::new());
+        let pending = PeerId::from(SecretKey::new());
+
+        let peers = vec![
+            Peer::Remote {
+                peer_id: laptop,
+                status: Status::replicated(Role::Tracker, "cloudhead"),
+            },
+            Peer::Remote {
+                peer_id: other,
+                status: Status::replicated(Role::Tracker, "rudolfs"),
+            },
+            Peer::Remote {
+                peer_id: pending,
+                status: Status::NotReplicated,
+            },
+            Peer::Remote {
+                peer_id: desktop,
+                status: Status::replicated(Role::Contributor, "cloudhead"),
+            },
+        ];
+
+        assert_eq!(
+            group_by_user(peers, |user| *user),
+            vec![
+                Devices {
+                    user: Status::replicated(Role::Contributor, "cloudhead"),
+                    peer_ids: vec![laptop, desktop],
+                },
+                Devices {
+                    user: Status::replicated(Role::Tracker, "rudolfs"),
+                    peer_ids: vec![other],
+                },
+                Devices {
+                    user: Status::NotReplicated,
+                    peer_ids: vec![pending],
+                },
+            ]
+        );
+    }
+}
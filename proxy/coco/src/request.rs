@@ -10,6 +10,7 @@
 use std::{collections::HashMap, ops::Deref};
 
 use either::Either;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use librad::{
@@ -55,14 +56,16 @@ mod sealed;
 ///
 /// The `T` type parameter represents some timestamp that is chosen by the user of the `Request`
 /// API. Note that it makes it easy to test by just choosing `()` for the timestamp.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Request<S, T> {
     /// The identifier of the identity on the network.
+    #[schemars(with = "String")]
     urn: RadUrn,
     /// The number of attempts this request has made to complete its job.
     attempts: Attempts,
     /// The timestamp of the latest action to be taken on this request.
+    #[schemars(with = "u64")]
     #[serde(with = "serde_millis", bound = "T: serde_millis::Milliseconds")]
     timestamp: T,
     /// The state of the request, as mentioned above.
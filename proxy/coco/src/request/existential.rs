@@ -5,6 +5,7 @@
 // much.
 #![allow(clippy::wildcard_enum_match_arm)]
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::{
@@ -22,7 +23,7 @@ use super::{
 ///
 /// When we pattern match we get back the request parameterised over the specific state and can
 /// work in a type safe manner with this request.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(
     bound = "T: serde_millis::Milliseconds",
     rename_all = "camelCase",
@@ -6,6 +6,7 @@ use std::{
     ops::{Add, AddAssign, Deref},
 };
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use librad::{peer::PeerId, uri::RadUrl};
@@ -53,12 +54,12 @@ impl fmt::Display for RequestState {
 }
 
 /// The initial state for a `Request`. It has simply been created.
-#[derive(Clone, Debug, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Created {}
 
 /// The state signifying that the `Request` has been kicked-off.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Requested {
     /// A set of found peers and the lifecycle of clone attempts made on those peers.
@@ -70,7 +71,10 @@ pub struct Requested {
 ///
 /// Note that the related data isn't in the `Status` variants. The status is free to be associated
 /// with any external data, e.g. using it as a value in a `HashMap`.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[schemars(rename = "PeerRequestStatus")]
 #[serde(rename_all = "camelCase")]
 pub enum Status {
     /// The status of the related data is: ready to go!
@@ -101,7 +105,7 @@ impl Status {
 }
 
 /// The `Found` state means that we have found at least one peer and can possibly find more.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Found {
     /// A set of found peers and the lifecycle of clone attempts made on those peers.
@@ -118,7 +122,7 @@ impl Deref for Found {
 
 /// The `Cloning` state means that we have found at least one peer and we are attempting a clone on
 /// one of them.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Cloning {
     /// A set of found peers and the lifecycle of clone attempts made on those peers.
@@ -134,19 +138,20 @@ impl Deref for Cloning {
 }
 
 /// The `Cloned` state means that we have successfully cloned the desired identity.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Cloned {
     /// The identity that we were attempting to find and the peer that we found it from.
+    #[schemars(with = "String")]
     pub(crate) url: RadUrl,
 }
 /// One of the terminal states for a `Request` where the task has been cancelled.
-#[derive(Clone, Debug, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Cancelled {}
 
 /// One of the terminal states for a `Request` where the task made too many attempts.
-#[derive(Clone, Debug, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum TimedOut {
     /// The `Request` made too many query attempts.
@@ -165,7 +170,9 @@ impl fmt::Display for TimedOut {
 }
 
 /// `Queries` is a wrapper around `usize` so that we can differentiate it from [`Clones`].
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub enum Queries {
     /// The max number of queries allowed per request.
     Max(usize),
@@ -211,7 +218,9 @@ impl AddAssign<usize> for Queries {
 }
 
 /// `Clones` is a wrapper around `usize` so that we can differentiate it from [`Queries`].
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub enum Clones {
     /// The max number of clones allowed per request.
     Max(usize),
@@ -257,7 +266,7 @@ impl AddAssign<usize> for Clones {
 }
 
 /// The number of different attempts a `Request` has made during its lifetime.
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Attempts {
     /// The number of query attempts we have made.
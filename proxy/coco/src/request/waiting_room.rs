@@ -132,6 +132,12 @@ impl<T, D> WaitingRoom<T, D> {
         }
     }
 
+    /// Replace the `config` of the `WaitingRoom`, e.g. when it was restored with the `Config` it
+    /// was persisted with. The new limits apply to all following transitions of its requests.
+    pub fn set_config(&mut self, config: Config<D>) {
+        self.config = config;
+    }
+
     /// Check that the `WaitingRoom` has the given `urn`.
     pub fn has(&self, urn: &RadUrn) -> bool {
         self.requests.contains_key(&urn.id)
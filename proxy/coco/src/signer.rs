@@ -1,3 +1,5 @@
 //! Utility for signing.
 
 pub use librad::signer::{BoxedSigner, SomeSigner};
+
+pub mod agent;
@@ -0,0 +1,401 @@
+//! Signing through an external agent listening on a Unix domain socket, similar to ssh-agent. The
+//! secret key stays with the agent and never enters the memory of the process using [`Agent`].
+//!
+//! Every request is sent over a new connection, which the agent answers with exactly one message.
+//! A message is framed as a 4 byte big-endian length, followed by the message type and the
+//! payload, where the length covers both type and payload:
+//!
+//! | type | message                       | payload                        |
+//! |------|-------------------------------|--------------------------------|
+//! | 1    | [`REQUEST_PUBLIC_KEY`] request | empty                          |
+//! | 2    | [`PUBLIC_KEY`] response        | 32 byte ed25519 public key     |
+//! | 3    | [`SIGN`] request               | the data to sign               |
+//! | 4    | [`SIGNATURE`] response         | 64 byte ed25519 signature      |
+//! | 5    | [`FAILURE`] response           | UTF-8 encoded reason           |
+//! | 6    | [`UNLOCK`] request             | UTF-8 encoded passphrase       |
+//! | 7    | [`LOCK`] request               | empty                          |
+//! | 8    | [`SUCCESS`] response           | empty                          |
+//!
+//! The agent starts locked and only answers [`REQUEST_PUBLIC_KEY`] and [`SIGN`] requests after it
+//! was unlocked with the passphrase of its key, until it is locked again. The socket is only
+//! accessible by the user running the agent, see [`bind`], and connections of other users are
+//! rejected.
+
+use std::{
+    convert::TryFrom as _,
+    fs, io,
+    os::unix::fs::{MetadataExt as _, PermissionsExt as _},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use radicle_keystore::sign;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{UnixListener, UnixStream},
+    sync::Mutex,
+};
+
+use crate::keystore::SecUtf8;
+
+/// Request for the public key of the agent.
+pub const REQUEST_PUBLIC_KEY: u8 = 1;
+/// Response carrying the public key of the agent.
+pub const PUBLIC_KEY: u8 = 2;
+/// Request to sign the payload.
+pub const SIGN: u8 = 3;
+/// Response carrying the signature of the payload of a [`SIGN`] request.
+pub const SIGNATURE: u8 = 4;
+/// Response indicating that the request failed.
+pub const FAILURE: u8 = 5;
+/// Request to unlock the key of the agent with the passphrase in the payload.
+pub const UNLOCK: u8 = 6;
+/// Request to lock the key of the agent, so it can't be used until it is unlocked again.
+pub const LOCK: u8 = 7;
+/// Response indicating that an [`UNLOCK`] or [`LOCK`] request succeeded.
+pub const SUCCESS: u8 = 8;
+
+/// Upper bound for the length of a message, to not allocate arbitrary amounts of memory for
+/// malformed messages.
+const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+
+/// Errors that occur when talking to a signing agent.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The agent reported that it could not fulfil the request.
+    #[error("the signing agent failed: {0}")]
+    Agent(String),
+
+    /// Reading from or writing to the socket failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A message exceeded the maximum length or was empty.
+    #[error("invalid message length {0}")]
+    InvalidLength(usize),
+
+    /// The payload of a message did not have the expected size.
+    #[error("invalid payload of {len} bytes for message type {kind}")]
+    InvalidPayload {
+        /// The type of the message.
+        kind: u8,
+        /// The size of the payload.
+        len: usize,
+    },
+
+    /// The agent answered with a message of an unexpected type.
+    #[error("unexpected message type {0}")]
+    UnexpectedMessage(u8),
+
+    /// A process of another user connected to the agent.
+    #[error("rejected connection of user {0}")]
+    ForeignUser(u32),
+}
+
+/// Socket of an agent, bound by [`bind`].
+pub struct Listener {
+    /// The bound socket.
+    listener: UnixListener,
+    /// The user owning the socket, the only one allowed to connect.
+    uid: u32,
+}
+
+/// Signer which delegates to an agent listening on a Unix domain socket.
+#[derive(Clone, Debug)]
+pub struct Agent {
+    /// Location of the socket the agent listens on.
+    socket: PathBuf,
+    /// The public key of the agent, obtained on [`Agent::connect`].
+    public_key: sign::PublicKey,
+}
+
+impl Agent {
+    /// Connect to the agent listening on `socket` and obtain its public key.
+    ///
+    /// # Errors
+    ///
+    ///   * The agent can't be reached.
+    ///   * The agent responds with a failure or a malformed public key.
+    pub async fn connect(socket: impl Into<PathBuf>) -> Result<Self, Error> {
+        let socket = socket.into();
+        let payload = request(&socket, REQUEST_PUBLIC_KEY, &[], PUBLIC_KEY).await?;
+        let mut public_key = [0; 32];
+        if payload.len() != public_key.len() {
+            return Err(Error::InvalidPayload {
+                kind: PUBLIC_KEY,
+                len: payload.len(),
+            });
+        }
+        public_key.copy_from_slice(&payload);
+
+        Ok(Self {
+            socket,
+            public_key: sign::PublicKey(public_key),
+        })
+    }
+
+    /// Unlock the key of the agent listening on `socket` with `passphrase`.
+    ///
+    /// # Errors
+    ///
+    ///   * The agent can't be reached.
+    ///   * The agent can't unlock its key with `passphrase`.
+    pub async fn unlock(socket: &Path, passphrase: SecUtf8) -> Result<(), Error> {
+        request(socket, UNLOCK, passphrase.unsecure().as_bytes(), SUCCESS).await?;
+        Ok(())
+    }
+
+    /// Lock the key of the agent listening on `socket`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the agent can't be reached.
+    pub async fn lock(socket: &Path) -> Result<(), Error> {
+        request(socket, LOCK, &[], SUCCESS).await?;
+        Ok(())
+    }
+
+    /// Location of the socket the agent listens on.
+    #[must_use]
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+}
+
+#[async_trait::async_trait]
+impl sign::Signer for Agent {
+    type Error = Error;
+
+    fn public_key(&self) -> sign::PublicKey {
+        self.public_key
+    }
+
+    async fn sign(&self, data: &[u8]) -> Result<sign::Signature, Self::Error> {
+        let payload = request(&self.socket, SIGN, data, SIGNATURE).await?;
+        let mut signature = [0; 64];
+        if payload.len() != signature.len() {
+            return Err(Error::InvalidPayload {
+                kind: SIGNATURE,
+                len: payload.len(),
+            });
+        }
+        signature.copy_from_slice(&payload);
+
+        Ok(sign::Signature(signature))
+    }
+}
+
+/// Bind the socket of an agent at `path`, only accessible by the current user.
+///
+/// The socket is created in a fresh directory only accessible by the current user and moved to
+/// `path` after its permissions are restricted, so other users can't connect in between.
+///
+/// # Errors
+///
+/// Errors if the socket can't be created or moved to `path`.
+pub fn bind(path: &Path) -> Result<Listener, Error> {
+    let parent = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let private_dir = tempfile::Builder::new()
+        .prefix(".radicle-signer")
+        .tempdir_in(parent)?;
+    fs::set_permissions(private_dir.path(), fs::Permissions::from_mode(0o700))?;
+
+    let private_path = private_dir.path().join("agent.sock");
+    let listener = UnixListener::bind(&private_path)?;
+    fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))?;
+    let uid = fs::metadata(&private_path)?.uid();
+    fs::rename(&private_path, path)?;
+
+    Ok(Listener { listener, uid })
+}
+
+/// Answer requests arriving on `listener` until accepting a connection fails. The agent starts
+/// locked, an [`UNLOCK`] request obtains the signer by calling `unlock` with the passphrase.
+///
+/// # Errors
+///
+/// Errors if accepting a connection fails. Failures while handling a single request are only
+/// logged.
+pub async fn serve<S, F>(listener: Listener, unlock: F) -> Result<(), Error>
+where
+    S: sign::Signer + Clone + Send + Sync + 'static,
+    F: Fn(SecUtf8) -> Result<S, String> + Send + Sync + 'static,
+{
+    let Listener { mut listener, uid } = listener;
+    let unlock = Arc::new(unlock);
+    let signer = Arc::new(Mutex::new(None));
+
+    loop {
+        let (stream, _addr) = listener.accept().await?;
+        let unlock = unlock.clone();
+        let signer = signer.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, uid, unlock, &signer).await {
+                log::warn!("Failed to handle signing request: {}", err);
+            }
+        });
+    }
+}
+
+/// Answer a single request arriving on `stream`, if it comes from the user `uid`.
+async fn handle<S, F>(
+    mut stream: UnixStream,
+    uid: u32,
+    unlock: Arc<F>,
+    signer: &Mutex<Option<S>>,
+) -> Result<(), Error>
+where
+    S: sign::Signer + Clone + Send + Sync + 'static,
+    F: Fn(SecUtf8) -> Result<S, String> + Send + Sync + 'static,
+{
+    let peer_uid = stream.peer_cred()?.uid;
+    if peer_uid != uid {
+        return Err(Error::ForeignUser(peer_uid));
+    }
+
+    let (kind, payload) = read_message(&mut stream).await?;
+    match kind {
+        UNLOCK => {
+            let passphrase = match String::from_utf8(payload) {
+                Ok(passphrase) => SecUtf8::from(passphrase),
+                Err(_err) => {
+                    return write_message(&mut stream, FAILURE, b"passphrase is not UTF-8").await
+                },
+            };
+            // Holding the lock while the passphrase is checked serializes unlock attempts.
+            let mut signer = signer.lock().await;
+            match tokio::task::spawn_blocking(move || unlock(passphrase))
+                .await
+                .expect("Task to unlock key was aborted")
+            {
+                Ok(unlocked) => {
+                    *signer = Some(unlocked);
+                    write_message(&mut stream, SUCCESS, &[]).await
+                },
+                Err(reason) => write_message(&mut stream, FAILURE, reason.as_bytes()).await,
+            }
+        },
+        LOCK => {
+            *signer.lock().await = None;
+            write_message(&mut stream, SUCCESS, &[]).await
+        },
+        REQUEST_PUBLIC_KEY | SIGN => {
+            let signer = match signer.lock().await.clone() {
+                Some(signer) => signer,
+                None => return write_message(&mut stream, FAILURE, b"agent is locked").await,
+            };
+            if kind == REQUEST_PUBLIC_KEY {
+                return write_message(&mut stream, PUBLIC_KEY, &signer.public_key().0).await;
+            }
+            match signer.sign(&payload).await {
+                Ok(signature) => write_message(&mut stream, SIGNATURE, &signature.0).await,
+                Err(err) => write_message(&mut stream, FAILURE, err.to_string().as_bytes()).await,
+            }
+        },
+        _ => {
+            let reason = format!("unknown message type {}", kind);
+            write_message(&mut stream, FAILURE, reason.as_bytes()).await
+        },
+    }
+}
+
+/// Send a message of type `kind` to the agent on `socket` and return the payload of the response,
+/// which is expected to be of type `expected`.
+async fn request(socket: &Path, kind: u8, payload: &[u8], expected: u8) -> Result<Vec<u8>, Error> {
+    let mut stream = UnixStream::connect(socket).await?;
+    write_message(&mut stream, kind, payload).await?;
+
+    match read_message(&mut stream).await? {
+        (kind, payload) if kind == expected => Ok(payload),
+        (FAILURE, reason) => Err(Error::Agent(String::from_utf8_lossy(&reason).to_string())),
+        (kind, _payload) => Err(Error::UnexpectedMessage(kind)),
+    }
+}
+
+/// Read a single framed message from `stream`.
+async fn read_message(stream: &mut UnixStream) -> Result<(u8, Vec<u8>), Error> {
+    let len = usize::try_from(stream.read_u32().await?)
+        .map_err(|_overflow| Error::InvalidLength(usize::MAX))?;
+    if len == 0 || len > MAX_MESSAGE_LEN {
+        return Err(Error::InvalidLength(len));
+    }
+
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).await?;
+    let kind = payload.remove(0);
+
+    Ok((kind, payload))
+}
+
+/// Write a single framed message to `stream`.
+async fn write_message(stream: &mut UnixStream, kind: u8, payload: &[u8]) -> Result<(), Error> {
+    let len = payload.len() + 1;
+    if len > MAX_MESSAGE_LEN {
+        return Err(Error::InvalidLength(len));
+    }
+
+    stream
+        .write_u32(u32::try_from(len).map_err(|_overflow| Error::InvalidLength(len))?)
+        .await?;
+    stream.write_u8(kind).await?;
+    stream.write_all(payload).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    use pretty_assertions::assert_eq;
+    use radicle_keystore::sign::{self, Signer as _};
+
+    use librad::keys::SecretKey;
+
+    use super::{bind, serve, Agent, Error};
+    use crate::keystore::SecUtf8;
+
+    #[tokio::test]
+    async fn sign_through_agent() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let socket = tmp_dir.path().join("agent.sock");
+        let key = SecretKey::new();
+        let listener = bind(&socket)?;
+        assert_eq!(
+            std::fs::metadata(&socket)?.permissions().mode() & 0o777,
+            0o600
+        );
+        tokio::spawn(serve(listener, move |passphrase: SecUtf8| {
+            if passphrase.unsecure() == "asdf" {
+                Ok(key)
+            } else {
+                Err("wrong passphrase".to_string())
+            }
+        }));
+
+        assert!(matches!(
+            Agent::connect(&socket).await,
+            Err(Error::Agent(reason)) if reason == "agent is locked"
+        ));
+        assert!(Agent::unlock(&socket, SecUtf8::from("nope")).await.is_err());
+        Agent::unlock(&socket, SecUtf8::from("asdf")).await?;
+
+        let agent = Agent::connect(&socket).await?;
+        assert_eq!(agent.public_key().0, sign::Signer::public_key(&key).0);
+
+        let data = b"the key never leaves the agent";
+        assert_eq!(
+            agent.sign(data).await?.0.to_vec(),
+            sign::Signer::sign(&key, data).await?.0.to_vec()
+        );
+
+        Agent::lock(&socket).await?;
+        assert!(agent.sign(data).await.is_err());
+
+        Ok(())
+    }
+}
@@ -3,12 +3,21 @@
 use std::{convert::TryFrom, fmt, path, str::FromStr};
 
 use nonempty::NonEmpty;
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Schema, SchemaObject},
+    JsonSchema,
+};
 use serde::{ser::SerializeStruct as _, Deserialize, Serialize, Serializer};
 use syntect::{
     easy::HighlightLines, highlighting::ThemeSet, parsing::SyntaxSet, util::LinesWithEndings,
 };
 
-use librad::peer::PeerId;
+use librad::{
+    git::local::url::LocalUrl,
+    peer::PeerId,
+    uri::{self, RadUrn},
+};
 use radicle_surf::{
     diff, file_system,
     vcs::git::{self, git2, BranchType, Browser, Rev, Stats},
@@ -41,6 +50,10 @@ pub enum Error {
     #[error("The repository has no branches")]
     NoBranches,
 
+    /// The commit was not found in the repository.
+    #[error("the commit '{0}' was not found")]
+    CommitNotFound(Oid),
+
     /// Trying to find a file path which could not be found.
     #[error("the path '{0}' was not found")]
     PathNotFound(file_system::Path),
@@ -79,7 +92,7 @@ lazy_static::lazy_static! {
 }
 
 /// Branch name representation.
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize, Serialize, JsonSchema)]
 pub struct Branch(pub(crate) String);
 
 impl From<String> for Branch {
@@ -97,7 +110,7 @@ impl fmt::Display for Branch {
 /// Tag name representation.
 ///
 /// We still need full tag support.
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, JsonSchema)]
 pub struct Tag(pub(crate) String);
 
 impl From<String> for Tag {
@@ -123,6 +136,7 @@ impl Serialize for Tag {
 
 /// Representation of a person (e.g. committer, author, signer) from a repository. Usually
 /// extracted from a signature.
+#[derive(JsonSchema)]
 pub struct Person {
     /// Name part of the commit signature.
     pub name: String,
@@ -143,7 +157,7 @@ impl Serialize for Person {
 }
 
 /// Commit statistics.
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 pub struct CommitStats {
     /// Additions.
     pub additions: u64,
@@ -152,12 +166,14 @@ pub struct CommitStats {
 }
 
 /// Representation of a changeset between two revs.
+#[derive(JsonSchema)]
 pub struct Commit {
     /// The commit header.
     pub header: CommitHeader,
     /// The change statistics for this commit.
     pub stats: CommitStats,
     /// The changeset introduced by this commit.
+    #[schemars(with = "DiffSchema")]
     pub diff: diff::Diff,
     /// The branch this commit belongs to.
     pub branch: Branch,
@@ -178,6 +194,8 @@ impl Serialize for Commit {
 }
 
 /// Representation of a code commit.
+#[derive(JsonSchema)]
+#[schemars(rename_all = "camelCase")]
 pub struct CommitHeader {
     /// Identifier of the commit in the form of a sha1 hash. Often referred to as oid or object
     /// id.
@@ -186,12 +204,14 @@ pub struct CommitHeader {
     pub author: Person,
     /// The summary of the commit message body.
     pub summary: String,
-    /// The entire commit message body.
+    /// The entire commit message body. Only the [`Self::description`] is serialized.
+    #[schemars(rename = "description")]
     pub message: String,
     /// The committer of the commit.
     pub committer: Person,
     /// The recorded time of the committer signature. This is a convenience alias until we
     /// expose the actual author and commiter signatures.
+    #[schemars(with = "i64")]
     pub committer_time: git2::Time,
 }
 
@@ -242,18 +262,49 @@ impl Serialize for CommitHeader {
 }
 
 /// A selection of commit headers and their statistics.
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 pub struct Commits {
     /// The commit headers
     pub headers: Vec<CommitHeader>,
     /// The statistics for the commit headers
+    #[schemars(with = "StatsSchema")]
     pub stats: Stats,
 }
 
+/// Schema of [`Stats`], which is defined by `radicle_surf`.
+#[derive(JsonSchema)]
+#[schemars(rename = "Stats")]
+pub struct StatsSchema {
+    /// Number of branches.
+    pub branches: usize,
+    /// Number of commits.
+    pub commits: usize,
+    /// Number of contributors.
+    pub contributors: usize,
+}
+
+/// Schema of [`diff::Diff`], which is defined by `radicle_surf` and only described as an object.
+pub struct DiffSchema;
+
+impl JsonSchema for DiffSchema {
+    fn schema_name() -> String {
+        "Diff".to_string()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(InstanceType::Object.into()),
+            ..SchemaObject::default()
+        }
+        .into()
+    }
+}
+
 /// Git object types.
 ///
 /// `shafiul.github.io/gitbook/1_the_git_object_model.html`
-#[derive(Debug, Eq, Ord, PartialOrd, PartialEq)]
+#[derive(Debug, Eq, Ord, PartialOrd, PartialEq, JsonSchema)]
+#[schemars(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ObjectType {
     /// References a list of other trees and blobs.
     Tree,
@@ -274,6 +325,8 @@ impl Serialize for ObjectType {
 }
 
 /// Set of extra information we carry for blob and tree objects returned from the API.
+#[derive(JsonSchema)]
+#[schemars(rename_all = "camelCase")]
 pub struct Info {
     /// Name part of an object.
     pub name: String,
@@ -335,6 +388,32 @@ impl Serialize for Blob {
     }
 }
 
+impl JsonSchema for Blob {
+    fn schema_name() -> String {
+        "Blob".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        /// The fields of a [`Blob`] as it is serialized.
+        #[allow(dead_code)]
+        #[derive(JsonSchema)]
+        struct Blob {
+            /// Indicates if the content of the blob is binary.
+            binary: bool,
+            /// Indicates if the content of the blob is syntax-highlighted HTML.
+            html: bool,
+            /// Actual content of the file, `None` if it is binary.
+            content: Option<String>,
+            /// Extra info for the file.
+            info: Info,
+            /// Absolute path to the object from the root of the repo.
+            path: String,
+        }
+
+        Blob::json_schema(gen)
+    }
+}
+
 /// Variants of blob content.
 #[derive(PartialEq)]
 pub enum BlobContent {
@@ -359,6 +438,7 @@ impl Serialize for BlobContent {
 }
 
 /// Result of a directory listing, carries other trees and blobs.
+#[derive(JsonSchema)]
 pub struct Tree {
     /// Absolute path to the tree object from the repo root.
     pub path: String,
@@ -383,6 +463,7 @@ impl Serialize for Tree {
 
 // TODO(xla): Ensure correct by construction.
 /// Entry in a Tree result.
+#[derive(JsonSchema)]
 pub struct TreeEntry {
     /// Extra info for the entry.
     pub info: Info,
@@ -568,46 +649,161 @@ pub fn branches(
 }
 
 /// Information about a locally checked out repository.
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct LocalState {
     /// List of branches.
     branches: Vec<Branch>,
+    /// List of remotes, sorted by name.
+    remotes: Vec<Remote>,
+    /// The checked out branch and the state of the working tree.
+    #[serde(flatten)]
+    status: WorkingCopyStatus,
 }
 
-/// Given a path to a repo returns the list of branches and if it is managed by coco.
+/// A remote configured in a locally checked out repository.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Remote {
+    /// Name of the remote.
+    pub name: String,
+    /// The URL of the remote, if it has one.
+    pub url: Option<String>,
+    /// If this is the `rad` remote, which is set up when creating or checking out a project.
+    pub rad: bool,
+    /// The project the remote points to, if its URL is a URL to a project in the monorepo.
+    #[schemars(with = "Option<String>")]
+    pub urn: Option<RadUrn>,
+}
+
+/// Given a path to a repo returns the list of branches and remotes, the checked out branch, if
+/// there are uncommitted changes and how the checked out branch relates to the `rad` remote.
 ///
 /// # Errors
 ///
-/// Will return [`Error`] if the repository doesn't exist.
+/// Will return [`Error`] if the repository doesn't exist or has no branches.
 pub fn local_state(repo_path: &str) -> Result<LocalState, Error> {
     let repo = git2::Repository::open(repo_path).map_err(git::error::Error::from)?;
-    let first_branch = repo
+    let mut branches = repo
         .branches(Some(git2::BranchType::Local))
         .map_err(git::error::Error::from)?
         .filter_map(|branch_result| {
             let (branch, _) = branch_result.ok()?;
             let name = branch.name().ok()?;
-            name.map(String::from)
+            name.map(|name| Branch(name.to_string()))
         })
-        .min()
-        .ok_or(Error::NoBranches)?;
+        .collect::<Vec<Branch>>();
+    if branches.is_empty() {
+        return Err(Error::NoBranches);
+    }
+    branches.sort();
 
-    let repo = git::Repository::new(repo_path)?;
+    let mut remotes = repo
+        .remotes()
+        .map_err(git::error::Error::from)?
+        .iter()
+        .flatten()
+        .filter_map(|name| {
+            let remote = repo.find_remote(name).ok()?;
+            let url = remote.url().map(ToString::to_string);
+            let urn = url
+                .as_ref()
+                .and_then(|url| url.parse::<LocalUrl>().ok())
+                .map(|url| RadUrn::new(url.repo, uri::Protocol::Git, uri::Path::empty()));
+            Some(Remote {
+                name: name.to_string(),
+                url,
+                rad: name == crate::config::RAD_REMOTE,
+                urn,
+            })
+        })
+        .collect::<Vec<_>>();
+    remotes.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let browser = match Browser::new(&repo, git::Branch::local("master")) {
-        Ok(browser) => browser,
-        Err(_) => Browser::new(&repo, git::Branch::local(&first_branch))?,
-    };
+    let status = working_copy_status(path::Path::new(repo_path))?;
 
-    let mut branches = browser
-        .list_branches(Some(BranchType::Local))?
-        .into_iter()
-        .map(|b| Branch(b.name.name().to_string()))
-        .collect::<Vec<Branch>>();
+    Ok(LocalState {
+        branches,
+        remotes,
+        status,
+    })
+}
 
-    branches.sort();
+/// Uncommitted changes and divergence from the `rad` remote of a working copy.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkingCopyStatus {
+    /// Short name of the checked out branch, `None` if `HEAD` is detached or unborn.
+    pub head: Option<String>,
+    /// If there are uncommitted changes to tracked files or untracked files.
+    pub dirty: bool,
+    /// How the checked out branch relates to the branch of the same name on the `rad` remote,
+    /// `None` if there is no such branch.
+    pub divergence: Option<Divergence>,
+}
+
+/// Number of commits two branches have that the other one doesn't.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Divergence {
+    /// Commits only the local branch has.
+    pub ahead: usize,
+    /// Commits only the remote branch has.
+    pub behind: usize,
+}
+
+/// Given a path to a working copy returns if it has uncommitted changes and how far its checked
+/// out branch is ahead of or behind the `rad` remote.
+///
+/// # Errors
+///
+/// Will return [`Error`] if the repository doesn't exist or reading its state fails.
+pub fn working_copy_status(repo_path: &path::Path) -> Result<WorkingCopyStatus, Error> {
+    let repo = git2::Repository::open(repo_path).map_err(git::error::Error::from)?;
+    let dirty = {
+        let mut options = git2::StatusOptions::new();
+        options.include_untracked(true).include_ignored(false);
+        !repo
+            .statuses(Some(&mut options))
+            .map_err(git::error::Error::from)?
+            .is_empty()
+    };
+
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => head,
+        _ => {
+            return Ok(WorkingCopyStatus {
+                head: None,
+                dirty,
+                divergence: None,
+            })
+        },
+    };
+    let name = head.shorthand().map(ToString::to_string);
+    let divergence = match (&name, head.target()) {
+        (Some(name), Some(local)) => {
+            match repo.refname_to_id(&format!(
+                "refs/remotes/{}/{}",
+                crate::config::RAD_REMOTE,
+                name
+            )) {
+                Ok(upstream) => {
+                    let (ahead, behind) = repo
+                        .graph_ahead_behind(local, upstream)
+                        .map_err(git::error::Error::from)?;
+                    Some(Divergence { ahead, behind })
+                },
+                Err(_) => None,
+            }
+        },
+        _ => None,
+    };
 
-    Ok(LocalState { branches })
+    Ok(WorkingCopyStatus {
+        head: name,
+        dirty,
+        divergence,
+    })
 }
 
 /// Retrieves the [`CommitHeader`] for the given `sha1`.
@@ -658,6 +854,25 @@ pub fn commit(browser: &mut Browser<'_>, sha1: Oid) -> Result<Commit, Error> {
         }
     }
 
+    let branch = commit_branch(browser, sha1)?;
+
+    Ok(Commit {
+        header: CommitHeader::from(commit),
+        stats: CommitStats {
+            additions,
+            deletions,
+        },
+        branch,
+        diff,
+    })
+}
+
+/// Retrieves the [`Branch`] the commit `sha1` is shown on, see [`Commit::branch`].
+///
+/// # Errors
+///
+/// Will return [`Error`] if the project doesn't exist or the surf interaction fails.
+pub fn commit_branch(browser: &Browser<'_>, sha1: Oid) -> Result<Branch, Error> {
     let oid: git2::Oid = sha1.into();
     let branches = browser.revision_branches(oid)?;
 
@@ -667,22 +882,12 @@ pub fn commit(browser: &mut Browser<'_>, sha1: Oid) -> Result<Commit, Error> {
     let branch = branches.first();
 
     // Known commits always have at least one branch. If this isn't the case, it's a bug.
-    let branch = Branch(
+    Ok(Branch(
         branch
             .expect("known commits must be on a branch")
             .name
             .to_string(),
-    );
-
-    Ok(Commit {
-        header: CommitHeader::from(commit),
-        stats: CommitStats {
-            additions,
-            deletions,
-        },
-        branch,
-        diff,
-    })
+    ))
 }
 
 /// Retrieves the [`Commit`] history for the given `revision`.
@@ -709,6 +914,28 @@ where
     Ok(Commits { headers, stats })
 }
 
+/// Resolves `revision` to the [`Oid`] of the commit it points to, or the head of the browser if
+/// no revision is given.
+///
+/// # Errors
+///
+/// Will return [`Error`] if the revision can't be resolved.
+pub fn revision_oid<P>(
+    browser: &mut Browser<'_>,
+    maybe_revision: Option<Revision<P>>,
+) -> Result<Oid, Error>
+where
+    P: ToString,
+{
+    let maybe_revision = maybe_revision.map(Rev::try_from).transpose()?;
+
+    if let Some(revision) = maybe_revision {
+        browser.rev(revision)?;
+    }
+
+    Ok(Oid::from(browser.get().first().id))
+}
+
 /// Retrieves the list of [`Tag`] for the given project `id`.
 ///
 /// # Errors
@@ -934,7 +1161,8 @@ mod tests {
         let tmp_dir = tempfile::tempdir().expect("failed to get tempdir");
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::new(signer::SomeSigner { signer: key });
-        let config = config::default(key, tmp_dir.path()).expect("unable to get default config");
+        let config =
+            config::default(signer.clone(), tmp_dir.path()).expect("unable to get default config");
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
         let owner = state.init_owner("cloudhead").await?;
@@ -961,4 +1189,64 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn local_state_without_master() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let repo = super::git2::Repository::init(tmp_dir.path())?;
+        let signature = super::git2::Signature::now("cloudhead", "cloudhead@radicle.xyz")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let first = repo.commit(
+            Some("refs/heads/main"),
+            &signature,
+            &signature,
+            "Initial commit",
+            &tree,
+            &[],
+        )?;
+        repo.reference("refs/remotes/rad/main", first, false, "test")?;
+        repo.commit(
+            Some("refs/heads/main"),
+            &signature,
+            &signature,
+            "Second commit",
+            &tree,
+            &[&repo.find_commit(first)?],
+        )?;
+        repo.set_head("refs/heads/main")?;
+        std::fs::write(tmp_dir.path().join("untracked.txt"), "untracked")?;
+
+        let urn = super::RadUrn::new(
+            librad::hash::Hash::hash(b"geez"),
+            super::uri::Protocol::Git,
+            super::uri::Path::empty(),
+        );
+        let url =
+            super::LocalUrl::from_urn(urn.clone(), librad::peer::PeerId::from(SecretKey::new()));
+        repo.remote(config::RAD_REMOTE, &url.to_string())?;
+
+        let state = super::local_state(tmp_dir.path().to_str().expect("path is not utf-8"))?;
+
+        assert_eq!(state.branches, vec![super::Branch("main".to_string())]);
+        assert_eq!(
+            state.remotes,
+            vec![super::Remote {
+                name: config::RAD_REMOTE.to_string(),
+                url: Some(url.to_string()),
+                rad: true,
+                urn: Some(urn),
+            }]
+        );
+        assert_eq!(state.status.head, Some("main".to_string()));
+        assert!(state.status.dirty);
+        assert_eq!(
+            state.status.divergence,
+            Some(super::Divergence {
+                ahead: 1,
+                behind: 0
+            })
+        );
+
+        Ok(())
+    }
 }
@@ -26,7 +26,7 @@ use crate::{
     project::{self, peer},
     seed::Seed,
     signer, source,
-    user::{verify as verify_user, User},
+    user::{verify as verify_user, verify_history, User},
 };
 
 pub mod error;
@@ -36,7 +36,7 @@ pub use error::Error;
 #[derive(Clone)]
 pub struct State {
     /// Internal handle on [`PeerApi`].
-    pub(crate) api: PeerApi<keys::SecretKey>,
+    pub(crate) api: PeerApi<signer::BoxedSigner>,
     /// Signer to sign artifacts generated by the user.
     signer: signer::BoxedSigner,
     /// A handle to the [`transport::Results`] which allows us to call [`transport::Results::wait`]
@@ -47,7 +47,7 @@ pub struct State {
 impl State {
     /// Create a new [`State`] given a [`PeerApi`].
     #[must_use]
-    pub fn new(api: PeerApi<keys::SecretKey>, signer: signer::BoxedSigner) -> Self {
+    pub fn new(api: PeerApi<signer::BoxedSigner>, signer: signer::BoxedSigner) -> Self {
         let paths = api.paths();
 
         // Register the transport so to use git2 to execute actions such as checkouts, fetch, and
@@ -170,6 +170,185 @@ impl State {
         Ok(user)
     }
 
+    /// Replace the [`crate::user::Profile`] of `owner` by creating a new signed revision of the
+    /// user entity, which is then also set as the default owner of this [`PeerApi`].
+    ///
+    /// # Errors
+    ///
+    ///   * The profile contains links or an avatar URL which are not valid URLs.
+    ///   * Fails to get the current revision of the user.
+    ///   * Fails to build or sign the new revision.
+    ///   * Fails to store the new revision or set it as the default `rad/self`.
+    pub async fn update_owner_profile(
+        &self,
+        owner: &User,
+        profile: crate::user::Profile,
+    ) -> Result<User, Error> {
+        let profile = profile.into_profile_ref()?;
+        self.revise_owner(owner, move |current| {
+            current
+                .to_builder()
+                .set_revision(current.revision() + 1)
+                .set_parent(current)
+                .set_profile(profile)
+                .build()
+        })
+        .await
+    }
+
+    /// Link another device to `owner` by adding the key of its `device` peer to a new signed
+    /// revision of the user entity. The device can then adopt the user via [`State::adopt_owner`].
+    ///
+    /// # Errors
+    ///
+    ///   * Fails to get the current revision of the user.
+    ///   * Fails to build or sign the new revision.
+    ///   * Fails to store the new revision or set it as the default `rad/self`.
+    pub async fn link_device(&self, owner: &User, device: PeerId) -> Result<User, Error> {
+        self.revise_owner(owner, move |current| {
+            current
+                .to_builder()
+                .set_revision(current.revision() + 1)
+                .set_parent(current)
+                .add_key(device.as_public_key().clone())
+                .build()
+        })
+        .await
+    }
+
+    /// Clone the user found at `url`, which another device of the same person linked this peer to
+    /// via [`State::link_device`], and make it the default owner of this [`PeerApi`].
+    ///
+    /// # Errors
+    ///
+    ///   * Failed to clone, fetch or verify the user.
+    ///   * The key of this peer is not part of the user's keys.
+    ///   * Fails to set the default `rad/self` for this `PeerApi`.
+    pub async fn adopt_owner<Addrs>(&self, url: RadUrl, addr_hints: Addrs) -> Result<User, Error>
+    where
+        Addrs: IntoIterator<Item = SocketAddr> + Send + 'static,
+    {
+        // An earlier attempt, e.g. before the device was linked, already cloned the user.
+        let urn = if self.get_user(url.urn.clone()).await.is_ok() {
+            let urn = url.urn.clone();
+            self.fetch(url, addr_hints).await?;
+            urn
+        } else {
+            self.clone_user(url, addr_hints).await?
+        };
+        let mut history = self.get_user_history(urn.clone()).await?;
+        let user = match history.pop() {
+            Some(user) => user,
+            None => self.get_user(urn.clone()).await?,
+        };
+        let key: keys::PublicKey = self.signer.public_key().into();
+        if !user.keys().contains(&key) {
+            return Err(Error::DeviceNotLinked {
+                urn,
+                peer_id: self.peer_id(),
+            });
+        }
+
+        let user = verify_history(user, history)?;
+        self.set_default_owner(user.clone()).await?;
+
+        Ok(user)
+    }
+
+    /// Serialise `owner` to JSON, to be restored on another machine with
+    /// [`State::restore_owner`]. Returns the latest revision together with all earlier revisions,
+    /// oldest first.
+    ///
+    /// # Errors
+    ///
+    ///   * Fails to get the revisions of the user.
+    ///   * Fails to serialise the user.
+    pub async fn export_owner(&self, owner: &User) -> Result<(String, Vec<String>), Error> {
+        let mut history = self.get_user_history(owner.urn()).await?;
+        let user = self.get_user(owner.urn()).await?;
+        history.retain(|ancestor| ancestor.revision() < user.revision());
+
+        Ok((
+            user.to_json_string()?,
+            history
+                .iter()
+                .map(user::User::to_json_string)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+
+    /// Store the `user` and its `ancestors` produced by [`State::export_owner`] on another
+    /// machine, and make it the default owner of this [`PeerApi`]. The revisions are stored in
+    /// order, so the user has the same history as on the original machine.
+    ///
+    /// # Errors
+    ///
+    ///   * Fails to deserialise or verify any of the revisions.
+    ///   * The key of this peer is not part of the user's keys.
+    ///   * Fails to store the user or set it as the default `rad/self`.
+    pub async fn restore_owner(&self, user: &str, ancestors: &[String]) -> Result<User, Error> {
+        let user = user::User::<entity::Draft>::from_json_str(user)?;
+        let ancestors = ancestors
+            .iter()
+            .map(|ancestor| user::User::<entity::Draft>::from_json_str(ancestor))
+            .collect::<Result<Vec<_>, _>>()?;
+        let key: keys::PublicKey = self.signer.public_key().into();
+        if !user.keys().contains(&key) {
+            return Err(Error::DeviceNotLinked {
+                urn: user.urn(),
+                peer_id: self.peer_id(),
+            });
+        }
+        let verified = verify_history(user.clone(), ancestors.clone())?;
+
+        self.api
+            .with_storage(move |storage| {
+                let mut revisions = ancestors.into_iter().chain(std::iter::once(user));
+                if let Some(first) = revisions.next() {
+                    let _ = storage.create_repo(&first)?;
+                }
+                for revision in revisions {
+                    storage.update_repo(&revision)?;
+                }
+                Ok::<_, Error>(())
+            })
+            .await??;
+        self.set_default_owner(verified.clone()).await?;
+
+        Ok(verified)
+    }
+
+    /// Create a new revision of `owner` with `revise`, sign and store it, and make it the default
+    /// owner of this [`PeerApi`].
+    async fn revise_owner<F>(&self, owner: &User, revise: F) -> Result<User, Error>
+    where
+        F: FnOnce(&user::User<entity::Draft>) -> Result<user::User<entity::Draft>, entity::Error>,
+    {
+        let mut history = self.get_user_history(owner.urn()).await?;
+        let current = self.get_user(owner.urn()).await?;
+        history.retain(|ancestor| ancestor.revision() < current.revision());
+        history.push(current.clone());
+        let mut user = revise(&current)?;
+        user.sign_owned(&self.signer)?;
+
+        let user = self
+            .api
+            .with_storage(move |storage| {
+                storage.update_repo(&user)?;
+                log::debug!(
+                    "Updated user '{}' to revision {}",
+                    user.urn(),
+                    user.revision()
+                );
+                Ok::<_, Error>(user)
+            })
+            .await??;
+        let user = verify_history(user, history)?;
+        self.set_default_owner(user.clone()).await?;
+
+        Ok(user)
+    }
+
     /// Given some hints as to where you might find it, get the urn of the project found at `url`.
     ///
     /// # Errors
@@ -350,6 +529,53 @@ impl State {
             .await??)
     }
 
+    /// Get all revisions of the user found at `urn`, oldest first, by walking the history of its
+    /// `rad/id`. Every commit in that history holds one revision of the user entity.
+    ///
+    /// # Errors
+    ///
+    ///   * The monorepo can't be opened or the user has no `rad/id`.
+    ///   * A revision can't be read or deserialised.
+    pub async fn get_user_history(
+        &self,
+        urn: RadUrn,
+    ) -> Result<Vec<user::User<entity::Draft>>, Error> {
+        let monorepo = self.monorepo();
+        tokio::task::spawn_blocking(move || {
+            let repo = git2::Repository::open(monorepo)?;
+            let mut commit = repo
+                .find_reference(&format!("refs/namespaces/{}/refs/rad/id", urn.id))?
+                .peel_to_commit()?;
+            let mut history = vec![];
+            loop {
+                let blob = commit
+                    .tree()?
+                    .iter()
+                    .find_map(|entry| entry.to_object(&repo).ok()?.into_blob().ok())
+                    .ok_or_else(|| {
+                        git2::Error::from_str(&format!(
+                            "revision {} of '{}' holds no entity",
+                            commit.id(),
+                            urn
+                        ))
+                    })?;
+                history.push(user::User::<entity::Draft>::from_json_str(
+                    &String::from_utf8_lossy(blob.content()),
+                )?);
+
+                match commit.parents().next() {
+                    Some(parent) => commit = parent,
+                    None => break,
+                }
+            }
+            history.reverse();
+
+            Ok(history)
+        })
+        .await
+        .expect("Task to read the user history was aborted")
+    }
+
     /// Fetch any updates at the given `RadUrl`, providing address hints if we have them.
     ///
     /// # Errors
@@ -532,6 +758,26 @@ impl State {
         Ok(meta)
     }
 
+    /// Check if [`State::init_project`] would succeed for `project`, reporting every problem found
+    /// with its working copy at once. Neither the monorepo nor the working copy are touched.
+    ///
+    /// # Errors
+    ///
+    /// Will error if:
+    ///     * The signing of the project metadata fails.
+    pub fn validate_project(
+        &self,
+        owner: &User,
+        project: &project::Create,
+    ) -> Result<Vec<project::create::validation::Error>, Error> {
+        let mut meta = project.build(owner, self.signer.public_key().into())?;
+        meta.sign_by_user(&self.signer, owner)?;
+
+        let url = LocalUrl::from_urn(meta.urn(), self.api.peer_id());
+
+        Ok(project.check(&url))
+    }
+
     /// Create a [`user::User`] with the provided `handle`. This assumes that you are creating a
     /// user that uses the secret key the `PeerApi` was configured with.
     ///
@@ -597,7 +843,9 @@ impl State {
         Ok(res)
     }
 
-    /// Get the [`user::User`]s that are tracking this project, including their [`PeerId`].
+    /// Get the [`user::User`]s that are tracking this project, together with the [`PeerId`]s of
+    /// their devices. Peers that are tracked but not replicated yet are listed on their own, see
+    /// [`peer::group_by_user`].
     ///
     /// # Errors
     ///
@@ -609,9 +857,9 @@ impl State {
     pub async fn tracked(
         &self,
         urn: RadUrn,
-    ) -> Result<Vec<project::Peer<peer::Status<user::User<entity::Draft>>>>, Error> {
+    ) -> Result<Vec<peer::Devices<peer::Status<user::User<entity::Draft>>>>, Error> {
         let project = self.get_project(urn.clone(), None).await?;
-        Ok(self
+        let peers = self
             .api
             .with_storage(move |storage| {
                 let mut peers = vec![];
@@ -638,7 +886,9 @@ impl State {
                 }
                 Ok::<_, Error>(peers)
             })
-            .await??)
+            .await??;
+
+        Ok(peer::group_by_user(peers, |user| user.urn()))
     }
 
     // TODO(xla): Account for projects not replicated but wanted.
@@ -679,9 +929,9 @@ impl State {
             status,
         });
 
-        let mut remotes = self.tracked(urn).await?;
-
-        peers.append(&mut remotes);
+        for devices in self.tracked(urn).await? {
+            peers.extend(devices.remotes());
+        }
 
         Ok(peers)
     }
@@ -692,17 +942,26 @@ impl State {
     ///
     /// The `peer_id` is from which peer we wish to base our checkout from.
     ///
+    /// The `target` is the branch, tag or commit of that peer to check out, the project's default
+    /// branch if `None`.
+    ///
+    /// If the `destination` already holds a working copy of the project, it is updated instead,
+    /// see [`project::Checkout::update`].
+    ///
     /// # Errors
     ///
     /// * if the project can't be found
     /// * if the include file creation fails
     /// * if the clone of the working copy fails
+    /// * if the `target` can't be found
+    /// * if the update of an existing working copy fails
     pub async fn checkout<P>(
         &self,
         urn: RadUrn,
         peer_id: P,
         destination: PathBuf,
-    ) -> Result<PathBuf, Error>
+        target: Option<project::checkout::Target>,
+    ) -> Result<project::checkout::Outcome, Error>
     where
         P: Into<Option<PeerId>> + Send + 'static,
     {
@@ -714,6 +973,7 @@ impl State {
             urn: proj.urn(),
             name: proj.name().to_string(),
             default_branch,
+            target,
             path: destination,
             include_path,
         };
@@ -737,18 +997,18 @@ impl State {
             },
         };
 
-        let path = {
+        let outcome = {
             let results = self.transport_results();
-            let path =
+            let outcome =
                 tokio::task::spawn_blocking(move || checkout.run(ownership).map_err(Error::from))
                     .await
                     .expect("blocking checkout failed")?;
 
             Self::process_transport_results(&results)?;
-            path
+            outcome
         };
 
-        Ok(path)
+        Ok(outcome)
     }
 
     /// Prepare the include file for the given `project` with the latest tracked peers.
@@ -764,7 +1024,14 @@ impl State {
             local_url,
             tracked
                 .into_iter()
-                .filter_map(|peer| project::Peer::replicated_remote(peer).map(|(p, u)| (u, p))),
+                .filter_map(peer::Devices::replicated)
+                .flat_map(|devices| {
+                    let user = devices.user;
+                    devices
+                        .peer_ids
+                        .into_iter()
+                        .map(move |peer_id| (user.clone(), peer_id))
+                }),
         )?;
         let include_path = include.file_path();
         log::info!("creating include file @ '{:?}'", include_path);
@@ -790,7 +1057,7 @@ mod test {
 
     use librad::{git::storage, git_ext::OneLevel, keys::SecretKey, reflike};
 
-    use crate::{config, control, project, signer};
+    use crate::{config, control, project, signer, user};
 
     use super::{Error, State};
 
@@ -821,7 +1088,7 @@ mod test {
         let tmp_dir = tempfile::tempdir().expect("failed to create temdir");
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::from(key);
-        let config = config::default(key, tmp_dir.path())?;
+        let config = config::default(signer.clone(), tmp_dir.path())?;
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
 
@@ -838,7 +1105,7 @@ mod test {
         let repo_path = tmp_dir.path().join("radicle");
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::from(key);
-        let config = config::default(key, tmp_dir.path())?;
+        let config = config::default(signer.clone(), tmp_dir.path())?;
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
 
@@ -860,7 +1127,7 @@ mod test {
         let repo_path = repo_path.join("radicalise");
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::from(key);
-        let config = config::default(key, tmp_dir.path())?;
+        let config = config::default(signer.clone(), tmp_dir.path())?;
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
 
@@ -880,7 +1147,7 @@ mod test {
         let tmp_dir = tempfile::tempdir().expect("failed to create temdir");
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::from(key);
-        let config = config::default(key, tmp_dir.path())?;
+        let config = config::default(signer.clone(), tmp_dir.path())?;
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
 
@@ -905,7 +1172,7 @@ mod test {
         let repo_path = tmp_dir.path().join("radicle");
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::from(key);
-        let config = config::default(key, tmp_dir.path())?;
+        let config = config::default(signer.clone(), tmp_dir.path())?;
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
 
@@ -936,7 +1203,7 @@ mod test {
 
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::from(key);
-        let config = config::default(key, tmp_dir.path())?;
+        let config = config::default(signer.clone(), tmp_dir.path())?;
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
 
@@ -972,7 +1239,7 @@ mod test {
         let tmp_dir = tempfile::tempdir().expect("failed to create temdir");
         let key = SecretKey::new();
         let signer = signer::BoxedSigner::from(key);
-        let config = config::default(key, tmp_dir.path())?;
+        let config = config::default(signer.clone(), tmp_dir.path())?;
         let (api, _run_loop) = config.try_into_peer().await?.accept()?;
         let state = State::new(api, signer);
 
@@ -992,4 +1259,90 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn restore_revised_owner() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir().expect("failed to create temdir");
+        let key = SecretKey::new();
+        let signer = signer::BoxedSigner::from(key);
+        let config = config::default(signer.clone(), tmp_dir.path().join("old"))?;
+        let (api, _run_loop) = config.try_into_peer().await?.accept()?;
+        let state = State::new(api, signer.clone());
+
+        let owner = state.init_owner("cloudhead").await?;
+        let owner = state
+            .update_owner_profile(
+                &owner,
+                user::Profile {
+                    display_name: Some("Alexis".to_string()),
+                    ..user::Profile::default()
+                },
+            )
+            .await?;
+        let owner = state
+            .update_owner_profile(
+                &owner,
+                user::Profile {
+                    bio: Some("radicle".to_string()),
+                    ..user::Profile::default()
+                },
+            )
+            .await?;
+        assert_eq!(owner.revision(), 3);
+        assert_eq!(state.get_user_history(owner.urn()).await?.len(), 3);
+
+        let (latest, ancestors) = state.export_owner(&owner).await?;
+        assert_eq!(ancestors.len(), 2);
+
+        let config = config::default(signer.clone(), tmp_dir.path().join("new"))?;
+        let (api, _run_loop) = config.try_into_peer().await?.accept()?;
+        let new_state = State::new(api, signer);
+        let restored = new_state.restore_owner(&latest, &ancestors).await?;
+        assert_eq!(restored.revision(), 3);
+        assert_eq!(new_state.get_user_history(owner.urn()).await?.len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_owner_profile_invalid_url() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir().expect("failed to create temdir");
+        let key = SecretKey::new();
+        let signer = signer::BoxedSigner::from(key);
+        let config = config::default(signer.clone(), tmp_dir.path())?;
+        let (api, _run_loop) = config.try_into_peer().await?.accept()?;
+        let state = State::new(api, signer);
+
+        let owner = state.init_owner("cloudhead").await?;
+        let res = state
+            .update_owner_profile(
+                &owner,
+                user::Profile {
+                    links: vec!["https://radicle.xyz".to_string(), "radicle".to_string()],
+                    ..user::Profile::default()
+                },
+            )
+            .await;
+        assert!(matches!(
+            res,
+            Err(Error::Profile(user::Error::InvalidLink { link, .. })) if link == "radicle"
+        ));
+
+        let res = state
+            .update_owner_profile(
+                &owner,
+                user::Profile {
+                    avatar_url: Some("avatar.png".to_string()),
+                    ..user::Profile::default()
+                },
+            )
+            .await;
+        assert!(matches!(
+            res,
+            Err(Error::Profile(user::Error::InvalidAvatarUrl { .. }))
+        ));
+        assert_eq!(state.get_user(owner.urn()).await?.revision(), 1);
+
+        Ok(())
+    }
 }
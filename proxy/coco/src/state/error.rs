@@ -48,6 +48,10 @@ pub enum Error {
     #[error(transparent)]
     PeerApi(#[from] net::peer::ApiError),
 
+    /// A profile can't be stored in the user entity.
+    #[error(transparent)]
+    Profile(#[from] crate::user::Error),
+
     /// Failed to parse a reference.
     #[error(transparent)]
     ReferenceName(#[from] librad::git_ext::reference::name::Error),
@@ -76,6 +80,15 @@ pub enum Error {
     #[error(transparent)]
     Verification(#[from] entity::HistoryVerificationError),
 
+    /// A user was to be adopted by a device whose key was not added to the user.
+    #[error("the key of '{peer_id}' is not linked to the user '{urn}'")]
+    DeviceNotLinked {
+        /// RadUrn of the user.
+        urn: RadUrn,
+        /// The peer adopting the user.
+        peer_id: librad::peer::PeerId,
+    },
+
     /// There were no references for a Browser to be initialised.
     #[error("we could not find a default branch for '{name}@{urn}'")]
     NoDefaultBranch {
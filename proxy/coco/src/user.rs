@@ -1,7 +1,10 @@
 //! Commonly used vesions of [`librad::meta::user::User`].
 
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
 use librad::{
-    meta::{entity, user},
+    meta::{entity, profile, user},
     uri::RadUrn,
 };
 
@@ -10,6 +13,108 @@ use crate::state;
 /// Export a verified [`user::User`] type.
 pub type User = user::User<entity::Verified>;
 
+/// Errors that occur when a [`Profile`] can't be stored in the user entity.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// One of the links is not a valid URL.
+    #[error("the link '{link}' is not a valid URL")]
+    InvalidLink {
+        /// The offending link.
+        link: String,
+        /// Reason the link failed to parse.
+        source: url::ParseError,
+    },
+
+    /// The avatar URL is not a valid URL.
+    #[error("the avatar URL '{url}' is not a valid URL")]
+    InvalidAvatarUrl {
+        /// The offending URL.
+        url: String,
+        /// Reason the URL failed to parse.
+        source: url::ParseError,
+    },
+}
+
+/// User maintained information beyond the handle, stored as part of the user entity. Every edit
+/// results in a new signed revision of the entity.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    /// Name to display instead of the handle.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Address to get in touch with the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Short free-form description.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    /// Links to websites and other profiles of the user.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<String>,
+    /// URL of a custom avatar image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+}
+
+impl Profile {
+    /// Read the profile of `user`, `None` if none was set or it only references an external
+    /// profile.
+    #[must_use]
+    pub fn of<ST>(user: &user::User<ST>) -> Option<Self>
+    where
+        ST: Clone,
+    {
+        match user.profile() {
+            Some(profile::ProfileRef::UserProfile(profile)) => Some(Self {
+                display_name: Some(profile.name.clone()).filter(|name| !name.is_empty()),
+                email: profile.email.clone(),
+                bio: profile.bio.clone(),
+                links: profile.urls.iter().map(ToString::to_string).collect(),
+                avatar_url: match &profile.img {
+                    Some(profile::ProfileImage::Url(url)) => Some(url.to_string()),
+                    _ => None,
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    /// Convert into the representation stored in the user entity.
+    ///
+    /// # Errors
+    ///
+    /// * If one of the links is not a valid URL.
+    /// * If the avatar URL is not a valid URL.
+    pub fn into_profile_ref(self) -> Result<profile::ProfileRef, Error> {
+        let img = self
+            .avatar_url
+            .map(|url| {
+                url.parse()
+                    .map(profile::ProfileImage::Url)
+                    .map_err(|source| Error::InvalidAvatarUrl { url, source })
+            })
+            .transpose()?;
+        let urls = self
+            .links
+            .into_iter()
+            .map(|link| {
+                link.parse()
+                    .map_err(|source| Error::InvalidLink { link, source })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(profile::ProfileRef::UserProfile(profile::UserProfile {
+            name: self.display_name.unwrap_or_default(),
+            img,
+            bio: self.bio,
+            geo: None,
+            email: self.email,
+            urls,
+        }))
+    }
+}
+
 /// Verify a user using a fake resolver that resolves the user to itself.
 ///
 /// TODO(finto): Should not live here permanently, because resolvers should solve this verification.
@@ -18,26 +123,65 @@ pub type User = user::User<entity::Verified>;
 ///
 /// If any of the verification steps fail
 pub fn verify(user: user::User<entity::Draft>) -> Result<User, state::Error> {
-    let fake_resolver = FakeUserResolver(user.clone());
+    check_history(user, Vec::new())
+}
+
+/// Verify `user` together with all its `ancestors`, i.e. its earlier revisions ordered oldest
+/// first. Every revision is verified against the ones before it.
+///
+/// # Errors
+///
+/// If any of the verification steps fail for any of the revisions
+pub fn verify_history(
+    user: user::User<entity::Draft>,
+    ancestors: Vec<user::User<entity::Draft>>,
+) -> Result<User, state::Error> {
+    let mut history = Vec::with_capacity(ancestors.len());
+    for ancestor in ancestors {
+        let _verified = check_history(ancestor.clone(), history.clone())?;
+        history.push(ancestor);
+    }
+
+    check_history(user, history)
+}
+
+/// Check the history status of `user` with a fake resolver that knows about `history`.
+fn check_history(
+    user: user::User<entity::Draft>,
+    history: Vec<user::User<entity::Draft>>,
+) -> Result<User, state::Error> {
+    let fake_resolver = FakeUserResolver {
+        user: user.clone(),
+        history,
+    };
     let verified_user = user.check_history_status(&fake_resolver, &fake_resolver)?;
     Ok(verified_user)
 }
 
-/// Acting as a fake resolver where a User resolves to itself.
+/// Acting as a fake resolver where a User resolves to itself, or to one of its earlier revisions
+/// when asked for it. Resolving any other revision fails.
 /// This allows us to check the history status of a single User.
 /// TODO(finto): Remove this once Resolvers are complete.
-struct FakeUserResolver(user::User<entity::Draft>);
+struct FakeUserResolver {
+    user: user::User<entity::Draft>,
+    history: Vec<user::User<entity::Draft>>,
+}
 
 impl entity::Resolver<user::User<entity::Draft>> for FakeUserResolver {
     fn resolve(&self, _uri: &RadUrn) -> Result<user::User<entity::Draft>, entity::Error> {
-        Ok(self.0.clone())
+        Ok(self.user.clone())
     }
 
     fn resolve_revision(
         &self,
-        _uri: &RadUrn,
-        _revision: u64,
+        uri: &RadUrn,
+        revision: u64,
     ) -> Result<user::User<entity::Draft>, entity::Error> {
-        Ok(self.0.clone())
+        self.history
+            .iter()
+            .chain(std::iter::once(&self.user))
+            .find(|known| known.revision() == revision)
+            .cloned()
+            .ok_or_else(|| entity::Error::RevisionResolutionFailed(uri.clone(), revision))
     }
 }
@@ -93,7 +93,7 @@ pub async fn build_peer(
     let key = SecretKey::new();
     let signer = signer::BoxedSigner::from(key);
     let store = kv::Store::new(kv::Config::new(tmp_dir.path().join("store")))?;
-    let conf = config::default(key, tmp_dir.path())?;
+    let conf = config::default(signer.clone(), tmp_dir.path())?;
     let (peer, state) = coco::into_peer_state(conf, signer.clone(), store, run_config).await?;
 
     Ok((peer, state))
@@ -112,7 +112,7 @@ pub async fn build_peer_with_seeds(
     let paths = Paths::from_root(tmp_dir.path())?;
     let conf = config::configure(
         paths,
-        key,
+        signer.clone(),
         *config::LOCALHOST_ANY,
         config::static_seed_discovery(seeds),
     );
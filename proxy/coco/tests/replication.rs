@@ -8,13 +8,7 @@ use tokio::time::timeout;
 use librad::uri;
 use radicle_surf::vcs::git::git2;
 
-use coco::{
-    config,
-    peer::run_config,
-    project::{peer, Peer},
-    seed::Seed,
-    RunConfig,
-};
+use coco::{config, peer::run_config, project::peer, seed::Seed, RunConfig};
 
 #[macro_use]
 mod common;
@@ -69,17 +63,20 @@ async fn can_clone_project() -> Result<(), Box<dyn std::error::Error>> {
             .tracked(project.urn())
             .await?
             .into_iter()
-            .map(|peer| peer.map(|status| status.map(|user| user.name().to_string())))
+            .map(|devices| peer::Devices {
+                user: devices.user.map(|user| user.name().to_string()),
+                peer_ids: devices.peer_ids,
+            })
             .collect::<Vec<_>>();
-        have.sort_by(|p1, p2| p1.status().cmp(p2.status()));
+        have.sort_by(|d1, d2| d1.user.cmp(&d2.user));
         let want: Vec<_> = vec![
-            coco::project::Peer::Remote {
-                peer_id: another_peer,
-                status: peer::Status::NotReplicated,
+            peer::Devices {
+                user: peer::Status::NotReplicated,
+                peer_ids: vec![another_peer],
             },
-            coco::project::Peer::Remote {
-                peer_id: alice_state.peer_id(),
-                status: peer::Status::replicated(peer::Role::Maintainer, alice.name().to_string()),
+            peer::Devices {
+                user: peer::Status::replicated(peer::Role::Maintainer, alice.name().to_string()),
+                peer_ids: vec![alice_state.peer_id()],
             },
         ];
         assert_eq!(have, want);
@@ -335,8 +332,9 @@ async fn can_create_working_copy_of_peer() -> Result<(), Box<dyn std::error::Err
     let commit_id = {
         let alice_peer_id = alice_state.peer_id();
         let path = bob_state
-            .checkout(project.urn(), alice_peer_id, bob_repo_path)
-            .await?;
+            .checkout(project.urn(), alice_peer_id, bob_repo_path, None)
+            .await?
+            .into_path();
 
         let repo = git2::Repository::open(path)?;
         let oid = repo
@@ -383,8 +381,9 @@ async fn can_create_working_copy_of_peer() -> Result<(), Box<dyn std::error::Err
     let path = {
         let alice_peer_id = alice_state.peer_id();
         eve_state
-            .checkout(project.urn(), alice_peer_id, eve_repo_path)
+            .checkout(project.urn(), alice_peer_id, eve_repo_path, None)
             .await?
+            .into_path()
     };
 
     let repo = git2::Repository::open(path)?;
@@ -427,10 +426,9 @@ async fn track_peer() -> Result<(), Box<dyn std::error::Error>> {
     assert_event!(alice_events, coco::PeerEvent::GossipFetched { .. })?;
 
     let tracked = alice_state.tracked(project.urn()).await?;
-    assert!(tracked.iter().any(|peer| match peer {
-        Peer::Remote { peer_id, status } =>
-            *peer_id == bob_state.peer_id() && matches!(status, peer::Status::Replicated(_)),
-        _ => false,
+    assert!(tracked.iter().any(|devices| {
+        devices.peer_ids.contains(&bob_state.peer_id())
+            && matches!(devices.user, peer::Status::Replicated(_))
     }));
 
     Ok(())
@@ -3,6 +3,15 @@ import { get as getStore, writable } from "svelte/store";
 
 export const backendAddressStore = writable("localhost:17246");
 
+// Header the proxy passes the CSRF token of the session in and expects it back
+// in on every mutating request authenticated with the auth cookie.
+const CSRF_HEADER = "x-csrf-token";
+
+// CSRF token of the current session, `null` if there is no session.
+export const csrfTokenStore = writable<string | null>(null);
+
+const MUTATING_METHODS = ["POST", "PUT", "DELETE"];
+
 interface Options {
   query?: Record<string, unknown>;
   signal?: AbortSignal;
@@ -39,17 +48,37 @@ const request = (endpoint: string, init?: Init): Request => {
     endpoint = `${endpoint}?${qs.stringify(init.query)}`;
   }
 
+  const headers: Record<string, string> = {
+    "Content-Type": "application/json",
+  };
+  const csrfToken = getStore(csrfTokenStore);
+  if (
+    csrfToken !== null &&
+    init !== undefined &&
+    MUTATING_METHODS.includes(init.method)
+  ) {
+    headers[CSRF_HEADER] = csrfToken;
+  }
+
   return new Request(`http://${getStore(backendAddressStore)}/v1/${endpoint}`, {
-    headers: {
-      "Content-Type": "application/json",
-    },
     credentials: "include",
     ...init,
+    headers: { ...headers, ...init?.headers },
   });
 };
 
+// Keep the CSRF token passed with a new session, e.g. by unsealing the
+// keystore.
+const storeCsrfToken = (res: Response): void => {
+  const csrfToken = res.headers.get(CSRF_HEADER);
+  if (csrfToken !== null && csrfToken !== "") {
+    csrfTokenStore.set(csrfToken);
+  }
+};
+
 const http = async <T>(req: RequestInfo): Promise<T> => {
   const res = await fetch(req);
+  storeCsrfToken(res);
   const body = await res.json();
 
   // For non-success status codes we throw the body as it carries the error type.
@@ -62,6 +91,7 @@ const http = async <T>(req: RequestInfo): Promise<T> => {
 
 const noContent = async (req: RequestInfo): Promise<null> => {
   const res = await fetch(req);
+  storeCsrfToken(res);
 
   if (res.status !== 204) {
     const body = await res.json();
@@ -116,6 +146,13 @@ export const set = async <T>(
     })
   );
 
+// Load the CSRF token of the session the auth cookie belongs to, e.g. after the
+// app was reloaded with an unsealed keystore.
+export const fetchCsrfToken = async (): Promise<void> => {
+  const { token } = await get<{ token: string | null }>("keystore/csrf");
+  csrfTokenStore.set(token);
+};
+
 const delay = (delay: number) => {
   return new Promise((resolve, _reject) => {
     setTimeout(resolve, delay);
@@ -104,13 +104,17 @@ const fetchSession = async (): Promise<void> => {
       100,
       50
     );
+    // Mutating requests are rejected without the CSRF token of the session.
+    await api.fetchCsrfToken();
     sessionStore.success({ status: Status.UnsealedSession, ...ses });
   } catch (err) {
     if (err instanceof api.ResponseError) {
       if (err.response.status === 404) {
+        api.csrfTokenStore.set(null);
         sessionStore.success({ status: Status.NoSession });
         return;
       } else if (err.response.status === 403) {
+        api.csrfTokenStore.set(null);
         sessionStore.success({ status: Status.SealedSession });
         return;
       }